@@ -0,0 +1,467 @@
+use crate::distributions::{bivariate_norm_cdf, norm_cdf, norm_pdf};
+use crate::{BlackScholesModel, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+const TOLERANCE: f64 = 1e-12;
+const MAX_ITERATIONS: usize = 100;
+
+// AmericanPrice is the value of an american option together with its early exercise
+// boundary, the underlying price at which exercising becomes optimal (calls are
+// exercised above it, puts below it)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmericanPrice {
+    pub price: f64,    // option value ($$$ per share)
+    pub boundary: f64, // critical underlying price ($$$ per share)
+}
+
+// barone_adesi_whaley prices the american version of the option with the quadratic
+// approximation of Barone-Adesi and Whaley (1987)
+//
+// Calls are never exercised early when the dividend yield is not positive and neither
+// are puts when the interest rate is not positive, the boundary is infinite (calls) or
+// zero (puts) then
+pub fn barone_adesi_whaley(model: &BlackScholesModel) -> MathResult<AmericanPrice> {
+    if let Some(expired) = at_expiration(model) {
+        return Ok(expired);
+    }
+    let rate = model.interest_rate;
+    let carry = rate - model.dividend.unwrap_or_default();
+    let variance = model.volatility.powi(2);
+    let deviation = model.volatility * model.time_to_expire.sqrt();
+    let carry_discount = E.powf((carry - rate) * model.time_to_expire);
+    let n = 2.0 * carry / variance;
+    let m = 2.0 * rate / variance;
+    let k = 1.0 - E.powf(-rate * model.time_to_expire);
+    let european = |stock: f64| BlackScholesModel { stock, ..*model };
+
+    match model.opt {
+        OptionKind::Call => {
+            if carry >= rate {
+                return Ok(AmericanPrice {
+                    price: model.price()?,
+                    boundary: f64::INFINITY,
+                });
+            }
+            let q2 = (-(n - 1.0) + ((n - 1.0).powi(2) + 4.0 * m / k).sqrt()) / 2.0;
+
+            // seed value of Barone-Adesi and Whaley, refined with Newton's method
+            let q2_infinity = (-(n - 1.0) + ((n - 1.0).powi(2) + 4.0 * m).sqrt()) / 2.0;
+            let infinity = model.strike / (1.0 - 1.0 / q2_infinity);
+            let h2 = -(carry * model.time_to_expire + 2.0 * deviation) * model.strike
+                / (infinity - model.strike);
+            let mut critical = model.strike + (infinity - model.strike) * (1.0 - E.powf(h2));
+
+            let mut converged = false;
+            for _ in 0..MAX_ITERATIONS {
+                let at_critical = european(critical);
+                let (d1, _) = at_critical.d1_d2();
+                let rhs =
+                    at_critical.price()? + (1.0 - carry_discount * norm_cdf(d1)) * critical / q2;
+                let lhs = critical - model.strike;
+                if ((lhs - rhs) / model.strike).abs() < TOLERANCE {
+                    converged = true;
+                    break;
+                }
+                let slope = carry_discount * norm_cdf(d1) * (1.0 - 1.0 / q2)
+                    + (1.0 - carry_discount * norm_pdf(d1) / deviation) / q2;
+                critical = (model.strike + rhs - slope * critical) / (1.0 - slope);
+            }
+            if !converged {
+                return Err(MathError::NoConvergence(MAX_ITERATIONS));
+            }
+
+            if model.stock >= critical {
+                return Ok(AmericanPrice {
+                    price: model.stock - model.strike,
+                    boundary: critical,
+                });
+            }
+            let (d1, _) = european(critical).d1_d2();
+            let a2 = critical / q2 * (1.0 - carry_discount * norm_cdf(d1));
+            Ok(AmericanPrice {
+                price: model.price()? + a2 * (model.stock / critical).powf(q2),
+                boundary: critical,
+            })
+        }
+        OptionKind::Put => {
+            if rate <= 0.0 {
+                return Ok(AmericanPrice {
+                    price: model.price()?,
+                    boundary: 0.0,
+                });
+            }
+            let q1 = (-(n - 1.0) - ((n - 1.0).powi(2) + 4.0 * m / k).sqrt()) / 2.0;
+
+            let q1_infinity = (-(n - 1.0) - ((n - 1.0).powi(2) + 4.0 * m).sqrt()) / 2.0;
+            let infinity = model.strike / (1.0 - 1.0 / q1_infinity);
+            let h1 = (carry * model.time_to_expire - 2.0 * deviation) * model.strike
+                / (model.strike - infinity);
+            let mut critical = infinity + (model.strike - infinity) * E.powf(h1);
+
+            let mut converged = false;
+            for _ in 0..MAX_ITERATIONS {
+                let at_critical = european(critical);
+                let (d1, _) = at_critical.d1_d2();
+                let rhs =
+                    at_critical.price()? - (1.0 - carry_discount * norm_cdf(-d1)) * critical / q1;
+                let lhs = model.strike - critical;
+                if ((lhs - rhs) / model.strike).abs() < TOLERANCE {
+                    converged = true;
+                    break;
+                }
+                let slope = -carry_discount * norm_cdf(-d1) * (1.0 - 1.0 / q1)
+                    - (1.0 + carry_discount * norm_pdf(-d1) / deviation) / q1;
+                critical = (model.strike - rhs + slope * critical) / (1.0 + slope);
+            }
+            if !converged {
+                return Err(MathError::NoConvergence(MAX_ITERATIONS));
+            }
+
+            if model.stock <= critical {
+                return Ok(AmericanPrice {
+                    price: model.strike - model.stock,
+                    boundary: critical,
+                });
+            }
+            let (d1, _) = european(critical).d1_d2();
+            let a1 = -critical / q1 * (1.0 - carry_discount * norm_cdf(-d1));
+            Ok(AmericanPrice {
+                price: model.price()? + a1 * (model.stock / critical).powf(q1),
+                boundary: critical,
+            })
+        }
+    }
+}
+
+// bjerksund_stensland prices the american version of the option with the two-step
+// flat boundary approximation of Bjerksund and Stensland (2002)
+//
+// Puts are priced as calls through the put-call transformation
+// P(S, K, T, r, b) = C(K, S, T, r - b, -b), where b is the cost of carry
+pub fn bjerksund_stensland(model: &BlackScholesModel) -> MathResult<AmericanPrice> {
+    if let Some(expired) = at_expiration(model) {
+        return Ok(expired);
+    }
+    let rate = model.interest_rate;
+    let carry = rate - model.dividend.unwrap_or_default();
+
+    match model.opt {
+        OptionKind::Call => {
+            if carry >= rate {
+                return Ok(AmericanPrice {
+                    price: model.price()?,
+                    boundary: f64::INFINITY,
+                });
+            }
+            let call = Call {
+                stock: model.stock,
+                strike: model.strike,
+                rate,
+                carry,
+                volatility: model.volatility,
+                time_to_expire: model.time_to_expire,
+            };
+            let (price, boundary) = call.price();
+            Ok(AmericanPrice { price, boundary })
+        }
+        OptionKind::Put => {
+            if rate <= 0.0 {
+                return Ok(AmericanPrice {
+                    price: model.price()?,
+                    boundary: 0.0,
+                });
+            }
+            let call = Call {
+                stock: model.strike,
+                strike: model.stock,
+                rate: rate - carry,
+                carry: -carry,
+                volatility: model.volatility,
+                time_to_expire: model.time_to_expire,
+            };
+            let (price, _) = call.price();
+            // the boundary scales with the strike, a put is exercised when the
+            // transformed call's stock (the strike) reaches the boundary of the
+            // transformed call struck at the current stock
+            let (_, unit_boundary) = Call {
+                stock: 1.0,
+                strike: 1.0,
+                ..call
+            }
+            .price();
+            Ok(AmericanPrice {
+                price,
+                boundary: model.strike / unit_boundary,
+            })
+        }
+    }
+}
+
+fn at_expiration(model: &BlackScholesModel) -> Option<AmericanPrice> {
+    if model.time_to_expire != 0.0 {
+        return None;
+    }
+    let price = match model.opt {
+        OptionKind::Call => (model.stock - model.strike).max(0.0),
+        OptionKind::Put => (model.strike - model.stock).max(0.0),
+    };
+    Some(AmericanPrice {
+        price,
+        boundary: model.strike,
+    })
+}
+
+// Call holds the generalized inputs of the Bjerksund-Stensland call formula
+#[derive(Debug, Clone, Copy)]
+struct Call {
+    stock: f64,
+    strike: f64,
+    rate: f64,
+    carry: f64,
+    volatility: f64,
+    time_to_expire: f64,
+}
+
+impl Call {
+    // price returns the call value and the flat exercise boundary of the first period
+    fn price(&self) -> (f64, f64) {
+        let (s, x, t) = (self.stock, self.strike, self.time_to_expire);
+        let (r, b, variance) = (self.rate, self.carry, self.volatility.powi(2));
+
+        let t1 = 0.5 * (5f64.sqrt() - 1.0) * t;
+        let beta =
+            (0.5 - b / variance) + ((b / variance - 0.5).powi(2) + 2.0 * r / variance).sqrt();
+        let b_infinity = beta / (beta - 1.0) * x;
+        let b_zero = x.max(r / (r - b) * x);
+        let h = |time: f64| {
+            -(b * time + 2.0 * self.volatility * time.sqrt()) * x.powi(2)
+                / ((b_infinity - b_zero) * b_zero)
+        };
+        let i1 = b_zero + (b_infinity - b_zero) * (1.0 - E.powf(h(t1)));
+        let i2 = b_zero + (b_infinity - b_zero) * (1.0 - E.powf(h(t)));
+        if s >= i2 {
+            return (s - x, i2);
+        }
+        let alpha1 = (i1 - x) * i1.powf(-beta);
+        let alpha2 = (i2 - x) * i2.powf(-beta);
+
+        let price = alpha2 * s.powf(beta) - alpha2 * self.phi(t1, beta, i2, i2)
+            + self.phi(t1, 1.0, i2, i2)
+            - self.phi(t1, 1.0, i1, i2)
+            - x * self.phi(t1, 0.0, i2, i2)
+            + x * self.phi(t1, 0.0, i1, i2)
+            + alpha1 * self.phi(t1, beta, i1, i2)
+            - alpha1 * self.psi(beta, i1, i2, i1, t1)
+            + self.psi(1.0, i1, i2, i1, t1)
+            - self.psi(1.0, x, i2, i1, t1)
+            - x * self.psi(0.0, i1, i2, i1, t1)
+            + x * self.psi(0.0, x, i2, i1, t1);
+        (price, i2)
+    }
+
+    fn phi(&self, time: f64, gamma: f64, h: f64, i: f64) -> f64 {
+        let (s, r, b, sigma) = (self.stock, self.rate, self.carry, self.volatility);
+        let deviation = sigma * time.sqrt();
+        let lambda = (-r + gamma * b + 0.5 * gamma * (gamma - 1.0) * sigma.powi(2)) * time;
+        let d = -((s / h).ln() + (b + (gamma - 0.5) * sigma.powi(2)) * time) / deviation;
+        let kappa = 2.0 * b / sigma.powi(2) + 2.0 * gamma - 1.0;
+        E.powf(lambda)
+            * s.powf(gamma)
+            * (norm_cdf(d) - (i / s).powf(kappa) * norm_cdf(d - 2.0 * (i / s).ln() / deviation))
+    }
+
+    fn psi(&self, gamma: f64, h: f64, i2: f64, i1: f64, t1: f64) -> f64 {
+        let (s, r, b, sigma, t2) = (
+            self.stock,
+            self.rate,
+            self.carry,
+            self.volatility,
+            self.time_to_expire,
+        );
+        let drift = b + (gamma - 0.5) * sigma.powi(2);
+        let (dev1, dev2) = (sigma * t1.sqrt(), sigma * t2.sqrt());
+
+        let e1 = ((s / i1).ln() + drift * t1) / dev1;
+        let e2 = ((i2.powi(2) / (s * i1)).ln() + drift * t1) / dev1;
+        let e3 = ((s / i1).ln() - drift * t1) / dev1;
+        let e4 = ((i2.powi(2) / (s * i1)).ln() - drift * t1) / dev1;
+        let f1 = ((s / h).ln() + drift * t2) / dev2;
+        let f2 = ((i2.powi(2) / (s * h)).ln() + drift * t2) / dev2;
+        let f3 = ((i1.powi(2) / (s * h)).ln() + drift * t2) / dev2;
+        let f4 = ((s * i1.powi(2) / (h * i2.powi(2))).ln() + drift * t2) / dev2;
+
+        let rho = (t1 / t2).sqrt();
+        let lambda = -r + gamma * b + 0.5 * gamma * (gamma - 1.0) * sigma.powi(2);
+        let kappa = 2.0 * b / sigma.powi(2) + 2.0 * gamma - 1.0;
+
+        E.powf(lambda * t2)
+            * s.powf(gamma)
+            * (bivariate_norm_cdf(-e1, -f1, rho)
+                - (i2 / s).powf(kappa) * bivariate_norm_cdf(-e2, -f2, rho)
+                - (i1 / s).powf(kappa) * bivariate_norm_cdf(-e3, -f3, -rho)
+                + (i1 / i2).powf(kappa) * bivariate_norm_cdf(-e4, -f4, -rho))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binomial::{BinomialModel, ExerciseStyle, Lattice};
+
+    // Haug, The Complete Guide to Option Pricing Formulas, american options with
+    // K = 100, r = 10% and cost of carry b = 0 (dividend yield 10%):
+    // (time to expiration, volatility, [values for S = 90, 100, 110])
+    const HAUG_CASES: [(f64, f64); 6] = [
+        (0.1, 0.15),
+        (0.1, 0.25),
+        (0.1, 0.35),
+        (0.5, 0.15),
+        (0.5, 0.25),
+        (0.5, 0.35),
+    ];
+    const BAW_CALLS: [[f64; 3]; 6] = [
+        [0.0206, 1.8771, 10.0089],
+        [0.3159, 3.1280, 10.3919],
+        [0.9495, 4.3777, 11.1679],
+        [0.8208, 4.0842, 10.8087],
+        [2.7437, 6.8015, 13.0170],
+        [5.0063, 9.5106, 15.5689],
+    ];
+    const BAW_PUTS: [[f64; 3]; 6] = [
+        [10.0000, 1.8770, 0.0410],
+        [10.2533, 3.1277, 0.4562],
+        [10.8787, 4.3777, 1.2402],
+        [10.5595, 4.0842, 1.0822],
+        [12.4419, 6.8014, 3.3226],
+        [14.6945, 9.5104, 5.8823],
+    ];
+    // 2.7180 is a price, not an approximation of e
+    #[allow(clippy::approx_constant)]
+    const BS2002_CALLS: [[f64; 3]; 6] = [
+        [0.0205, 1.8757, 10.0000],
+        [0.3151, 3.1256, 10.3725],
+        [0.9479, 4.3746, 11.1578],
+        [0.8099, 4.0628, 10.7898],
+        [2.7180, 6.7661, 12.9814],
+        [4.9665, 9.4608, 15.5137],
+    ];
+    const BS2002_PUTS: [[f64; 3]; 6] = [
+        [10.0000, 1.8757, 0.0408],
+        [10.2280, 3.1256, 0.4552],
+        [10.8663, 4.3746, 1.2383],
+        [10.5400, 4.0628, 1.0689],
+        [12.4097, 6.7661, 3.2932],
+        [14.6445, 9.4608, 5.8374],
+    ];
+    const STOCKS: [f64; 3] = [90.0, 100.0, 110.0];
+
+    fn model(opt: OptionKind, stock: f64, volatility: f64, time: f64) -> BlackScholesModel {
+        BlackScholesModel::new(opt, 100.0, stock, 0.1, volatility, time, Some(0.1))
+    }
+
+    fn assert_table(
+        opt: OptionKind,
+        table: &[[f64; 3]; 6],
+        pricer: fn(&BlackScholesModel) -> MathResult<AmericanPrice>,
+        tolerance: f64,
+    ) {
+        for (&(time, volatility), row) in HAUG_CASES.iter().zip(table.iter()) {
+            for (&stock, &expected) in STOCKS.iter().zip(row.iter()) {
+                let result = pricer(&model(opt, stock, volatility, time)).unwrap().price;
+                assert!(
+                    (result - expected).abs() < tolerance,
+                    "{:?} S = {} T = {} vol = {}: {} is not {}",
+                    opt,
+                    stock,
+                    time,
+                    volatility,
+                    result,
+                    expected
+                );
+            }
+        }
+    }
+
+    // the published Barone-Adesi-Whaley figures drift from the exactly solved
+    // critical price by a few 1e-4, up to 3e-3 right below the exercise boundary
+    #[test]
+    fn barone_adesi_whaley_calls() {
+        assert_table(OptionKind::Call, &BAW_CALLS, barone_adesi_whaley, 3e-3);
+    }
+
+    #[test]
+    fn barone_adesi_whaley_puts() {
+        assert_table(OptionKind::Put, &BAW_PUTS, barone_adesi_whaley, 3e-3);
+    }
+
+    #[test]
+    fn barone_adesi_whaley_exact_critical_price() {
+        // critical price solved to 30 digits with mpmath
+        let result = barone_adesi_whaley(&model(OptionKind::Call, 110.0, 0.15, 0.1)).unwrap();
+        assert!((result.price - 10.006060055259993).abs() < 1e-10);
+        assert!((result.boundary - 110.96379651052499).abs() < 1e-8);
+
+        let result = barone_adesi_whaley(&model(OptionKind::Call, 100.0, 0.35, 0.5)).unwrap();
+        assert!((result.price - 9.51030684162174).abs() < 1e-10);
+        assert!((result.boundary - 151.77062265324936).abs() < 1e-8);
+    }
+
+    #[test]
+    fn bjerksund_stensland_calls() {
+        assert_table(OptionKind::Call, &BS2002_CALLS, bjerksund_stensland, 5e-5);
+    }
+
+    #[test]
+    fn bjerksund_stensland_puts() {
+        assert_table(OptionKind::Put, &BS2002_PUTS, bjerksund_stensland, 5e-5);
+    }
+
+    #[test]
+    fn approximations_are_close_to_binomial_tree() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = BlackScholesModel::new(opt, 100.0, 95.0, 0.06, 0.3, 1.0, Some(0.04));
+            let tree = BinomialModel::from(model)
+                .with_exercise(ExerciseStyle::American)
+                .with_lattice(Lattice::LeisenReimer)
+                .with_steps(1001)
+                .price()
+                .unwrap();
+
+            let baw = barone_adesi_whaley(&model).unwrap().price;
+            let bs = bjerksund_stensland(&model).unwrap().price;
+            assert!(
+                (baw - tree).abs() < 0.05,
+                "{} is not close to {}",
+                baw,
+                tree
+            );
+            assert!((bs - tree).abs() < 0.05, "{} is not close to {}", bs, tree);
+            assert!(baw >= model.price().unwrap() && bs >= model.price().unwrap());
+        }
+    }
+
+    #[test]
+    fn exercise_boundary() {
+        for pricer in [barone_adesi_whaley, bjerksund_stensland] {
+            let call = pricer(&model(OptionKind::Call, 100.0, 0.25, 0.5)).unwrap();
+            assert!(call.boundary > 100.0);
+            let beyond = pricer(&model(OptionKind::Call, call.boundary + 1.0, 0.25, 0.5)).unwrap();
+            assert_eq!(beyond.price, call.boundary + 1.0 - 100.0);
+
+            let put = pricer(&model(OptionKind::Put, 100.0, 0.25, 0.5)).unwrap();
+            assert!(put.boundary < 100.0);
+            let beyond = pricer(&model(OptionKind::Put, put.boundary - 1.0, 0.25, 0.5)).unwrap();
+            assert!((beyond.price - (100.0 - put.boundary + 1.0)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn call_without_dividend_is_european() {
+        let model = BlackScholesModel::new(OptionKind::Call, 100.0, 95.0, 0.06, 0.3, 1.0, None);
+        for pricer in [barone_adesi_whaley, bjerksund_stensland] {
+            let result = pricer(&model).unwrap();
+            assert_eq!(result.price, model.price().unwrap());
+            assert_eq!(result.boundary, f64::INFINITY);
+        }
+    }
+}
@@ -0,0 +1,493 @@
+use crate::distributions::norm_cdf;
+use crate::monte_carlo::{covariance, estimate, Estimate};
+use crate::quadrature::adaptive;
+use crate::random::Xoshiro256;
+use crate::{check_finite, BlackScholesModel, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+const TOLERANCE: f64 = 1e-12;
+const SIMULATED_FIXINGS: usize = 250;
+
+// Averaging is how the stock enters the average, continuously over the averaging
+// period or at a number of equally spaced fixings, the last of them at expiration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Averaging {
+    Continuous,
+    Discrete(usize),
+}
+
+// AsianModel prices average price options, which pay the difference between the
+// average of the stock over the averaging period and the strike, under the
+// Black-Scholes model
+//
+// Averaging starts today unless set with with_start. A partially fixed average gets
+// the average of the past fixings and their weight in the final average with
+// with_fixings, the remaining fixings then start today
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AsianModel {
+    model: BlackScholesModel, // contract and market inputs, the strike applies to the average
+    averaging: Averaging,     // continuous or the number of remaining fixings
+    start: f64,               // time until averaging starts (% of year)
+    realized: f64,            // average of the past fixings ($$$ per share)
+    weight: f64,              // weight of the past fixings in the final average
+}
+
+impl AsianModel {
+    pub fn new(model: BlackScholesModel, averaging: Averaging) -> AsianModel {
+        AsianModel {
+            model,
+            averaging,
+            start: 0.0,
+            realized: 0.0,
+            weight: 0.0,
+        }
+    }
+
+    // try_new creates the model like new does, but rejects market inputs for which the
+    // Black-Scholes formula is undefined and averages without fixings
+    pub fn try_new(model: BlackScholesModel, averaging: Averaging) -> MathResult<AsianModel> {
+        BlackScholesModel::try_new(
+            model.opt,
+            model.strike,
+            model.stock,
+            model.interest_rate,
+            model.volatility,
+            model.time_to_expire,
+            model.dividend,
+        )?;
+        if averaging == Averaging::Discrete(0) {
+            return Err(MathError::TooFewSteps(0));
+        }
+        Ok(AsianModel::new(model, averaging))
+    }
+
+    // with_start delays the averaging period until the start (% of year), which has to
+    // come before expiration
+    pub fn with_start(mut self, start: f64) -> AsianModel {
+        self.start = start;
+        self
+    }
+
+    // with_fixings sets the average of the past fixings, arithmetic or geometric like
+    // the option, and their weight between 0 and 1 in the final average
+    pub fn with_fixings(mut self, realized: f64, weight: f64) -> AsianModel {
+        self.realized = realized;
+        self.weight = weight;
+        self
+    }
+
+    // geometric calculates the fair value of the option on the geometric average
+    // ($$$ per share), the log of which is normally distributed
+    pub fn geometric(&self) -> MathResult {
+        self.validate()?;
+        let m = &self.model;
+        let carry = m.interest_rate - m.dividend.unwrap_or_default();
+        let (mean_time, variance_time) = self.log_moments(self.averaging);
+        let future = 1.0 - self.weight;
+        let mut mean = future * (m.stock.ln() + (carry - m.volatility.powi(2) / 2.0) * mean_time);
+        if self.weight > 0.0 {
+            mean += self.weight * self.realized.ln();
+        }
+        let variance = (future * m.volatility).powi(2) * variance_time;
+        Ok(black(
+            m.opt,
+            E.powf(mean + variance / 2.0),
+            m.strike,
+            variance,
+            m.discount(),
+        ))
+    }
+
+    // turnbull_wakeman approximates the fair value of the option on the arithmetic
+    // average ($$$ per share) with Turnbull and Wakeman (1991), the Black-Scholes
+    // formula with the cost of carry and volatility which match the first two moments
+    // of the average
+    pub fn turnbull_wakeman(&self) -> MathResult {
+        self.validate()?;
+        let m = &self.model;
+        let (first, second) = self.moments(self.averaging)?;
+        let (future, strike) = self.adjusted_strike();
+        if strike <= 0.0 || m.time_to_expire == 0.0 {
+            return Ok(future * lognormal(m, first, second, strike));
+        }
+        let carry = (first / m.stock).ln() / m.time_to_expire;
+        let volatility = ((second / (first * first)).ln() / m.time_to_expire).sqrt();
+        let adjusted = BlackScholesModel {
+            strike,
+            volatility,
+            dividend: Some(m.interest_rate - carry),
+            ..*m
+        };
+        Ok(future * adjusted.price()?)
+    }
+
+    // levy approximates the fair value of the option on the arithmetic average
+    // ($$$ per share) with Levy (1992), a lognormal forward with the first two moments
+    // of the continuous average, discrete fixings are treated as continuous averaging
+    pub fn levy(&self) -> MathResult {
+        self.validate()?;
+        let (first, second) = self.moments(Averaging::Continuous)?;
+        let (future, strike) = self.adjusted_strike();
+        Ok(future * lognormal(&self.model, first, second, strike))
+    }
+
+    // monte_carlo estimates the fair value of the option on the arithmetic average
+    // ($$$ per share) with the option on the geometric average of the same fixings as
+    // control variate, continuous averaging is simulated with 250 fixings
+    pub fn monte_carlo(&self, paths: usize, seed: u64) -> MathResult<Estimate> {
+        self.validate()?;
+        let m = &self.model;
+        let fixings = match self.averaging {
+            Averaging::Continuous => SIMULATED_FIXINGS,
+            Averaging::Discrete(fixings) => fixings,
+        };
+        let (future, strike) = self.adjusted_strike();
+        if strike <= 0.0 {
+            let (first, second) = self.moments(self.averaging)?;
+            return Ok(Estimate {
+                price: future * lognormal(m, first, second, strike),
+                standard_error: 0.0,
+            });
+        }
+        if paths < 2 {
+            return Err(MathError::TooFewPaths(paths));
+        }
+
+        // the geometric option on the same fixings without the past ones
+        let discount = m.discount();
+        let carry = m.interest_rate - m.dividend.unwrap_or_default();
+        let drift = carry - m.volatility.powi(2) / 2.0;
+        let (mean_time, variance_time) = self.log_moments(Averaging::Discrete(fixings));
+        let variance = m.volatility.powi(2) * variance_time;
+        let forward = m.stock * E.powf(drift * mean_time + variance / 2.0);
+        let expected = black(m.opt, forward, strike, variance, discount);
+
+        let interval = (m.time_to_expire - self.start) / fixings as f64;
+        let intrinsic = |average: f64| match m.opt {
+            OptionKind::Call => (average - strike).max(0.0),
+            OptionKind::Put => (strike - average).max(0.0),
+        };
+        let mut rng = Xoshiro256::new(seed);
+        let mut values = Vec::with_capacity(paths);
+        let mut controls = Vec::with_capacity(paths);
+        for _ in 0..paths {
+            let mut log_stock = m.stock.ln();
+            let (mut arithmetic, mut geometric) = (0.0, 0.0);
+            for i in 0..fixings {
+                let dt = if i == 0 {
+                    self.start + interval
+                } else {
+                    interval
+                };
+                log_stock += drift * dt + m.volatility * dt.sqrt() * rng.normal();
+                arithmetic += E.powf(log_stock);
+                geometric += log_stock;
+            }
+            values.push(discount * intrinsic(arithmetic / fixings as f64));
+            controls.push(discount * intrinsic(E.powf(geometric / fixings as f64)));
+        }
+
+        let beta = covariance(&controls, &values) / covariance(&controls, &controls);
+        if beta.is_finite() {
+            for (value, control) in values.iter_mut().zip(&controls) {
+                *value -= beta * (control - expected);
+            }
+        }
+        let result = estimate(&values);
+        Ok(Estimate {
+            price: future * result.price,
+            standard_error: future * result.standard_error,
+        })
+    }
+
+    // adjusted_strike splits off the past fixings, the option is worth the weight of
+    // the remaining fixings times an option on their average with the adjusted strike
+    fn adjusted_strike(&self) -> (f64, f64) {
+        let future = 1.0 - self.weight;
+        let strike = (self.model.strike - self.weight * self.realized) / future;
+        (future, strike)
+    }
+
+    // log_moments returns the mean time of the fixings and the variance of the average
+    // of the Brownian motion at them (% of year)
+    fn log_moments(&self, averaging: Averaging) -> (f64, f64) {
+        let period = self.model.time_to_expire - self.start;
+        match averaging {
+            Averaging::Continuous => (self.start + period / 2.0, self.start + period / 3.0),
+            Averaging::Discrete(fixings) => {
+                let n = fixings as f64;
+                let interval = period / n;
+                (
+                    self.start + interval * (n + 1.0) / 2.0,
+                    self.start + interval * (n + 1.0) * (2.0 * n + 1.0) / (6.0 * n),
+                )
+            }
+        }
+    }
+
+    // moments returns the first two moments of the arithmetic average of the
+    // remaining fixings
+    fn moments(&self, averaging: Averaging) -> MathResult<(f64, f64)> {
+        let m = &self.model;
+        let carry = m.interest_rate - m.dividend.unwrap_or_default();
+        let variance = m.volatility.powi(2);
+        let period = m.time_to_expire - self.start;
+        let growth = |time: f64| m.stock * E.powf(carry * time);
+
+        match averaging {
+            Averaging::Continuous if period > 0.0 => {
+                let first = growth(self.start) * relative_growth(carry * period);
+                // E[S(s) S(u)] for s < u, integrated over u in closed form
+                let integrand = |x: f64| {
+                    E.powf((2.0 * carry + variance) * (self.start + x))
+                        * (period - x)
+                        * relative_growth(carry * (period - x))
+                };
+                let integral = adaptive(&integrand, 0.0, period, TOLERANCE)?;
+                Ok((first, 2.0 * m.stock.powi(2) * integral / period.powi(2)))
+            }
+            Averaging::Continuous => Ok((
+                growth(m.time_to_expire),
+                growth(m.time_to_expire).powi(2) * E.powf(variance * m.time_to_expire),
+            )),
+            Averaging::Discrete(fixings) => {
+                let n = fixings as f64;
+                let interval = period / n;
+                let time = |i: usize| self.start + (i + 1) as f64 * interval;
+                let first = (0..fixings).map(|i| growth(time(i))).sum::<f64>() / n;
+
+                // sum over the pairs of fixings, the later of which only adds its growth
+                let mut later = 0.0;
+                let mut second = 0.0;
+                for i in (0..fixings).rev() {
+                    let t = time(i);
+                    second += m.stock.powi(2) * E.powf((2.0 * carry + variance) * t)
+                        + 2.0 * m.stock * E.powf((carry + variance) * t) * later;
+                    later += growth(t);
+                }
+                Ok((first, second / (n * n)))
+            }
+        }
+    }
+
+    fn validate(&self) -> MathResult<()> {
+        if let Averaging::Discrete(fixings) = self.averaging {
+            if fixings == 0 {
+                return Err(MathError::TooFewSteps(fixings));
+            }
+        }
+        check_finite("start", self.start)?;
+        check_finite("realized", self.realized)?;
+        check_finite("weight", self.weight)?;
+        if self.start < 0.0 || self.start > self.model.time_to_expire {
+            return Err(MathError::ParameterOutOfBounds("start", self.start));
+        }
+        if !(0.0..1.0).contains(&self.weight) || (self.weight > 0.0 && self.start > 0.0) {
+            return Err(MathError::ParameterOutOfBounds("weight", self.weight));
+        }
+        if self.weight > 0.0 && self.realized <= 0.0 {
+            return Err(MathError::ParameterOutOfBounds("realized", self.realized));
+        }
+        Ok(())
+    }
+}
+
+// lognormal prices the option on an average with the given first two moments as an
+// option on a lognormal forward
+fn lognormal(model: &BlackScholesModel, first: f64, second: f64, strike: f64) -> f64 {
+    let variance = (second / (first * first)).ln().max(0.0);
+    black(model.opt, first, strike, variance, model.discount())
+}
+
+// black is Black's formula with the total variance of the log of the forward, strikes
+// which are not positive leave calls certainly in the money and puts worthless
+fn black(opt: OptionKind, forward: f64, strike: f64, variance: f64, discount: f64) -> f64 {
+    let phi = match opt {
+        OptionKind::Call => 1.0,
+        OptionKind::Put => -1.0,
+    };
+    if strike <= 0.0 || variance == 0.0 {
+        return discount * (phi * (forward - strike)).max(0.0);
+    }
+    let deviation = variance.sqrt();
+    let d1 = ((forward / strike).ln() + variance / 2.0) / deviation;
+    let d2 = d1 - deviation;
+    phi * discount * (forward * norm_cdf(phi * d1) - strike * norm_cdf(phi * d2))
+}
+
+// relative_growth is (e^x - 1) / x, which tends to 1 for small x
+fn relative_growth(x: f64) -> f64 {
+    if x.abs() < 1e-8 {
+        1.0 + x / 2.0
+    } else {
+        x.exp_m1() / x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(opt: OptionKind) -> BlackScholesModel {
+        BlackScholesModel::new(opt, 100.0, 100.0, 0.05, 0.25, 1.0, Some(0.02))
+    }
+
+    #[test]
+    fn kemna_vorst() {
+        // Haug (2007), geometric average rate put with a cost of carry of 8%
+        let model =
+            BlackScholesModel::new(OptionKind::Put, 85.0, 80.0, 0.05, 0.2, 0.25, Some(-0.03));
+        let result = AsianModel::try_new(model, Averaging::Continuous)
+            .unwrap()
+            .geometric()
+            .unwrap();
+        assert!((result - 4.6922).abs() < 1e-4, "{} is not 4.6922", result);
+    }
+
+    #[test]
+    fn single_fixing_is_vanilla() {
+        for &opt in &[OptionKind::Call, OptionKind::Put] {
+            let expected = model(opt).price().unwrap();
+            let discrete = AsianModel::new(model(opt), Averaging::Discrete(1));
+            let late = AsianModel::new(model(opt), Averaging::Continuous).with_start(1.0);
+            for result in [
+                discrete.geometric(),
+                discrete.turnbull_wakeman(),
+                late.geometric(),
+                late.turnbull_wakeman(),
+                late.levy(),
+            ] {
+                let result = result.unwrap();
+                assert!(
+                    (result - expected).abs() < 1e-10,
+                    "{} is not {}",
+                    result,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn discrete_tends_to_continuous() {
+        for &opt in &[OptionKind::Call, OptionKind::Put] {
+            let continuous = AsianModel::new(model(opt), Averaging::Continuous).with_start(0.25);
+            let discrete =
+                AsianModel::new(model(opt), Averaging::Discrete(10_000)).with_start(0.25);
+            let result = discrete.geometric().unwrap();
+            let expected = continuous.geometric().unwrap();
+            assert!(
+                (result - expected).abs() < 1e-3,
+                "{} is not {}",
+                result,
+                expected
+            );
+            let result = discrete.turnbull_wakeman().unwrap();
+            let expected = continuous.turnbull_wakeman().unwrap();
+            assert!(
+                (result - expected).abs() < 1e-3,
+                "{} is not {}",
+                result,
+                expected
+            );
+
+            // both approximations match the same moments of a continuous average
+            let result = continuous.levy().unwrap();
+            assert!(
+                (result - expected).abs() < 1e-10,
+                "{} is not {}",
+                result,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn approximations_match_simulation() {
+        for &opt in &[OptionKind::Call, OptionKind::Put] {
+            let start = AsianModel::new(model(opt), Averaging::Discrete(12)).with_start(0.25);
+            let fixed = AsianModel::new(model(opt), Averaging::Continuous).with_fixings(105.0, 0.4);
+            for asian in [start, fixed] {
+                let simulated = asian.monte_carlo(20_000, 42).unwrap();
+                let result = asian.turnbull_wakeman().unwrap();
+                assert!(simulated.standard_error < 5e-3, "{:?}", simulated);
+                assert!(
+                    (result - simulated.price).abs() < 1e-2 * simulated.price,
+                    "{} is not {:?}",
+                    result,
+                    simulated
+                );
+
+                // the geometric average is never above the arithmetic one
+                let geometric = asian.geometric().unwrap();
+                match opt {
+                    OptionKind::Call => assert!(geometric < simulated.price),
+                    OptionKind::Put => assert!(geometric > simulated.price),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fixings_above_strike() {
+        // half of the average is fixed far above the strike, the call becomes a forward
+        // on the rest and the put is worthless
+        let model = model(OptionKind::Call);
+        let forward = 100.0 * relative_growth(0.03);
+        let expected = model.discount() * (0.5 * forward + 100.0 - 90.0);
+        let asian = AsianModel::new(
+            BlackScholesModel {
+                strike: 90.0,
+                ..model
+            },
+            Averaging::Continuous,
+        )
+        .with_fixings(200.0, 0.5);
+        for result in [asian.turnbull_wakeman(), asian.levy()] {
+            let result = result.unwrap();
+            assert!(
+                (result - expected).abs() < 1e-10,
+                "{} is not {}",
+                result,
+                expected
+            );
+        }
+        let simulated = asian.monte_carlo(100, 1).unwrap();
+        assert!((simulated.price - expected).abs() < 1e-10);
+
+        let put = AsianModel {
+            model: BlackScholesModel {
+                opt: OptionKind::Put,
+                ..asian.model
+            },
+            ..asian
+        };
+        assert_eq!(put.levy(), Ok(0.0));
+    }
+
+    #[test]
+    fn err_invalid_averaging() {
+        let model = model(OptionKind::Call);
+        assert_eq!(
+            AsianModel::try_new(model, Averaging::Discrete(0)),
+            Err(MathError::TooFewSteps(0))
+        );
+        let asian = AsianModel::new(model, Averaging::Continuous);
+        assert_eq!(
+            asian.with_start(1.5).geometric(),
+            Err(MathError::ParameterOutOfBounds("start", 1.5))
+        );
+        assert_eq!(
+            asian.with_fixings(100.0, 1.0).levy(),
+            Err(MathError::ParameterOutOfBounds("weight", 1.0))
+        );
+        assert_eq!(
+            asian
+                .with_start(0.5)
+                .with_fixings(100.0, 0.5)
+                .turnbull_wakeman(),
+            Err(MathError::ParameterOutOfBounds("weight", 0.5))
+        );
+        assert_eq!(asian.monte_carlo(1, 1), Err(MathError::TooFewPaths(1)));
+    }
+}
@@ -0,0 +1,459 @@
+use crate::distributions::{norm_cdf, norm_pdf};
+use crate::{check_finite, solver, Greeks, MathError, MathResult, OptionKind};
+use std::f64::consts::{E, PI};
+
+// BachelierModel prices european options assuming normally distributed forward prices,
+// the volatility is quoted in $$$ per unit and year (normal vol) and both the forward
+// and the strike are allowed to be zero or negative
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BachelierModel {
+    opt: OptionKind,     // option type (call or put)
+    strike: f64,         // strike price ($$$ per unit)
+    forward: f64,        // forward or futures price ($$$ per unit)
+    interest_rate: f64,  // continuously compounded risk-free interest rate (% p.a.)
+    volatility: f64,     // normal volatility ($$$ per unit p.a.)
+    time_to_expire: f64, // time to expiration (% of year)
+}
+
+impl BachelierModel {
+    pub fn new(
+        opt: OptionKind,
+        strike: f64,
+        forward: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+    ) -> BachelierModel {
+        BachelierModel {
+            opt,
+            strike,
+            forward,
+            interest_rate,
+            volatility,
+            time_to_expire,
+        }
+    }
+
+    // try_new creates the model like new does, but rejects non-finite inputs, a
+    // non-positive volatility and a negative time to expiration
+    pub fn try_new(
+        opt: OptionKind,
+        strike: f64,
+        forward: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+    ) -> MathResult<BachelierModel> {
+        check_finite("strike", strike)?;
+        check_finite("forward", forward)?;
+        check_finite("interest_rate", interest_rate)?;
+        check_finite("volatility", volatility)?;
+        check_finite("time_to_expire", time_to_expire)?;
+        if volatility <= 0.0 {
+            return Err(MathError::NonPositiveVolatility(volatility));
+        }
+        if time_to_expire < 0.0 {
+            return Err(MathError::NegativeTimeToExpire(time_to_expire));
+        }
+        Ok(BachelierModel::new(
+            opt,
+            strike,
+            forward,
+            interest_rate,
+            volatility,
+            time_to_expire,
+        ))
+    }
+
+    // price calculates the fair value of the option ($$$ per unit), at expiration or
+    // without volatility that's the discounted intrinsic value
+    pub fn price(&self) -> MathResult {
+        let moneyness = self.moneyness();
+        let deviation = self.deviation();
+        if deviation == 0.0 {
+            return Ok(self.discount() * moneyness.max(0.0));
+        }
+        let d = moneyness / deviation;
+        Ok(self.discount() * (moneyness * norm_cdf(d) + deviation * norm_pdf(d)))
+    }
+
+    // greeks calculates the sensitivities with respect to the forward price, rho moves
+    // the discount rate only and dividend rho is always 0
+    //
+    // At expiration or without volatility they're the greeks of the discounted intrinsic
+    // value, which has a kink at the money where that's an error
+    pub fn greeks(&self) -> MathResult<Greeks> {
+        let moneyness = self.moneyness();
+        let deviation = self.deviation();
+        let discount = self.discount();
+        let price = self.price()?;
+        let direction = match self.opt {
+            OptionKind::Call => 1.0,
+            OptionKind::Put => -1.0,
+        };
+
+        if deviation == 0.0 {
+            if moneyness == 0.0 {
+                return Err(MathError::AtTheMoneyWithoutTimeValue(self.forward));
+            }
+            let exercised = if moneyness > 0.0 { 1.0 } else { 0.0 };
+            return Ok(Greeks {
+                delta: direction * exercised * discount,
+                gamma: 0.0,
+                vega: 0.0,
+                theta: self.interest_rate * price,
+                rho: -self.time_to_expire * price,
+                dividend_rho: 0.0,
+            });
+        }
+
+        let d = moneyness / deviation;
+        Ok(Greeks {
+            delta: direction * discount * norm_cdf(d),
+            gamma: discount * norm_pdf(d) / deviation,
+            vega: self.vega(),
+            theta: self.interest_rate * price
+                - discount * self.volatility * norm_pdf(d) / (2.0 * self.time_to_expire.sqrt()),
+            rho: -self.time_to_expire * price,
+            dividend_rho: 0.0,
+        })
+    }
+
+    fn vega(&self) -> f64 {
+        let d = self.moneyness() / self.deviation();
+        self.discount() * self.time_to_expire.sqrt() * norm_pdf(d)
+    }
+
+    // moneyness is the intrinsic value of the forward, signed by the option type
+    fn moneyness(&self) -> f64 {
+        match self.opt {
+            OptionKind::Call => self.forward - self.strike,
+            OptionKind::Put => self.strike - self.forward,
+        }
+    }
+
+    // deviation is the standard deviation of the forward at expiration
+    fn deviation(&self) -> f64 {
+        self.volatility * self.time_to_expire.sqrt()
+    }
+
+    fn discount(&self) -> f64 {
+        E.powf(-self.interest_rate * self.time_to_expire)
+    }
+}
+
+// implied_volatility calculates the normal volatility at which BachelierModel::price
+// matches the observed premium ($$$ per unit)
+pub fn implied_volatility(
+    opt: OptionKind,
+    strike: f64,
+    forward: f64,
+    interest_rate: f64,
+    time_to_expire: f64,
+    premium: f64,
+) -> MathResult {
+    check_finite("premium", premium)?;
+    let model = |volatility| {
+        BachelierModel::try_new(
+            opt,
+            strike,
+            forward,
+            interest_rate,
+            volatility,
+            time_to_expire,
+        )
+    };
+    // validates all the other inputs
+    let reference = model(1.0)?;
+    let lower = reference.discount() * reference.moneyness().max(0.0);
+    if premium <= lower || time_to_expire == 0.0 {
+        return Err(MathError::PremiumOutOfBounds {
+            premium,
+            lower,
+            upper: if time_to_expire == 0.0 {
+                lower
+            } else {
+                f64::INFINITY
+            },
+        });
+    }
+
+    // the at-the-money price is linear in volatility, which gives both the starting
+    // point and the scale of the bracket
+    let guess = premium / reference.discount() * (2.0 * PI / time_to_expire).sqrt();
+    let mut upper = guess;
+    while model(upper)?.price()? < premium {
+        upper *= 2.0;
+    }
+
+    solver::newton_bisection(
+        |volatility| {
+            let model = BachelierModel::new(
+                opt,
+                strike,
+                forward,
+                interest_rate,
+                volatility,
+                time_to_expire,
+            );
+            Ok((model.price()? - premium, model.vega()))
+        },
+        0.0,
+        upper,
+        guess,
+    )
+}
+
+// lognormal_to_normal_volatility converts a Black (lognormal) implied volatility into
+// the normal one with Hagan's approximation
+//
+//   normal = lognormal * sqrt(F K) (1 + ln(F/K)^2 / 24 + ln(F/K)^4 / 1920)
+//          / (1 + lognormal^2 T / 24 + lognormal^4 T^2 / 5760)
+//
+// Both the forward and the strike have to be positive
+pub fn lognormal_to_normal_volatility(
+    forward: f64,
+    strike: f64,
+    time_to_expire: f64,
+    volatility: f64,
+) -> MathResult {
+    let scale = hagan_scale(forward, strike)?;
+    check_volatility(time_to_expire, volatility)?;
+    Ok(scale * volatility / hagan_correction(volatility, time_to_expire))
+}
+
+// normal_to_lognormal_volatility inverts lognormal_to_normal_volatility
+pub fn normal_to_lognormal_volatility(
+    forward: f64,
+    strike: f64,
+    time_to_expire: f64,
+    volatility: f64,
+) -> MathResult {
+    let scale = hagan_scale(forward, strike)?;
+    check_volatility(time_to_expire, volatility)?;
+    if time_to_expire == 0.0 {
+        return Ok(volatility / scale);
+    }
+
+    // the approximation peaks at the root of 1 - T x^2 / 24 - 3 T^2 x^4 / 5760, beyond
+    // which it's no longer invertible
+    let a = time_to_expire / 24.0;
+    let b = time_to_expire.powi(2) / 5760.0;
+    let peak = ((-a + (a * a + 12.0 * b).sqrt()) / (6.0 * b)).sqrt();
+    solver::brent(
+        |lognormal| {
+            Ok(scale * lognormal / hagan_correction(lognormal, time_to_expire) - volatility)
+        },
+        0.0,
+        peak,
+    )
+}
+
+fn hagan_scale(forward: f64, strike: f64) -> MathResult {
+    check_finite("forward", forward)?;
+    check_finite("strike", strike)?;
+    if forward <= 0.0 {
+        return Err(MathError::NonPositiveStock(forward));
+    }
+    if strike <= 0.0 {
+        return Err(MathError::NonPositiveStrike(strike));
+    }
+    let log_moneyness = (forward / strike).ln();
+    Ok((forward * strike).sqrt()
+        * (1.0 + log_moneyness.powi(2) / 24.0 + log_moneyness.powi(4) / 1920.0))
+}
+
+fn check_volatility(time_to_expire: f64, volatility: f64) -> MathResult<()> {
+    check_finite("time_to_expire", time_to_expire)?;
+    check_finite("volatility", volatility)?;
+    if time_to_expire < 0.0 {
+        return Err(MathError::NegativeTimeToExpire(time_to_expire));
+    }
+    if volatility <= 0.0 {
+        return Err(MathError::NonPositiveVolatility(volatility));
+    }
+    Ok(())
+}
+
+fn hagan_correction(volatility: f64, time_to_expire: f64) -> f64 {
+    1.0 + volatility.powi(2) * time_to_expire / 24.0
+        + volatility.powi(4) * time_to_expire.powi(2) / 5760.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::black76::Black76Model;
+    use crate::testing::assert_close;
+
+    #[test]
+    fn at_the_money_price() {
+        let model = BachelierModel::new(OptionKind::Call, 100.0, 100.0, 0.0, 20.0, 1.0);
+        assert_close(model.price().unwrap(), 20.0 / (2.0 * PI).sqrt(), 1e-12);
+    }
+
+    #[test]
+    fn put_call_parity_with_negative_forward() {
+        let call = BachelierModel::new(OptionKind::Call, 0.25, -0.5, 0.02, 0.8, 2.0);
+        let put = BachelierModel::new(OptionKind::Put, 0.25, -0.5, 0.02, 0.8, 2.0);
+        let parity = E.powf(-0.02 * 2.0) * (-0.5 - 0.25);
+        assert_close(call.price().unwrap() - put.price().unwrap(), parity, 1e-12);
+        assert!(call.price().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn price_at_expiration_is_intrinsic() {
+        let put = BachelierModel::new(OptionKind::Put, 0.25, -0.5, 0.02, 0.8, 0.0);
+        assert_eq!(put.price().unwrap(), 0.75);
+    }
+
+    #[test]
+    fn greeks_match_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = BachelierModel::new(opt, 0.25, -0.5, 0.02, 0.8, 2.0);
+            let greeks = model.greeks().unwrap();
+            let h = 1e-4;
+            let diff = |bump: fn(&mut BachelierModel, f64)| {
+                let mut up = model;
+                bump(&mut up, h);
+                let mut down = model;
+                bump(&mut down, -h);
+                (up.price().unwrap() - down.price().unwrap()) / (2.0 * h)
+            };
+
+            assert_close(greeks.delta, diff(|m, h| m.forward += h), 1e-7);
+            assert_close(greeks.vega, diff(|m, h| m.volatility += h), 1e-7);
+            assert_close(greeks.theta, -diff(|m, h| m.time_to_expire += h), 1e-7);
+            assert_close(greeks.rho, diff(|m, h| m.interest_rate += h), 1e-7);
+
+            let mut up = model;
+            up.forward += h;
+            let mut down = model;
+            down.forward -= h;
+            let gamma = (greeks_delta(up) - greeks_delta(down)) / (2.0 * h);
+            assert_close(greeks.gamma, gamma, 1e-7);
+        }
+    }
+
+    fn greeks_delta(model: BachelierModel) -> f64 {
+        model.greeks().unwrap().delta
+    }
+
+    #[test]
+    fn greeks_without_time_value() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            for strike in [-0.75, 0.25] {
+                let model = BachelierModel::new(opt, strike, -0.5, 0.02, 0.0, 2.0);
+                let almost = BachelierModel {
+                    volatility: 1e-6,
+                    ..model
+                };
+                let (result, expected) = (model.greeks().unwrap(), almost.greeks().unwrap());
+                assert_close(result.delta, expected.delta, 1e-12);
+                assert_close(result.gamma, expected.gamma, 1e-12);
+                assert_close(result.vega, expected.vega, 1e-12);
+                assert_close(result.theta, expected.theta, 1e-12);
+                assert_close(result.rho, expected.rho, 1e-12);
+            }
+        }
+
+        let expired = BachelierModel::new(OptionKind::Put, 0.25, -0.5, 0.02, 0.8, 0.0);
+        let greeks = expired.greeks().unwrap();
+        assert_eq!((greeks.delta, greeks.gamma, greeks.vega), (-1.0, 0.0, 0.0));
+        let at_the_money = BachelierModel {
+            strike: -0.5,
+            ..expired
+        };
+        assert_eq!(
+            at_the_money.greeks(),
+            Err(MathError::AtTheMoneyWithoutTimeValue(-0.5))
+        );
+    }
+
+    #[test]
+    fn implied_volatility_recovers_model_volatility() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            for forward in [-0.5, 0.25, 1.5] {
+                let premium = BachelierModel::new(opt, 0.25, forward, 0.02, 0.8, 2.0)
+                    .price()
+                    .unwrap();
+                let result = implied_volatility(opt, 0.25, forward, 0.02, 2.0, premium).unwrap();
+                assert_close(result, 0.8, 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn err_implied_volatility_below_intrinsic() {
+        let result = implied_volatility(OptionKind::Put, 0.25, -0.5, 0.0, 2.0, 0.7);
+        assert_eq!(
+            result,
+            Err(MathError::PremiumOutOfBounds {
+                premium: 0.7,
+                lower: 0.75,
+                upper: f64::INFINITY
+            })
+        );
+    }
+
+    #[test]
+    fn hagan_conversion_matches_prices() {
+        let (forward, time_to_expire, lognormal) = (100.0, 1.0, 0.25);
+        for strike in [80.0, 100.0, 125.0] {
+            let normal =
+                lognormal_to_normal_volatility(forward, strike, time_to_expire, lognormal).unwrap();
+            let black = Black76Model::new(
+                OptionKind::Call,
+                strike,
+                forward,
+                0.0,
+                lognormal,
+                time_to_expire,
+            )
+            .price()
+            .unwrap();
+            let bachelier = BachelierModel::new(
+                OptionKind::Call,
+                strike,
+                forward,
+                0.0,
+                normal,
+                time_to_expire,
+            )
+            .price()
+            .unwrap();
+            assert_close(bachelier, black, 1e-3);
+
+            let back =
+                normal_to_lognormal_volatility(forward, strike, time_to_expire, normal).unwrap();
+            assert_close(back, lognormal, 1e-12);
+        }
+    }
+
+    #[test]
+    fn err_converting_negative_forward() {
+        let result = lognormal_to_normal_volatility(-0.5, 0.25, 1.0, 0.2);
+        assert_eq!(result, Err(MathError::NonPositiveStock(-0.5)));
+    }
+
+    #[test]
+    fn err_converting_invalid_volatility() {
+        for &convert in &[
+            lognormal_to_normal_volatility as fn(f64, f64, f64, f64) -> MathResult,
+            normal_to_lognormal_volatility,
+        ] {
+            assert_eq!(
+                convert(100.0, 90.0, 1.0, -0.2),
+                Err(MathError::NonPositiveVolatility(-0.2))
+            );
+            assert_eq!(
+                convert(100.0, 90.0, 1.0, f64::INFINITY),
+                Err(MathError::NonFiniteInput("volatility", f64::INFINITY))
+            );
+            assert_eq!(
+                convert(100.0, 90.0, -1.0, 0.2),
+                Err(MathError::NegativeTimeToExpire(-1.0))
+            );
+        }
+    }
+}
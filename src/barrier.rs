@@ -0,0 +1,672 @@
+use crate::distributions::norm_cdf;
+use crate::{check_finite, BlackScholesModel, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+const MAX_TERMS: i32 = 100;
+
+// -zeta(1/2) / sqrt(2 pi), the shift of Broadie, Glasserman and Kou (1997)
+const DISCRETE_SHIFT: f64 = 0.582_597_157_939_010_6;
+
+// BarrierKind is the side of the stock the barrier sits on and whether touching it
+// activates (in) or extinguishes (out) the option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarrierKind {
+    DownAndIn,
+    DownAndOut,
+    UpAndIn,
+    UpAndOut,
+}
+
+// Monitoring is how often the stock is compared with the barriers, discrete monitoring
+// takes the interval between observations (% of year)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Monitoring {
+    Continuous,
+    Discrete(f64),
+}
+
+// BarrierModel prices single barrier options with the closed forms of Reiner and
+// Rubinstein (1991) under the Black-Scholes model
+//
+// The rebate of a knock-out option is paid when the barrier is hit, the rebate of a
+// knock-in option at expiration when it never was. Discrete monitoring moves the
+// barrier away from the stock by exp(0.5826 vol sqrt(interval)) as Broadie, Glasserman
+// and Kou suggest, the correction is accurate unless the stock is next to the barrier
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BarrierModel {
+    model: BlackScholesModel, // contract and market inputs
+    kind: BarrierKind,        // barrier side and effect
+    barrier: f64,             // barrier level ($$$ per share)
+    rebate: f64,              // cash paid when the option does not pay off ($$$ per share)
+    monitoring: Monitoring,   // continuous unless set with with_monitoring
+}
+
+impl BarrierModel {
+    pub fn new(
+        model: BlackScholesModel,
+        kind: BarrierKind,
+        barrier: f64,
+        rebate: f64,
+    ) -> BarrierModel {
+        BarrierModel {
+            model,
+            kind,
+            barrier,
+            rebate,
+            monitoring: Monitoring::Continuous,
+        }
+    }
+
+    // try_new creates the model like new does, but rejects market inputs for which the
+    // Black-Scholes formula is undefined, a non-positive barrier and a negative rebate
+    pub fn try_new(
+        model: BlackScholesModel,
+        kind: BarrierKind,
+        barrier: f64,
+        rebate: f64,
+    ) -> MathResult<BarrierModel> {
+        validate(&model)?;
+        check_barrier("barrier", barrier)?;
+        check_finite("rebate", rebate)?;
+        if rebate < 0.0 {
+            return Err(MathError::ParameterOutOfBounds("rebate", rebate));
+        }
+        Ok(BarrierModel::new(model, kind, barrier, rebate))
+    }
+
+    pub fn with_monitoring(mut self, monitoring: Monitoring) -> BarrierModel {
+        self.monitoring = monitoring;
+        self
+    }
+
+    // price calculates the fair value of the option ($$$ per share), the barrier is
+    // already breached when the stock is at it or beyond
+    pub fn price(&self) -> MathResult {
+        let m = &self.model;
+        check_monitoring(self.monitoring)?;
+        let (down, knock_in) = match self.kind {
+            BarrierKind::DownAndIn => (true, true),
+            BarrierKind::DownAndOut => (true, false),
+            BarrierKind::UpAndIn => (false, true),
+            BarrierKind::UpAndOut => (false, false),
+        };
+        let breached = if down {
+            m.stock <= self.barrier
+        } else {
+            m.stock >= self.barrier
+        };
+        if breached {
+            return if knock_in { m.price() } else { Ok(self.rebate) };
+        }
+        if m.time_to_expire == 0.0 {
+            return Ok(if knock_in { self.rebate } else { intrinsic(m) });
+        }
+
+        let eta = if down { 1.0 } else { -1.0 };
+        let phi = match m.opt {
+            OptionKind::Call => 1.0,
+            OptionKind::Put => -1.0,
+        };
+        let h = shift(self.barrier, -eta, m.volatility, self.monitoring);
+        let x = m.strike;
+        let s = m.stock;
+        let variance = m.volatility.powi(2);
+        let carry = m.interest_rate - m.dividend.unwrap_or_default();
+        let v = m.volatility * m.time_to_expire.sqrt();
+        let mu = (carry - variance / 2.0) / variance;
+        let (discount, dividend_discount) = (m.discount(), m.dividend_discount());
+
+        let x1 = (s / x).ln() / v + (1.0 + mu) * v;
+        let x2 = (s / h).ln() / v + (1.0 + mu) * v;
+        let y1 = (h * h / (s * x)).ln() / v + (1.0 + mu) * v;
+        let y2 = (h / s).ln() / v + (1.0 + mu) * v;
+        let ratio = h / s;
+
+        // vanilla (a), vanilla above or below the barrier (b), their reflections (c, d)
+        // and the rebates paid at expiration (e) and at the hit (f)
+        let a = phi * s * dividend_discount * norm_cdf(phi * x1)
+            - phi * x * discount * norm_cdf(phi * (x1 - v));
+        let b = phi * s * dividend_discount * norm_cdf(phi * x2)
+            - phi * x * discount * norm_cdf(phi * (x2 - v));
+        let c = phi * s * dividend_discount * ratio.powf(2.0 * (mu + 1.0)) * norm_cdf(eta * y1)
+            - phi * x * discount * ratio.powf(2.0 * mu) * norm_cdf(eta * (y1 - v));
+        let d = phi * s * dividend_discount * ratio.powf(2.0 * (mu + 1.0)) * norm_cdf(eta * y2)
+            - phi * x * discount * ratio.powf(2.0 * mu) * norm_cdf(eta * (y2 - v));
+        let e = self.rebate
+            * discount
+            * (norm_cdf(eta * (x2 - v)) - ratio.powf(2.0 * mu) * norm_cdf(eta * (y2 - v)));
+        let f = if self.rebate == 0.0 {
+            0.0
+        } else {
+            // the hit is discounted by the first passage time, whose transform needs
+            // mu^2 + 2r/vol^2 >= 0, negative rates can break it
+            let lambda_squared = mu * mu + 2.0 * m.interest_rate / variance;
+            if lambda_squared < 0.0 {
+                return Err(MathError::ParameterOutOfBounds(
+                    "interest_rate",
+                    m.interest_rate,
+                ));
+            }
+            let lambda = lambda_squared.sqrt();
+            let z = (h / s).ln() / v + lambda * v;
+            self.rebate
+                * (ratio.powf(mu + lambda) * norm_cdf(eta * z)
+                    + ratio.powf(mu - lambda) * norm_cdf(eta * (z - 2.0 * lambda * v)))
+        };
+
+        let above = x >= h;
+        let price = match (self.kind, m.opt, above) {
+            (BarrierKind::DownAndIn, OptionKind::Call, true) => c + e,
+            (BarrierKind::DownAndIn, OptionKind::Call, false) => a - b + d + e,
+            (BarrierKind::UpAndIn, OptionKind::Call, true) => a + e,
+            (BarrierKind::UpAndIn, OptionKind::Call, false) => b - c + d + e,
+            (BarrierKind::DownAndIn, OptionKind::Put, true) => b - c + d + e,
+            (BarrierKind::DownAndIn, OptionKind::Put, false) => a + e,
+            (BarrierKind::UpAndIn, OptionKind::Put, true) => a - b + d + e,
+            (BarrierKind::UpAndIn, OptionKind::Put, false) => c + e,
+            (BarrierKind::DownAndOut, OptionKind::Call, true) => a - c + f,
+            (BarrierKind::DownAndOut, OptionKind::Call, false) => b - d + f,
+            (BarrierKind::UpAndOut, OptionKind::Call, true) => f,
+            (BarrierKind::UpAndOut, OptionKind::Call, false) => a - b + c - d + f,
+            (BarrierKind::DownAndOut, OptionKind::Put, true) => a - b + c - d + f,
+            (BarrierKind::DownAndOut, OptionKind::Put, false) => f,
+            (BarrierKind::UpAndOut, OptionKind::Put, true) => b - d + f,
+            (BarrierKind::UpAndOut, OptionKind::Put, false) => a - c + f,
+        };
+        Ok(price)
+    }
+}
+
+// DoubleBarrierKind is whether touching either barrier activates (in) or extinguishes
+// (out) the option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoubleBarrierKind {
+    KnockIn,
+    KnockOut,
+}
+
+// DoubleBarrierModel prices options with a flat lower and upper barrier by the series
+// of Ikeda and Kunitomo (1992), knock-in options are the vanilla minus the knock-out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DoubleBarrierModel {
+    model: BlackScholesModel, // contract and market inputs
+    kind: DoubleBarrierKind,  // barrier effect
+    lower: f64,               // lower barrier level ($$$ per share)
+    upper: f64,               // upper barrier level ($$$ per share)
+    monitoring: Monitoring,   // continuous unless set with with_monitoring
+}
+
+impl DoubleBarrierModel {
+    pub fn new(
+        model: BlackScholesModel,
+        kind: DoubleBarrierKind,
+        lower: f64,
+        upper: f64,
+    ) -> DoubleBarrierModel {
+        DoubleBarrierModel {
+            model,
+            kind,
+            lower,
+            upper,
+            monitoring: Monitoring::Continuous,
+        }
+    }
+
+    // try_new creates the model like new does, but rejects market inputs for which the
+    // Black-Scholes formula is undefined and barriers which are not 0 < lower < upper
+    pub fn try_new(
+        model: BlackScholesModel,
+        kind: DoubleBarrierKind,
+        lower: f64,
+        upper: f64,
+    ) -> MathResult<DoubleBarrierModel> {
+        validate(&model)?;
+        check_barrier("lower", lower)?;
+        check_barrier("upper", upper)?;
+        if upper <= lower {
+            return Err(MathError::ParameterOutOfBounds("upper", upper));
+        }
+        Ok(DoubleBarrierModel::new(model, kind, lower, upper))
+    }
+
+    pub fn with_monitoring(mut self, monitoring: Monitoring) -> DoubleBarrierModel {
+        self.monitoring = monitoring;
+        self
+    }
+
+    // price calculates the fair value of the option ($$$ per share)
+    pub fn price(&self) -> MathResult {
+        let m = &self.model;
+        check_monitoring(self.monitoring)?;
+        let out = if m.stock <= self.lower || m.stock >= self.upper {
+            0.0
+        } else if m.time_to_expire == 0.0 {
+            intrinsic(m)
+        } else {
+            self.knock_out()
+        };
+        match self.kind {
+            DoubleBarrierKind::KnockOut => Ok(out),
+            DoubleBarrierKind::KnockIn => Ok(m.price()? - out),
+        }
+    }
+
+    // knock_out sums the series over the reflections of the stock at both barriers, it
+    // pays off for stock prices in the window between the strike and a barrier
+    fn knock_out(&self) -> f64 {
+        let m = &self.model;
+        let lower = shift(self.lower, -1.0, m.volatility, self.monitoring);
+        let upper = shift(self.upper, 1.0, m.volatility, self.monitoring);
+        let (phi, from, to) = match m.opt {
+            OptionKind::Call => (1.0, m.strike.max(lower), upper),
+            OptionKind::Put => (-1.0, lower, m.strike.min(upper)),
+        };
+        if from >= to {
+            return 0.0;
+        }
+
+        let variance = m.volatility.powi(2);
+        let carry = m.interest_rate - m.dividend.unwrap_or_default();
+        let v = m.volatility * m.time_to_expire.sqrt();
+        let drift = (carry + variance / 2.0) * m.time_to_expire;
+        let mu = 2.0 * carry / variance + 1.0;
+        let (ln_stock, ln_lower, ln_upper) = (m.stock.ln(), lower.ln(), upper.ln());
+
+        // contributions to the stock and strike legs of the n-th reflection
+        let term = |n: f64| {
+            let direct = |x: f64| (ln_stock + 2.0 * n * (ln_upper - ln_lower) - x.ln() + drift) / v;
+            let reflected = |x: f64| {
+                (2.0 * (n + 1.0) * ln_lower - 2.0 * n * ln_upper - x.ln() - ln_stock + drift) / v
+            };
+            let growth = n * (ln_upper - ln_lower);
+            let image = (n + 1.0) * ln_lower - n * ln_upper - ln_stock;
+            let window = |d: &dyn Fn(f64) -> f64, shift: f64| {
+                norm_cdf(d(from) - shift) - norm_cdf(d(to) - shift)
+            };
+            let stock = E.powf(mu * growth) * window(&direct, 0.0)
+                - E.powf(mu * image) * window(&reflected, 0.0);
+            let strike = E.powf((mu - 2.0) * growth) * window(&direct, v)
+                - E.powf((mu - 2.0) * image) * window(&reflected, v);
+            (stock, strike)
+        };
+
+        let (mut stock, mut strike) = term(0.0);
+        for n in 1..=MAX_TERMS {
+            let (up_stock, up_strike) = term(n as f64);
+            let (down_stock, down_strike) = term(-n as f64);
+            let change = (up_stock + down_stock) * m.stock + (up_strike + down_strike) * m.strike;
+            stock += up_stock + down_stock;
+            strike += up_strike + down_strike;
+            if change.abs() < f64::EPSILON * (m.stock + m.strike) {
+                break;
+            }
+        }
+        phi * (m.stock * m.dividend_discount() * stock - m.strike * m.discount() * strike)
+    }
+}
+
+// shift moves a discretely monitored barrier up (direction 1) or down (-1)
+fn shift(barrier: f64, direction: f64, volatility: f64, monitoring: Monitoring) -> f64 {
+    match monitoring {
+        Monitoring::Continuous => barrier,
+        Monitoring::Discrete(interval) => {
+            barrier * E.powf(direction * DISCRETE_SHIFT * volatility * interval.sqrt())
+        }
+    }
+}
+
+fn intrinsic(model: &BlackScholesModel) -> f64 {
+    match model.opt {
+        OptionKind::Call => (model.stock - model.strike).max(0.0),
+        OptionKind::Put => (model.strike - model.stock).max(0.0),
+    }
+}
+
+fn check_barrier(name: &'static str, barrier: f64) -> MathResult<()> {
+    check_finite(name, barrier)?;
+    if barrier <= 0.0 {
+        return Err(MathError::ParameterOutOfBounds(name, barrier));
+    }
+    Ok(())
+}
+
+fn check_monitoring(monitoring: Monitoring) -> MathResult<()> {
+    if let Monitoring::Discrete(interval) = monitoring {
+        check_finite("interval", interval)?;
+        if interval <= 0.0 {
+            return Err(MathError::ParameterOutOfBounds("interval", interval));
+        }
+    }
+    Ok(())
+}
+
+fn validate(model: &BlackScholesModel) -> MathResult<()> {
+    BlackScholesModel::try_new(
+        model.opt,
+        model.strike,
+        model.stock,
+        model.interest_rate,
+        model.volatility,
+        model.time_to_expire,
+        model.dividend,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monte_carlo::MonteCarloModel;
+    use crate::testing::assert_close;
+
+    const KINDS: [BarrierKind; 4] = [
+        BarrierKind::DownAndIn,
+        BarrierKind::DownAndOut,
+        BarrierKind::UpAndIn,
+        BarrierKind::UpAndOut,
+    ];
+
+    // Haug (2007), table 4-13: stock 100, rebate 3, half a year, rate 8%, carry 4%,
+    // volatility 25% and strikes 90, 100 and 110
+    fn haug(opt: OptionKind, strike: f64) -> BlackScholesModel {
+        BlackScholesModel::new(opt, strike, 100.0, 0.08, 0.25, 0.5, Some(0.04))
+    }
+
+    #[test]
+    fn reiner_rubinstein() {
+        let table = [
+            (
+                BarrierKind::DownAndOut,
+                OptionKind::Call,
+                95.0,
+                [9.0246, 6.7924, 4.8759],
+            ),
+            (
+                BarrierKind::DownAndOut,
+                OptionKind::Call,
+                100.0,
+                [3.0, 3.0, 3.0],
+            ),
+            (
+                BarrierKind::UpAndOut,
+                OptionKind::Call,
+                105.0,
+                [2.6789, 2.3580, 2.3453],
+            ),
+            (
+                BarrierKind::DownAndIn,
+                OptionKind::Call,
+                95.0,
+                [7.7627, 4.0109, 2.0576],
+            ),
+            (
+                BarrierKind::DownAndIn,
+                OptionKind::Call,
+                100.0,
+                [13.8333, 7.8494, 3.9795],
+            ),
+            (
+                BarrierKind::UpAndIn,
+                OptionKind::Call,
+                105.0,
+                [14.1112, 8.4482, 4.5910],
+            ),
+            (
+                BarrierKind::DownAndIn,
+                OptionKind::Put,
+                95.0,
+                [2.9586, 6.5677, 11.9752],
+            ),
+            (
+                BarrierKind::DownAndIn,
+                OptionKind::Put,
+                100.0,
+                [2.2845, 5.9085, 11.6465],
+            ),
+            (
+                BarrierKind::UpAndIn,
+                OptionKind::Put,
+                105.0,
+                [1.4653, 3.3721, 7.0846],
+            ),
+            (
+                BarrierKind::DownAndOut,
+                OptionKind::Put,
+                95.0,
+                [2.2798, 2.2947, 2.6252],
+            ),
+            (
+                BarrierKind::DownAndOut,
+                OptionKind::Put,
+                100.0,
+                [3.0, 3.0, 3.0],
+            ),
+            (
+                BarrierKind::UpAndOut,
+                OptionKind::Put,
+                105.0,
+                [3.7760, 5.4932, 7.5187],
+            ),
+        ];
+        for &(kind, opt, barrier, expected) in &table {
+            for (&strike, &expected) in [90.0, 100.0, 110.0].iter().zip(&expected) {
+                let result = BarrierModel::try_new(haug(opt, strike), kind, barrier, 3.0)
+                    .unwrap()
+                    .price()
+                    .unwrap();
+                assert!(
+                    (result - expected).abs() < 1e-4,
+                    "{:?} {:?} {}: {} is not {}",
+                    kind,
+                    opt,
+                    strike,
+                    result,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn in_out_parity() {
+        for &opt in &[OptionKind::Call, OptionKind::Put] {
+            for &strike in &[80.0, 95.0, 100.0, 105.0, 120.0] {
+                let model = haug(opt, strike);
+                let vanilla = model.price().unwrap();
+                for &(barrier, kinds) in &[
+                    (90.0, [BarrierKind::DownAndIn, BarrierKind::DownAndOut]),
+                    (110.0, [BarrierKind::UpAndIn, BarrierKind::UpAndOut]),
+                ] {
+                    for &monitoring in &[Monitoring::Continuous, Monitoring::Discrete(1.0 / 52.0)] {
+                        let price = |kind| {
+                            BarrierModel::new(model, kind, barrier, 0.0)
+                                .with_monitoring(monitoring)
+                                .price()
+                                .unwrap()
+                        };
+                        let result = price(kinds[0]) + price(kinds[1]);
+                        assert!(
+                            (result - vanilla).abs() < 1e-10,
+                            "{} is not {}",
+                            result,
+                            vanilla
+                        );
+                    }
+                }
+
+                let price = |kind| {
+                    DoubleBarrierModel::new(model, kind, 85.0, 115.0)
+                        .price()
+                        .unwrap()
+                };
+                let result = price(DoubleBarrierKind::KnockIn) + price(DoubleBarrierKind::KnockOut);
+                assert!(
+                    (result - vanilla).abs() < 1e-10,
+                    "{} is not {}",
+                    result,
+                    vanilla
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn in_out_parity_with_negative_rates() {
+        // mu^2 + 2r/vol^2 is negative, which only the rebate paid at the hit depends on
+        for &opt in &[OptionKind::Call, OptionKind::Put] {
+            for &strike in &[80.0, 100.0, 120.0] {
+                let model =
+                    BlackScholesModel::new(opt, strike, 100.0, -0.01, 0.2, 1.0, Some(-0.01));
+                let vanilla = model.price().unwrap();
+                for &(barrier, kinds) in &[
+                    (90.0, [BarrierKind::DownAndIn, BarrierKind::DownAndOut]),
+                    (110.0, [BarrierKind::UpAndIn, BarrierKind::UpAndOut]),
+                ] {
+                    let price = |kind| {
+                        BarrierModel::new(model, kind, barrier, 0.0)
+                            .price()
+                            .unwrap()
+                    };
+                    assert_close(price(kinds[0]) + price(kinds[1]), vanilla, 1e-10);
+                }
+            }
+        }
+
+        let model =
+            BlackScholesModel::new(OptionKind::Call, 100.0, 100.0, -0.01, 0.2, 1.0, Some(-0.01));
+        let result = BarrierModel::new(model, BarrierKind::DownAndOut, 90.0, 3.0).price();
+        assert_eq!(
+            result,
+            Err(MathError::ParameterOutOfBounds("interest_rate", -0.01))
+        );
+    }
+
+    #[test]
+    fn breached_barriers() {
+        let model = haug(OptionKind::Call, 100.0);
+        for &kind in &KINDS {
+            let result = BarrierModel::new(model, kind, 100.0, 3.0).price().unwrap();
+            let expected = match kind {
+                BarrierKind::DownAndIn | BarrierKind::UpAndIn => model.price().unwrap(),
+                BarrierKind::DownAndOut | BarrierKind::UpAndOut => 3.0,
+            };
+            assert_eq!(result, expected);
+        }
+        let result = DoubleBarrierModel::new(model, DoubleBarrierKind::KnockOut, 100.0, 120.0);
+        assert_eq!(result.price(), Ok(0.0));
+    }
+
+    #[test]
+    fn ikeda_kunitomo() {
+        // references from the eigenfunction expansion of the killed Brownian motion
+        let call =
+            BlackScholesModel::new(OptionKind::Call, 100.0, 100.0, 0.05, 0.25, 0.5, Some(0.02));
+        let put = BlackScholesModel::new(OptionKind::Put, 105.0, 100.0, 0.03, 0.2, 1.0, None);
+        for &(model, lower, upper, expected) in &[
+            (call, 80.0, 130.0, 3.543864539880451),
+            (put, 85.0, 120.0, 1.240099430736284),
+        ] {
+            let result =
+                DoubleBarrierModel::try_new(model, DoubleBarrierKind::KnockOut, lower, upper)
+                    .unwrap()
+                    .price()
+                    .unwrap();
+            assert!(
+                (result - expected).abs() < 1e-10,
+                "{} is not {}",
+                result,
+                expected
+            );
+        }
+
+        // barriers far away leave the vanilla
+        let result = DoubleBarrierModel::new(call, DoubleBarrierKind::KnockOut, 1.0, 1e4)
+            .price()
+            .unwrap();
+        assert!((result - call.price().unwrap()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn discrete_monitoring_matches_simulation() {
+        // daily monitoring over half a year
+        let model = BlackScholesModel::new(OptionKind::Call, 100.0, 100.0, 0.05, 0.3, 0.5, None);
+        let steps = 126;
+        let interval = model.time_to_expire / steps as f64;
+        let simulated = MonteCarloModel::from(model)
+            .with_paths(100_000)
+            .with_steps(steps)
+            .with_antithetic(true)
+            .with_seed(7)
+            .price_with(|path| {
+                if path.iter().any(|&s| s <= 90.0) {
+                    0.0
+                } else {
+                    (path[steps] - 100.0).max(0.0)
+                }
+            })
+            .unwrap();
+        let result = BarrierModel::new(model, BarrierKind::DownAndOut, 90.0, 0.0)
+            .with_monitoring(Monitoring::Discrete(interval))
+            .price()
+            .unwrap();
+        let continuous = BarrierModel::new(model, BarrierKind::DownAndOut, 90.0, 0.0)
+            .price()
+            .unwrap();
+        assert!(
+            (result - simulated.price).abs() < 3.0 * simulated.standard_error,
+            "{} is not {:?}",
+            result,
+            simulated
+        );
+        assert!(result > continuous);
+
+        let double = DoubleBarrierModel::new(model, DoubleBarrierKind::KnockOut, 80.0, 140.0);
+        let simulated = MonteCarloModel::from(model)
+            .with_paths(100_000)
+            .with_steps(steps)
+            .with_antithetic(true)
+            .with_seed(7)
+            .price_with(|path| {
+                if path.iter().any(|&s| s <= 80.0 || s >= 140.0) {
+                    0.0
+                } else {
+                    (path[steps] - 100.0).max(0.0)
+                }
+            })
+            .unwrap();
+        let result = double
+            .with_monitoring(Monitoring::Discrete(interval))
+            .price()
+            .unwrap();
+        assert!(
+            (result - simulated.price).abs() < 3.0 * simulated.standard_error,
+            "{} is not {:?}",
+            result,
+            simulated
+        );
+    }
+
+    #[test]
+    fn err_invalid_barriers() {
+        let model = haug(OptionKind::Put, 100.0);
+        assert_eq!(
+            BarrierModel::try_new(model, BarrierKind::DownAndOut, 0.0, 0.0),
+            Err(MathError::ParameterOutOfBounds("barrier", 0.0))
+        );
+        assert_eq!(
+            BarrierModel::try_new(model, BarrierKind::DownAndOut, 90.0, -1.0),
+            Err(MathError::ParameterOutOfBounds("rebate", -1.0))
+        );
+        assert_eq!(
+            DoubleBarrierModel::try_new(model, DoubleBarrierKind::KnockIn, 110.0, 90.0),
+            Err(MathError::ParameterOutOfBounds("upper", 90.0))
+        );
+        let result = BarrierModel::new(model, BarrierKind::UpAndIn, 110.0, 0.0)
+            .with_monitoring(Monitoring::Discrete(0.0))
+            .price();
+        assert_eq!(
+            result,
+            Err(MathError::ParameterOutOfBounds("interval", 0.0))
+        );
+    }
+}
@@ -0,0 +1,393 @@
+use crate::{BlackScholesModel, Greeks, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+const DEFAULT_STEPS: usize = 200;
+
+// ExerciseStyle tells when the holder is allowed to exercise the option
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExerciseStyle {
+    European, // only at expiration
+    American, // at any time up to expiration
+}
+
+// Lattice selects how the up/down moves and their probabilities are parameterised
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lattice {
+    CoxRossRubinstein, // recombining tree with u = 1/d
+    JarrowRudd,        // equal probability tree matching the drift of log prices
+    LeisenReimer,      // Peizer-Pratt inversion of d1/d2, requires an odd number of steps
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BinomialModel {
+    model: BlackScholesModel, // market inputs shared with the closed-form model
+    steps: usize,             // number of time steps in the tree
+    exercise: ExerciseStyle,  // european or american exercise
+    lattice: Lattice,         // parameterisation of the tree
+}
+
+// Tree keeps the first nodes of a lattice which are needed for Greeks
+struct Tree {
+    price: f64,
+    step_one: [(f64, f64); 2], // (stock, value) at the down and up nodes
+    step_two: [(f64, f64); 3], // (stock, value) from the lowest to the highest node
+}
+
+impl BinomialModel {
+    // new creates a Cox-Ross-Rubinstein tree for a european option, use with_steps,
+    // with_exercise and with_lattice to change the defaults
+    pub fn new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+    ) -> BinomialModel {
+        BinomialModel::from(BlackScholesModel::new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        ))
+    }
+
+    // try_new creates the tree like new does, but rejects inputs for which the
+    // Black-Scholes formula is undefined
+    pub fn try_new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+    ) -> MathResult<BinomialModel> {
+        BlackScholesModel::try_new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        )
+        .map(BinomialModel::from)
+    }
+
+    pub fn with_steps(mut self, steps: usize) -> BinomialModel {
+        self.steps = steps;
+        self
+    }
+
+    pub fn with_exercise(mut self, exercise: ExerciseStyle) -> BinomialModel {
+        self.exercise = exercise;
+        self
+    }
+
+    pub fn with_lattice(mut self, lattice: Lattice) -> BinomialModel {
+        self.lattice = lattice;
+        self
+    }
+
+    // price calculates the fair value of the option ($$$ per share), at expiration
+    // that's the intrinsic value whatever the exercise style
+    pub fn price(&self) -> MathResult {
+        if self.model.time_to_expire == 0.0 {
+            return Ok(self.intrinsic(self.model.stock));
+        }
+        Ok(self.build(1)?.price)
+    }
+
+    // greeks takes delta, gamma and theta from the nodes of the tree, the rest of the
+    // sensitivities are calculated by rebuilding the tree with bumped inputs
+    //
+    // At expiration there's no tree, the greeks are those of the intrinsic value
+    pub fn greeks(&self) -> MathResult<Greeks> {
+        if self.model.time_to_expire == 0.0 {
+            return self.model.greeks();
+        }
+        let tree = self.build(2)?;
+        let [(down_stock, down_value), (up_stock, up_value)] = tree.step_one;
+        let [(low_stock, low_value), (mid_stock, mid_value), (high_stock, high_value)] =
+            tree.step_two;
+
+        let delta = (up_value - down_value) / (up_stock - down_stock);
+        let gamma = ((high_value - mid_value) / (high_stock - mid_stock)
+            - (mid_value - low_value) / (mid_stock - low_stock))
+            / (0.5 * (high_stock - low_stock));
+        // the middle node after two steps only sits at the initial stock price in
+        // recombining u = 1/d trees, so its value is shifted back along the tree's own
+        // delta and gamma before taking the time difference
+        let shift = self.model.stock - mid_stock;
+        let mid_delta = (high_value - low_value) / (high_stock - low_stock);
+        let theta = (mid_value + mid_delta * shift + 0.5 * gamma * shift.powi(2) - tree.price)
+            / (2.0 * self.time_step());
+
+        Ok(Greeks {
+            delta,
+            gamma,
+            vega: self.bumped(|m, h| m.volatility += h)?,
+            theta,
+            rho: self.bumped(|m, h| m.interest_rate += h)?,
+            dividend_rho: self
+                .bumped(|m, h| m.dividend = Some(m.dividend.unwrap_or_default() + h))?,
+        })
+    }
+
+    fn bumped(&self, bump: fn(&mut BlackScholesModel, f64)) -> MathResult {
+        let h = 1e-4;
+        let mut up = *self;
+        bump(&mut up.model, h);
+        let mut down = *self;
+        bump(&mut down.model, -h);
+        Ok((up.price()? - down.price()?) / (2.0 * h))
+    }
+
+    fn steps(&self) -> usize {
+        match self.lattice {
+            Lattice::LeisenReimer if self.steps % 2 == 0 => self.steps + 1,
+            _ => self.steps,
+        }
+    }
+
+    fn time_step(&self) -> f64 {
+        self.model.time_to_expire / self.steps() as f64
+    }
+
+    // moves returns the up and down factors with the probability of the up move
+    fn moves(&self) -> (f64, f64, f64) {
+        let m = &self.model;
+        let dt = self.time_step();
+        let carry = m.interest_rate - m.dividend.unwrap_or_default();
+        let growth = E.powf(carry * dt);
+
+        match self.lattice {
+            Lattice::CoxRossRubinstein => {
+                let up = E.powf(m.volatility * dt.sqrt());
+                let down = 1.0 / up;
+                (up, down, (growth - down) / (up - down))
+            }
+            Lattice::JarrowRudd => {
+                let drift = (carry - m.volatility.powi(2) / 2.0) * dt;
+                let diffusion = m.volatility * dt.sqrt();
+                (E.powf(drift + diffusion), E.powf(drift - diffusion), 0.5)
+            }
+            Lattice::LeisenReimer => {
+                let (d1, d2) = m.d1_d2();
+                let steps = self.steps();
+                let p = peizer_pratt(d2, steps);
+                let up = growth * peizer_pratt(d1, steps) / p;
+                let down = (growth - p * up) / (1.0 - p);
+                (up, down, p)
+            }
+        }
+    }
+
+    fn intrinsic(&self, stock: f64) -> f64 {
+        match self.model.opt {
+            OptionKind::Call => (stock - self.model.strike).max(0.0),
+            OptionKind::Put => (self.model.strike - stock).max(0.0),
+        }
+    }
+
+    fn build(&self, min_steps: usize) -> MathResult<Tree> {
+        let steps = self.steps();
+        if steps < min_steps {
+            return Err(MathError::TooFewSteps(steps));
+        }
+        let (up, down, p) = self.moves();
+        let discount = E.powf(-self.model.interest_rate * self.time_step());
+        let stock_at = |step: usize, ups: usize| {
+            self.model.stock * up.powi(ups as i32) * down.powi((step - ups) as i32)
+        };
+
+        let mut values: Vec<f64> = (0..=steps)
+            .map(|ups| self.intrinsic(stock_at(steps, ups)))
+            .collect();
+        let mut tree = Tree {
+            price: 0.0,
+            step_one: [(0.0, 0.0); 2],
+            step_two: [(0.0, 0.0); 3],
+        };
+        // the nodes of the first two steps are kept for the greeks, they can be the
+        // terminal nodes of short trees
+        let record = |tree: &mut Tree, step: usize, values: &[f64]| match step {
+            2 => {
+                for (ups, node) in tree.step_two.iter_mut().enumerate() {
+                    *node = (stock_at(2, ups), values[ups]);
+                }
+            }
+            1 => {
+                for (ups, node) in tree.step_one.iter_mut().enumerate() {
+                    *node = (stock_at(1, ups), values[ups]);
+                }
+            }
+            _ => {}
+        };
+        record(&mut tree, steps, &values);
+
+        for step in (0..steps).rev() {
+            for ups in 0..=step {
+                let continuation = discount * (p * values[ups + 1] + (1.0 - p) * values[ups]);
+                values[ups] = match self.exercise {
+                    ExerciseStyle::European => continuation,
+                    ExerciseStyle::American => {
+                        continuation.max(self.intrinsic(stock_at(step, ups)))
+                    }
+                };
+            }
+            record(&mut tree, step, &values);
+        }
+        tree.price = values[0];
+        Ok(tree)
+    }
+}
+
+impl From<BlackScholesModel> for BinomialModel {
+    fn from(model: BlackScholesModel) -> BinomialModel {
+        BinomialModel {
+            model,
+            steps: DEFAULT_STEPS,
+            exercise: ExerciseStyle::European,
+            lattice: Lattice::CoxRossRubinstein,
+        }
+    }
+}
+
+// peizer_pratt is the Peizer-Pratt method 2 inversion used by Leisen-Reimer trees,
+// it maps a normal quantile to the binomial probability for the given number of steps
+fn peizer_pratt(z: f64, steps: usize) -> f64 {
+    let n = steps as f64;
+    let exponent = -(z / (n + 1.0 / 3.0 + 0.1 / (n + 1.0))).powi(2) * (n + 1.0 / 6.0);
+    0.5 + z.signum() * 0.5 * (1.0 - E.powf(exponent)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_close;
+
+    fn tree(opt: OptionKind) -> BinomialModel {
+        BinomialModel::new(opt, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125))
+    }
+
+    #[test]
+    fn european_lattices_converge_to_black_scholes() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let expected = tree(opt).model.price().unwrap();
+            for (lattice, tolerance) in [
+                (Lattice::CoxRossRubinstein, 1e-2),
+                (Lattice::JarrowRudd, 1e-2),
+                (Lattice::LeisenReimer, 1e-4),
+            ] {
+                let result = tree(opt)
+                    .with_lattice(lattice)
+                    .with_steps(501)
+                    .price()
+                    .unwrap();
+                assert_close(result, expected, tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn leisen_reimer_rounds_up_to_odd_steps() {
+        let even = tree(OptionKind::Put)
+            .with_lattice(Lattice::LeisenReimer)
+            .with_steps(100);
+        let odd = even.with_steps(101);
+        assert_eq!(even.price().unwrap(), odd.price().unwrap());
+    }
+
+    #[test]
+    fn american_put() {
+        // Hull, Options, Futures and Other Derivatives: S = 50, K = 50, r = 10%, vol = 40%,
+        // 5 months, the 5-step tree gives 4.49 and the limit is about 4.28
+        let model = BinomialModel::new(OptionKind::Put, 50.0, 50.0, 0.1, 0.4, 5.0 / 12.0, None)
+            .with_exercise(ExerciseStyle::American);
+
+        assert_close(model.with_steps(5).price().unwrap(), 4.49, 5e-3);
+        assert_close(model.with_steps(1000).price().unwrap(), 4.28, 1e-2);
+        assert!(
+            model.price().unwrap()
+                > model
+                    .with_exercise(ExerciseStyle::European)
+                    .price()
+                    .unwrap()
+        );
+    }
+
+    #[test]
+    fn american_call_without_dividend_is_european() {
+        let model = BinomialModel::new(OptionKind::Call, 58.0, 60.0, 0.035, 0.2, 0.5, None);
+        let american = model
+            .with_exercise(ExerciseStyle::American)
+            .price()
+            .unwrap();
+        assert_close(american, model.price().unwrap(), 1e-12);
+    }
+
+    #[test]
+    fn greeks_from_tree_match_black_scholes() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = tree(opt)
+                .with_lattice(Lattice::LeisenReimer)
+                .with_steps(301);
+            let expected = model.model.greeks().unwrap();
+            let result = model.greeks().unwrap();
+
+            assert_close(result.delta, expected.delta, 1e-3);
+            assert_close(result.gamma, expected.gamma, 1e-3);
+            assert_close(result.vega, expected.vega, 1e-2);
+            assert_close(result.theta, expected.theta, 1e-2);
+            assert_close(result.rho, expected.rho, 1e-2);
+            assert_close(result.dividend_rho, expected.dividend_rho, 1e-2);
+        }
+    }
+
+    #[test]
+    fn greeks_from_two_steps() {
+        for lattice in [Lattice::CoxRossRubinstein, Lattice::JarrowRudd] {
+            let model = tree(OptionKind::Call).with_lattice(lattice).with_steps(2);
+            let greeks = model.greeks().unwrap();
+            assert!(greeks.delta > 0.0 && greeks.delta < 1.0);
+            assert!(greeks.gamma > 0.0);
+            assert!(greeks.theta < 0.0);
+            assert!(greeks.vega.is_finite() && greeks.rho.is_finite());
+        }
+    }
+
+    #[test]
+    fn at_expiration() {
+        for exercise in [ExerciseStyle::European, ExerciseStyle::American] {
+            let call = BinomialModel::try_new(OptionKind::Call, 58.0, 60.0, 0.035, 0.2, 0.0, None)
+                .unwrap()
+                .with_exercise(exercise);
+            assert_eq!(call.price(), Ok(2.0));
+            let greeks = call.greeks().unwrap();
+            assert_eq!((greeks.delta, greeks.gamma, greeks.vega), (1.0, 0.0, 0.0));
+
+            let put = BinomialModel::new(OptionKind::Put, 58.0, 60.0, 0.035, 0.2, 0.0, None)
+                .with_exercise(exercise);
+            assert_eq!(put.price(), Ok(0.0));
+            assert_eq!(put.greeks().unwrap().delta, 0.0);
+        }
+        assert_eq!(
+            BinomialModel::try_new(OptionKind::Call, 58.0, 60.0, 0.035, 0.2, -1.0, None),
+            Err(MathError::NegativeTimeToExpire(-1.0))
+        );
+    }
+
+    #[test]
+    fn err_with_too_few_steps() {
+        let model = tree(OptionKind::Call).with_steps(1);
+        assert!(model.price().is_ok());
+        assert_eq!(model.greeks(), Err(MathError::TooFewSteps(1)));
+        assert_eq!(model.with_steps(0).price(), Err(MathError::TooFewSteps(0)));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{BlackScholesModel, Greeks, MathResult, OptionKind};
+use std::f64::consts::E;
+
+// Black76Model prices european options on futures and forwards with Black's 1976
+// formula, which is the generalized Black-Scholes-Merton model with the cost of carry
+// of a forward (the dividend yield equals the interest rate)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Black76Model {
+    opt: OptionKind,     // option type (call or put)
+    strike: f64,         // strike price ($$$ per unit)
+    forward: f64,        // forward or futures price ($$$ per unit)
+    interest_rate: f64,  // continuously compounded risk-free interest rate (% p.a.)
+    volatility: f64,     // volatility of the forward (% p.a.)
+    time_to_expire: f64, // time to expiration (% of year)
+}
+
+impl Black76Model {
+    pub fn new(
+        opt: OptionKind,
+        strike: f64,
+        forward: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+    ) -> Black76Model {
+        Black76Model {
+            opt,
+            strike,
+            forward,
+            interest_rate,
+            volatility,
+            time_to_expire,
+        }
+    }
+
+    // try_new creates the model like new does, but rejects inputs for which Black's
+    // formula is undefined
+    pub fn try_new(
+        opt: OptionKind,
+        strike: f64,
+        forward: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+    ) -> MathResult<Black76Model> {
+        BlackScholesModel::try_new(
+            opt,
+            strike,
+            forward,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            Some(interest_rate),
+        )
+        .map(Black76Model::from)
+    }
+
+    pub fn price(&self) -> MathResult {
+        self.as_black_scholes().price()
+    }
+
+    // greeks calculates the sensitivities with respect to the forward price, rho moves
+    // the discount rate only (the forward stays where it is) and dividend rho is always 0
+    pub fn greeks(&self) -> MathResult<Greeks> {
+        let model = self.as_black_scholes();
+        Ok(Greeks {
+            delta: model.delta()?,
+            gamma: model.gamma()?,
+            vega: model.vega()?,
+            theta: model.theta()?,
+            rho: model.rho()? + model.dividend_rho()?,
+            dividend_rho: 0.0,
+        })
+    }
+
+    // as_black_scholes returns the equivalent spot model, a forward is an asset which
+    // yields the risk-free rate
+    fn as_black_scholes(&self) -> BlackScholesModel {
+        BlackScholesModel::new(
+            self.opt,
+            self.strike,
+            self.forward,
+            self.interest_rate,
+            self.volatility,
+            self.time_to_expire,
+            Some(self.interest_rate),
+        )
+    }
+}
+
+// a spot model is converted by moving the stock price forward with the cost of carry
+impl From<BlackScholesModel> for Black76Model {
+    fn from(model: BlackScholesModel) -> Black76Model {
+        let carry = model.interest_rate - model.dividend.unwrap_or_default();
+        Black76Model::new(
+            model.opt,
+            model.strike,
+            model.stock * E.powf(carry * model.time_to_expire),
+            model.interest_rate,
+            model.volatility,
+            model.time_to_expire,
+        )
+    }
+}
+
+// implied_volatility calculates the volatility at which Black76Model::price matches the
+// observed premium ($$$ per unit)
+pub fn implied_volatility(
+    opt: OptionKind,
+    strike: f64,
+    forward: f64,
+    interest_rate: f64,
+    time_to_expire: f64,
+    premium: f64,
+) -> MathResult {
+    crate::implied_volatility(
+        opt,
+        strike,
+        forward,
+        interest_rate,
+        time_to_expire,
+        Some(interest_rate),
+        premium,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_close;
+    use crate::MathError;
+
+    #[test]
+    fn call_price() {
+        // Haug, The Complete Guide to Option Pricing Formulas: F = 19, K = 19, r = 10%,
+        // vol = 28%, 9 months
+        let model = Black76Model::new(OptionKind::Call, 19.0, 19.0, 0.1, 0.28, 0.75);
+        assert_close(model.price().unwrap(), 1.7011, 1e-4);
+    }
+
+    #[test]
+    fn put_call_parity() {
+        let call = Black76Model::new(OptionKind::Call, 95.0, 100.0, 0.03, 0.25, 0.5);
+        let put = Black76Model::new(OptionKind::Put, 95.0, 100.0, 0.03, 0.25, 0.5);
+        let parity = (100.0 - 95.0) * E.powf(-0.03 * 0.5);
+        assert_close(call.price().unwrap() - put.price().unwrap(), parity, 1e-12);
+    }
+
+    #[test]
+    fn converts_from_black_scholes() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let spot = BlackScholesModel::new(opt, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125));
+            let forward = Black76Model::from(spot);
+
+            assert_close(forward.forward, 60.0 * E.powf(0.0225 * 0.5), 1e-12);
+            assert_close(forward.price().unwrap(), spot.price().unwrap(), 1e-12);
+        }
+    }
+
+    #[test]
+    fn greeks_match_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = Black76Model::new(opt, 95.0, 100.0, 0.03, 0.25, 0.5);
+            let greeks = model.greeks().unwrap();
+            let h = 1e-4;
+            let diff = |bump: fn(&mut Black76Model, f64)| {
+                let mut up = model;
+                bump(&mut up, h);
+                let mut down = model;
+                bump(&mut down, -h);
+                (up.price().unwrap() - down.price().unwrap()) / (2.0 * h)
+            };
+
+            assert_close(greeks.delta, diff(|m, h| m.forward += h), 1e-7);
+            assert_close(greeks.vega, diff(|m, h| m.volatility += h), 1e-6);
+            assert_close(greeks.theta, -diff(|m, h| m.time_to_expire += h), 1e-6);
+            assert_close(greeks.rho, diff(|m, h| m.interest_rate += h), 1e-6);
+            assert_close(
+                greeks.rho,
+                -model.time_to_expire * model.price().unwrap(),
+                1e-12,
+            );
+            assert_eq!(greeks.dividend_rho, 0.0);
+        }
+    }
+
+    #[test]
+    fn implied_volatility_recovers_model_volatility() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let premium = Black76Model::new(opt, 95.0, 100.0, 0.03, 0.25, 0.5)
+                .price()
+                .unwrap();
+            let result = implied_volatility(opt, 95.0, 100.0, 0.03, 0.5, premium).unwrap();
+            assert_close(result, 0.25, 1e-10);
+        }
+    }
+
+    #[test]
+    fn err_with_non_positive_forward() {
+        let result = Black76Model::try_new(OptionKind::Call, 95.0, 0.0, 0.03, 0.25, 0.5);
+        assert_eq!(result, Err(MathError::NonPositiveStock(0.0)));
+    }
+}
@@ -0,0 +1,187 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+// Complex is a complex number re + i im, just enough of it for characteristic
+// functions and Fourier transforms
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn exp(self) -> Complex {
+        let scale = self.re.exp();
+        Complex::new(scale * self.im.cos(), scale * self.im.sin())
+    }
+
+    // ln is the principal branch, with the argument in (-pi, pi]
+    pub fn ln(self) -> Complex {
+        Complex::new(self.abs().ln(), self.arg())
+    }
+
+    // sqrt is the principal branch, with a non-negative real part
+    pub fn sqrt(self) -> Complex {
+        let r = self.abs();
+        let re = ((r + self.re) / 2.0).sqrt();
+        let im = ((r - self.re) / 2.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Complex {
+        Complex::new(re, 0.0)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, other: Complex) -> Complex {
+        let scale = other.re * other.re + other.im * other.im;
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / scale,
+            (self.im * other.re - self.re * other.im) / scale,
+        )
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+// mixing with real numbers goes through From<f64>
+impl Add<f64> for Complex {
+    type Output = Complex;
+    fn add(self, other: f64) -> Complex {
+        self + Complex::from(other)
+    }
+}
+
+impl Sub<f64> for Complex {
+    type Output = Complex;
+    fn sub(self, other: f64) -> Complex {
+        self - Complex::from(other)
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Complex;
+    fn mul(self, other: f64) -> Complex {
+        Complex::new(self.re * other, self.im * other)
+    }
+}
+
+impl Div<f64> for Complex {
+    type Output = Complex;
+    fn div(self, other: f64) -> Complex {
+        Complex::new(self.re / other, self.im / other)
+    }
+}
+
+impl Add<Complex> for f64 {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex {
+        Complex::from(self) + other
+    }
+}
+
+impl Sub<Complex> for f64 {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex {
+        Complex::from(self) - other
+    }
+}
+
+impl Mul<Complex> for f64 {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        other * self
+    }
+}
+
+impl Div<Complex> for f64 {
+    type Output = Complex;
+    fn div(self, other: Complex) -> Complex {
+        Complex::from(self) / other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn assert_close(actual: Complex, expected: Complex) {
+        assert!(
+            (actual - expected).abs() < 1e-14,
+            "{:?} is not {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = Complex::new(1.0, 2.0);
+        let b = Complex::new(3.0, -1.0);
+        assert_close(a * b, Complex::new(5.0, 5.0));
+        assert_close(a / b * b, a);
+        assert_close(Complex::I * Complex::I, Complex::from(-1.0));
+        assert_close(2.0 - a, Complex::new(1.0, -2.0));
+    }
+
+    #[test]
+    fn exp_ln_and_sqrt() {
+        assert_close((Complex::I * PI).exp(), Complex::new(-1.0, PI.sin()));
+        let z = Complex::new(-3.0, 4.0);
+        assert_close(z.ln().exp(), z);
+        assert_close(z.sqrt(), Complex::new(1.0, 2.0));
+        assert_close(z.conj().sqrt(), Complex::new(1.0, -2.0));
+        assert_eq!(Complex::from(-4.0).sqrt(), Complex::new(0.0, 2.0));
+    }
+}
@@ -0,0 +1,387 @@
+use crate::distributions::{norm_cdf, norm_pdf};
+use crate::{check_finite, BlackScholesModel, Greeks, MathError, MathResult, OptionKind};
+
+// DigitalPayoff is what a digital pays when it expires in the money, that is above the
+// strike for calls and below it for puts
+//
+// A cash-or-nothing option pays a fixed amount, an asset-or-nothing option the stock
+// and a gap option the difference between the stock and its payoff strike, which
+// unlike a vanilla option can be negative
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DigitalPayoff {
+    CashOrNothing(f64), // cash amount ($$$ per share)
+    AssetOrNothing,
+    Gap(f64), // payoff strike ($$$ per share)
+}
+
+// DigitalModel prices digital and gap options under the Black-Scholes model, the
+// strike of the model is the trigger which decides whether the option pays
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DigitalModel {
+    model: BlackScholesModel, // contract and market inputs
+    payoff: DigitalPayoff,    // amount paid in the money
+}
+
+impl DigitalModel {
+    pub fn new(model: BlackScholesModel, payoff: DigitalPayoff) -> DigitalModel {
+        DigitalModel { model, payoff }
+    }
+
+    // try_new creates the model like new does, but rejects market inputs for which the
+    // Black-Scholes formula is undefined and amounts which are not finite
+    pub fn try_new(model: BlackScholesModel, payoff: DigitalPayoff) -> MathResult<DigitalModel> {
+        BlackScholesModel::try_new(
+            model.opt,
+            model.strike,
+            model.stock,
+            model.interest_rate,
+            model.volatility,
+            model.time_to_expire,
+            model.dividend,
+        )?;
+        match payoff {
+            DigitalPayoff::CashOrNothing(cash) => check_finite("cash", cash)?,
+            DigitalPayoff::AssetOrNothing => {}
+            DigitalPayoff::Gap(strike) => check_finite("payoff_strike", strike)?,
+        }
+        Ok(DigitalModel::new(model, payoff))
+    }
+
+    // price calculates the fair value of the option ($$$ per share)
+    pub fn price(&self) -> MathResult {
+        let m = &self.model;
+        if m.volatility * m.time_to_expire.sqrt() == 0.0 {
+            let forward = m.stock * m.dividend_discount() / m.discount();
+            let in_the_money = match m.opt {
+                OptionKind::Call => forward > m.strike,
+                OptionKind::Put => forward < m.strike,
+            };
+            if !in_the_money {
+                return Ok(0.0);
+            }
+            let (cash, asset) = self.weights();
+            return Ok(cash * m.discount() + asset * m.stock * m.dividend_discount());
+        }
+        Ok(self.sensitivities()[0])
+    }
+
+    // greeks calculates all first-order sensitivities of the option price at once
+    //
+    // Without time value the option pays for sure or not at all, and the greeks are
+    // those of the discounted payment, which is undefined at the money
+    pub fn greeks(&self) -> MathResult<Greeks> {
+        let m = &self.model;
+        if let Some(direction) = m.without_time_value()? {
+            let paid = direction.abs();
+            let (cash, asset) = self.weights();
+            let cash = paid * cash * m.discount();
+            let asset = paid * asset * m.stock * m.dividend_discount();
+            return Ok(Greeks {
+                delta: asset / m.stock,
+                gamma: 0.0,
+                vega: 0.0,
+                theta: m.interest_rate * cash + m.dividend.unwrap_or_default() * asset,
+                rho: -m.time_to_expire * cash,
+                dividend_rho: -m.time_to_expire * asset,
+            });
+        }
+        let [_, delta, gamma, vega, theta, rho, dividend_rho] = self.sensitivities();
+        Ok(Greeks {
+            delta,
+            gamma,
+            vega,
+            theta,
+            rho,
+            dividend_rho,
+        })
+    }
+
+    // call_spread prices the option with vanilla options, replacing the jump at the
+    // strike by a call spread (put spread for puts) of the width centred on it
+    //
+    // The replication converges to price as the width shrinks, with an error which is
+    // quadratic in the width
+    pub fn call_spread(&self, width: f64) -> MathResult {
+        let m = &self.model;
+        check_finite("width", width)?;
+        if width <= 0.0 || width >= 2.0 * m.strike {
+            return Err(MathError::ParameterOutOfBounds("width", width));
+        }
+        let phi = match m.opt {
+            OptionKind::Call => 1.0,
+            OptionKind::Put => -1.0,
+        };
+        let vanilla = |strike: f64| BlackScholesModel { strike, ..*m }.price();
+        let digital =
+            phi * (vanilla(m.strike - width / 2.0)? - vanilla(m.strike + width / 2.0)?) / width;
+
+        // asset-or-nothing and gap options are a vanilla option plus cash-or-nothing
+        Ok(match self.payoff {
+            DigitalPayoff::CashOrNothing(cash) => cash * digital,
+            DigitalPayoff::AssetOrNothing => phi * vanilla(m.strike)? + m.strike * digital,
+            DigitalPayoff::Gap(strike) => vanilla(m.strike)? + phi * (m.strike - strike) * digital,
+        })
+    }
+
+    // weights returns the cash and the number of shares paid in the money
+    fn weights(&self) -> (f64, f64) {
+        let phi = match self.model.opt {
+            OptionKind::Call => 1.0,
+            OptionKind::Put => -1.0,
+        };
+        match self.payoff {
+            DigitalPayoff::CashOrNothing(cash) => (cash, 0.0),
+            DigitalPayoff::AssetOrNothing => (0.0, 1.0),
+            DigitalPayoff::Gap(strike) => (-phi * strike, phi),
+        }
+    }
+
+    // sensitivities returns the price and the greeks in the order of Greeks, as the
+    // weighted sum of a unit cash-or-nothing and an asset-or-nothing option
+    fn sensitivities(&self) -> [f64; 7] {
+        let m = &self.model;
+        let phi = match m.opt {
+            OptionKind::Call => 1.0,
+            OptionKind::Put => -1.0,
+        };
+        let dividend = m.dividend.unwrap_or_default();
+        let carry = m.interest_rate - dividend;
+        let sqrt_time = m.time_to_expire.sqrt();
+        let deviation = m.volatility * sqrt_time;
+        let (d1, d2) = m.d1_d2();
+
+        // changes of d1 and d2 as time to expiration passes
+        let d1_time =
+            (carry + m.volatility.powi(2) / 2.0) / deviation - d1 / (2.0 * m.time_to_expire);
+        let d2_time =
+            (carry - m.volatility.powi(2) / 2.0) / deviation - d2 / (2.0 * m.time_to_expire);
+
+        let cash_density = phi * m.discount() * norm_pdf(d2);
+        let cash_price = m.discount() * norm_cdf(phi * d2);
+        let cash = [
+            cash_price,
+            cash_density / (m.stock * deviation),
+            -cash_density * d1 / (m.stock * deviation).powi(2),
+            -cash_density * d1 / m.volatility,
+            m.interest_rate * cash_price - cash_density * d2_time,
+            -m.time_to_expire * cash_price + cash_density * sqrt_time / m.volatility,
+            -cash_density * sqrt_time / m.volatility,
+        ];
+
+        let asset_density = phi * m.stock * m.dividend_discount() * norm_pdf(d1);
+        let asset_price = m.stock * m.dividend_discount() * norm_cdf(phi * d1);
+        let asset = [
+            asset_price,
+            asset_price / m.stock + asset_density / (m.stock * deviation),
+            -asset_density * d2 / (m.stock * deviation).powi(2),
+            -asset_density * d2 / m.volatility,
+            dividend * asset_price - asset_density * d1_time,
+            asset_density * sqrt_time / m.volatility,
+            -m.time_to_expire * asset_price - asset_density * sqrt_time / m.volatility,
+        ];
+
+        let (cash_weight, asset_weight) = self.weights();
+        let mut result = [0.0; 7];
+        for (r, (c, a)) in result.iter_mut().zip(cash.iter().zip(&asset)) {
+            *r = cash_weight * c + asset_weight * a;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_relative, finite_difference_greeks};
+
+    const PAYOFFS: [DigitalPayoff; 3] = [
+        DigitalPayoff::CashOrNothing(10.0),
+        DigitalPayoff::AssetOrNothing,
+        DigitalPayoff::Gap(95.0),
+    ];
+
+    #[test]
+    fn haug() {
+        // Haug (2007), the gap call is triggered at 50 and pays the stock less 57
+        for &(model, payoff, expected) in &[
+            (
+                BlackScholesModel::new(OptionKind::Put, 80.0, 100.0, 0.06, 0.35, 0.75, Some(0.06)),
+                DigitalPayoff::CashOrNothing(10.0),
+                2.67104568446135,
+            ),
+            (
+                BlackScholesModel::new(OptionKind::Put, 65.0, 70.0, 0.07, 0.27, 0.5, Some(0.05)),
+                DigitalPayoff::AssetOrNothing,
+                20.2069472983685,
+            ),
+            (
+                BlackScholesModel::new(OptionKind::Call, 50.0, 50.0, 0.09, 0.2, 0.5, None),
+                DigitalPayoff::Gap(57.0),
+                -0.00525248925878,
+            ),
+        ] {
+            let result = DigitalModel::try_new(model, payoff)
+                .unwrap()
+                .price()
+                .unwrap();
+            assert!(
+                (result - expected).abs() < 1e-10,
+                "{} is not {}",
+                result,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn decompositions() {
+        let call =
+            BlackScholesModel::new(OptionKind::Call, 105.0, 100.0, 0.05, 0.25, 1.0, Some(0.02));
+        let put = BlackScholesModel {
+            opt: OptionKind::Put,
+            ..call
+        };
+        let price = |model, payoff| DigitalModel::new(model, payoff).price().unwrap();
+        let cash = DigitalPayoff::CashOrNothing(1.0);
+        let asset = DigitalPayoff::AssetOrNothing;
+
+        // a call and a put always pay between them
+        let result = price(call, cash) + price(put, cash);
+        assert!((result - call.discount()).abs() < 1e-12);
+        let result = price(call, asset) + price(put, asset);
+        assert!((result - call.stock * call.dividend_discount()).abs() < 1e-12);
+
+        // vanilla options are gap options paying at their strike
+        for &model in &[call, put] {
+            let vanilla = model.price().unwrap();
+            let result = price(model, DigitalPayoff::Gap(model.strike));
+            assert!(
+                (result - vanilla).abs() < 1e-12,
+                "{} is not {}",
+                result,
+                vanilla
+            );
+        }
+        let result = price(call, asset) - call.strike * price(call, cash);
+        assert!((result - call.price().unwrap()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn greeks_match_finite_differences() {
+        for &opt in &[OptionKind::Call, OptionKind::Put] {
+            let model = BlackScholesModel::new(opt, 105.0, 100.0, 0.05, 0.25, 0.75, Some(0.02));
+            for &payoff in &PAYOFFS {
+                let greeks = DigitalModel::new(model, payoff).greeks().unwrap();
+                let expected = finite_difference_greeks(&model, |m| {
+                    DigitalModel::new(*m, payoff).price().unwrap()
+                });
+                assert_relative(greeks.delta, expected.delta, 1e-4);
+                assert_relative(greeks.gamma, expected.gamma, 1e-4);
+                assert_relative(greeks.vega, expected.vega, 1e-4);
+                assert_relative(greeks.theta, expected.theta, 1e-4);
+                assert_relative(greeks.rho, expected.rho, 1e-4);
+                assert_relative(greeks.dividend_rho, expected.dividend_rho, 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn call_spread_converges() {
+        for &opt in &[OptionKind::Call, OptionKind::Put] {
+            let model = BlackScholesModel::new(opt, 105.0, 100.0, 0.05, 0.25, 0.75, Some(0.02));
+            for &payoff in &PAYOFFS {
+                let digital = DigitalModel::new(model, payoff);
+                let expected = digital.price().unwrap();
+                let errors: Vec<f64> = [8.0, 4.0, 2.0, 1.0]
+                    .iter()
+                    .map(|&width| (digital.call_spread(width).unwrap() - expected).abs())
+                    .collect();
+
+                // halving the width quarters the error
+                for pair in errors.windows(2) {
+                    let ratio = pair[0] / pair[1];
+                    assert!(
+                        (ratio - 4.0).abs() < 0.1,
+                        "{:?} {:?}: {:?}",
+                        opt,
+                        payoff,
+                        errors
+                    );
+                }
+                let result = digital.call_spread(1e-2).unwrap();
+                assert!(
+                    (result - expected).abs() < 1e-5,
+                    "{} is not {}",
+                    result,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn expired() {
+        let model = BlackScholesModel::new(OptionKind::Put, 105.0, 100.0, 0.05, 0.25, 0.0, None);
+        let price = |payoff| DigitalModel::new(model, payoff).price().unwrap();
+        assert_eq!(price(DigitalPayoff::CashOrNothing(10.0)), 10.0);
+        assert_eq!(price(DigitalPayoff::AssetOrNothing), 100.0);
+        assert_eq!(price(DigitalPayoff::Gap(110.0)), 10.0);
+        let call = BlackScholesModel {
+            opt: OptionKind::Call,
+            ..model
+        };
+        let result = DigitalModel::new(call, DigitalPayoff::CashOrNothing(10.0)).price();
+        assert_eq!(result, Ok(0.0));
+    }
+
+    #[test]
+    fn greeks_without_time_value() {
+        for &opt in &[OptionKind::Call, OptionKind::Put] {
+            for &strike in &[90.0, 115.0] {
+                let model = BlackScholesModel::new(opt, strike, 100.0, 0.05, 0.0, 0.75, Some(0.02));
+                let almost = BlackScholesModel {
+                    volatility: 1e-3,
+                    ..model
+                };
+                for &payoff in &PAYOFFS {
+                    let result = DigitalModel::new(model, payoff).greeks().unwrap();
+                    let expected = DigitalModel::new(almost, payoff).greeks().unwrap();
+                    assert_relative(result.delta, expected.delta, 1e-12);
+                    assert_relative(result.gamma, expected.gamma, 1e-12);
+                    assert_relative(result.vega, expected.vega, 1e-12);
+                    assert_relative(result.theta, expected.theta, 1e-12);
+                    assert_relative(result.rho, expected.rho, 1e-12);
+                    assert_relative(result.dividend_rho, expected.dividend_rho, 1e-12);
+                }
+            }
+        }
+
+        let expired = BlackScholesModel::new(OptionKind::Put, 105.0, 100.0, 0.05, 0.25, 0.0, None);
+        let greeks = DigitalModel::new(expired, DigitalPayoff::AssetOrNothing)
+            .greeks()
+            .unwrap();
+        assert_eq!((greeks.delta, greeks.gamma, greeks.vega), (1.0, 0.0, 0.0));
+        let at_the_money = BlackScholesModel {
+            strike: 100.0,
+            ..expired
+        };
+        assert_eq!(
+            DigitalModel::new(at_the_money, DigitalPayoff::CashOrNothing(10.0)).greeks(),
+            Err(MathError::AtTheMoneyWithoutTimeValue(100.0))
+        );
+    }
+
+    #[test]
+    fn err_invalid_payoff() {
+        let model = BlackScholesModel::new(OptionKind::Call, 100.0, 100.0, 0.05, 0.2, 1.0, None);
+        assert!(matches!(
+            DigitalModel::try_new(model, DigitalPayoff::Gap(f64::NAN)),
+            Err(MathError::NonFiniteInput("payoff_strike", _))
+        ));
+        let digital = DigitalModel::new(model, DigitalPayoff::AssetOrNothing);
+        assert_eq!(
+            digital.call_spread(0.0),
+            Err(MathError::ParameterOutOfBounds("width", 0.0))
+        );
+    }
+}
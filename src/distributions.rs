@@ -0,0 +1,477 @@
+use crate::quadrature::{LEGENDRE_12, LEGENDRE_20, LEGENDRE_6};
+use std::f64::consts::PI;
+
+// 1 / sqrt(2 pi)
+const FRAC_1_SQRT_2PI: f64 = 0.3989422804014327;
+// boundary between the central and the tail approximations of norm_cdf
+const SQRT_32: f64 = 5.656854249492381;
+
+// Cody's rational Chebyshev approximations, "Rational Chebyshev approximations for
+// the error function" (1969), as used by the ANORM routine of SPECFUN
+const A: [f64; 5] = [
+    2.2352520354606837,
+    1.6102823106855587e2,
+    1.0676894854603709e3,
+    1.815498125334356e4,
+    6.568233791820745e-2,
+];
+const B: [f64; 4] = [
+    4.7202581904688245e1,
+    9.760985517377767e2,
+    1.0260932208618979e4,
+    4.550778933502673e4,
+];
+const C: [f64; 9] = [
+    3.9894151208813466e-1,
+    8.883149794388377,
+    9.350665613217785e1,
+    5.972702763948002e2,
+    2.4945375852903726e3,
+    6.848190450536283e3,
+    1.160265143764735e4,
+    9.842714838383978e3,
+    1.0765576773720192e-8,
+];
+const D: [f64; 8] = [
+    2.2266688044328117e1,
+    2.35387901782625e2,
+    1.5193775994075547e3,
+    6.485558298266761e3,
+    1.8615571640885097e4,
+    3.490095272114598e4,
+    3.891200328609327e4,
+    1.9685429676859992e4,
+];
+const P: [f64; 6] = [
+    2.15898534057957e-1,
+    1.2740116116024736e-1,
+    2.2235277870649807e-2,
+    1.4216191932278934e-3,
+    2.9112874951168793e-5,
+    2.3073441764940174e-2,
+];
+const Q: [f64; 5] = [
+    1.284260096144911,
+    4.682382124808651e-1,
+    6.598813786892856e-2,
+    3.7823963320275824e-3,
+    7.297515550839662e-5,
+];
+
+// Wichura's algorithm AS241 (PPND16), "The percentage points of the normal
+// distribution" (1988)
+const CENTRAL_NUMERATOR: [f64; 8] = [
+    3.3871328727963665,
+    1.3314166789178438e2,
+    1.9715909503065513e3,
+    1.373169376550946e4,
+    4.592195393154987e4,
+    6.72657709270087e4,
+    3.343057558358813e4,
+    2.5090809287301227e3,
+];
+const CENTRAL_DENOMINATOR: [f64; 8] = [
+    1.0,
+    4.231333070160091e1,
+    6.871870074920579e2,
+    5.394196021424751e3,
+    2.1213794301586597e4,
+    3.930789580009271e4,
+    2.8729085735721943e4,
+    5.226495278852854e3,
+];
+const INTERMEDIATE_NUMERATOR: [f64; 8] = [
+    1.4234371107496835,
+    4.630337846156546,
+    5.769497221460691,
+    3.6478483247632045,
+    1.2704582524523684,
+    2.417807251774506e-1,
+    2.2723844989269184e-2,
+    7.745450142783414e-4,
+];
+const INTERMEDIATE_DENOMINATOR: [f64; 8] = [
+    1.0,
+    2.053191626637759,
+    1.6763848301838038,
+    6.897673349851e-1,
+    1.4810397642748008e-1,
+    1.5198666563616457e-2,
+    5.475938084995345e-4,
+    1.0507500716444169e-9,
+];
+const TAIL_NUMERATOR: [f64; 8] = [
+    6.657904643501103,
+    5.463784911164114,
+    1.7848265399172913,
+    2.9656057182850487e-1,
+    2.6532189526576124e-2,
+    1.2426609473880784e-3,
+    2.7115555687434876e-5,
+    2.0103343992922881e-7,
+];
+const TAIL_DENOMINATOR: [f64; 8] = [
+    1.0,
+    5.99832206555888e-1,
+    1.369298809227358e-1,
+    1.4875361290850615e-2,
+    7.868691311456133e-4,
+    1.8463183175100548e-5,
+    1.421511758316446e-7,
+    2.0442631033899397e-15,
+];
+
+// norm_pdf calculates the probability density of the standard normal distribution
+pub fn norm_pdf(z: f64) -> f64 {
+    FRAC_1_SQRT_2PI * (-0.5 * z * z).exp()
+}
+
+// norm_cdf calculates the cumulative standard normal distribution with Cody's
+// algorithm, which is accurate to double precision over the whole real line
+pub fn norm_cdf(z: f64) -> f64 {
+    let y = z.abs();
+    if y == f64::INFINITY {
+        return if z > 0.0 { 1.0 } else { 0.0 };
+    }
+    if y <= 0.66291 {
+        return 0.5 + z * central(z * z);
+    }
+    let tail = if y <= SQRT_32 {
+        intermediate(y) * gaussian(y)
+    } else {
+        far_tail(y) * gaussian(y)
+    };
+    if z > 0.0 {
+        1.0 - tail
+    } else {
+        tail
+    }
+}
+
+// norm_log_cdf calculates the natural logarithm of norm_cdf, without underflowing
+// far in the lower tail where norm_cdf itself rounds to zero
+pub fn norm_log_cdf(z: f64) -> f64 {
+    if z < -SQRT_32 {
+        return -0.5 * z * z + far_tail(-z).ln();
+    }
+    if z > 0.0 {
+        return (-norm_cdf(-z)).ln_1p();
+    }
+    norm_cdf(z).ln()
+}
+
+// norm_inv_cdf calculates the quantile of the standard normal distribution for the
+// probability p with Wichura's AS241, it's NaN outside of [0, 1]
+pub fn norm_inv_cdf(p: f64) -> f64 {
+    if !(0.0..=1.0).contains(&p) {
+        return f64::NAN;
+    }
+    if p == 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p == 1.0 {
+        return f64::INFINITY;
+    }
+
+    let q = p - 0.5;
+    if q.abs() <= 0.425 {
+        let r = 0.180625 - q * q;
+        return q * polynomial(&CENTRAL_NUMERATOR, r) / polynomial(&CENTRAL_DENOMINATOR, r);
+    }
+
+    let r = (-p.min(1.0 - p).ln()).sqrt();
+    let z = if r <= 5.0 {
+        let r = r - 1.6;
+        polynomial(&INTERMEDIATE_NUMERATOR, r) / polynomial(&INTERMEDIATE_DENOMINATOR, r)
+    } else {
+        let r = r - 5.0;
+        polynomial(&TAIL_NUMERATOR, r) / polynomial(&TAIL_DENOMINATOR, r)
+    };
+    if q < 0.0 {
+        -z
+    } else {
+        z
+    }
+}
+
+// bivariate_norm_cdf calculates P(X < x, Y < y) for standard normal variables with
+// correlation rho, using Genz's (2004) double-precision method based on Drezner and
+// Wesolowsky's integral
+pub fn bivariate_norm_cdf(x: f64, y: f64, rho: f64) -> f64 {
+    let nodes: &[(f64, f64)] = if rho.abs() < 0.3 {
+        &LEGENDRE_6
+    } else if rho.abs() < 0.75 {
+        &LEGENDRE_12
+    } else {
+        &LEGENDRE_20
+    };
+    // Genz works with the upper tail P(X > h, Y > k)
+    let h = -x;
+    let mut k = -y;
+    let mut hk = h * k;
+    let mut result = 0.0;
+
+    if rho.abs() < 0.925 {
+        let hs = (h * h + k * k) / 2.0;
+        let asr = rho.asin();
+        for &(node, weight) in nodes {
+            for sign in [-1.0, 1.0] {
+                let sn = (asr * (1.0 + sign * node) / 2.0).sin();
+                result += weight * ((sn * hk - hs) / (1.0 - sn * sn)).exp();
+            }
+        }
+        return result * asr / (4.0 * PI) + norm_cdf(-h) * norm_cdf(-k);
+    }
+
+    if rho < 0.0 {
+        k = -k;
+        hk = -hk;
+    }
+    if rho.abs() < 1.0 {
+        let a2 = (1.0 - rho) * (1.0 + rho);
+        let mut a = a2.sqrt();
+        let bs = (h - k).powi(2);
+        let c = (4.0 - hk) / 8.0;
+        let d = (12.0 - hk) / 16.0;
+        let asr = -(bs / a2 + hk) / 2.0;
+        if asr > -100.0 {
+            result = a
+                * asr.exp()
+                * (1.0 - c * (bs - a2) * (1.0 - d * bs / 5.0) / 3.0 + c * d * a2 * a2 / 5.0);
+        }
+        if -hk < 100.0 {
+            let b = bs.sqrt();
+            result -= (-hk / 2.0).exp()
+                * (2.0 * PI).sqrt()
+                * norm_cdf(-b / a)
+                * b
+                * (1.0 - c * bs * (1.0 - d * bs / 5.0) / 3.0);
+        }
+        a /= 2.0;
+        for &(node, weight) in nodes {
+            for sign in [-1.0, 1.0] {
+                let xs = (a * (sign * node + 1.0)).powi(2);
+                let rs = (1.0 - xs).sqrt();
+                let asr = -(bs / xs + hk) / 2.0;
+                if asr > -100.0 {
+                    result += a
+                        * weight
+                        * asr.exp()
+                        * ((-hk * (1.0 - rs) / (2.0 * (1.0 + rs))).exp() / rs
+                            - (1.0 + c * xs * (1.0 + d * xs)));
+                }
+            }
+        }
+        result = -result / (2.0 * PI);
+    }
+
+    if rho > 0.0 {
+        result + norm_cdf(-h.max(k))
+    } else if k > h {
+        // k has been negated, so this is the probability of h < X < -k
+        if h < 0.0 {
+            norm_cdf(k) - norm_cdf(h) - result
+        } else {
+            norm_cdf(-h) - norm_cdf(-k) - result
+        }
+    } else {
+        -result
+    }
+}
+
+// central returns (cdf(z) - 1/2) / z for |z| <= 0.66291, taking z squared
+fn central(z2: f64) -> f64 {
+    let mut numerator = A[4] * z2;
+    let mut denominator = z2;
+    for i in 0..3 {
+        numerator = (numerator + A[i]) * z2;
+        denominator = (denominator + B[i]) * z2;
+    }
+    (numerator + A[3]) / (denominator + B[3])
+}
+
+// intermediate returns the upper tail for 0.66291 < y <= sqrt(32) divided by exp(-y^2/2)
+fn intermediate(y: f64) -> f64 {
+    let mut numerator = C[8] * y;
+    let mut denominator = y;
+    for i in 0..7 {
+        numerator = (numerator + C[i]) * y;
+        denominator = (denominator + D[i]) * y;
+    }
+    (numerator + C[7]) / (denominator + D[7])
+}
+
+// far_tail returns the upper tail for y > sqrt(32) divided by exp(-y^2/2)
+fn far_tail(y: f64) -> f64 {
+    let y2 = 1.0 / (y * y);
+    let mut numerator = P[5] * y2;
+    let mut denominator = y2;
+    for i in 0..4 {
+        numerator = (numerator + P[i]) * y2;
+        denominator = (denominator + Q[i]) * y2;
+    }
+    let rational = y2 * (numerator + P[4]) / (denominator + Q[4]);
+    (FRAC_1_SQRT_2PI - rational) / y
+}
+
+// gaussian returns exp(-y^2/2), splitting y^2 to avoid the rounding error of squaring
+fn gaussian(y: f64) -> f64 {
+    let rounded = (y * 16.0).trunc() / 16.0;
+    let rest = (y - rounded) * (y + rounded);
+    (-0.5 * rounded * rounded).exp() * (-0.5 * rest).exp()
+}
+
+// polynomial evaluates coefficients[0] + coefficients[1] x + ... with Horner's rule
+fn polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_close;
+
+    // assert_values compares the function with reference values calculated with 50
+    // significant digits for the exact binary inputs, to 1e-15 relative to the value
+    fn assert_values(f: fn(f64) -> f64, cases: &[(f64, f64)]) {
+        for &(x, expected) in cases {
+            assert_close(f(x), expected, 1e-15 * expected.abs());
+        }
+    }
+
+    #[test]
+    fn zero_norm_cdf() {
+        assert_eq!(norm_cdf(0.0), 0.5);
+    }
+
+    #[test]
+    fn positive_norm_cdf() {
+        assert_values(
+            norm_cdf,
+            &[
+                (0.39, 0.6517317265359824),
+                (0.5, 0.6914624612740131),
+                (1.5, 0.9331927987311419),
+                (3.0, 0.9986501019683699),
+                (6.0, 0.9999999990134123),
+            ],
+        );
+        assert_eq!(norm_cdf(f64::INFINITY), 1.0);
+    }
+
+    #[test]
+    fn negative_norm_cdf() {
+        assert_values(
+            norm_cdf,
+            &[
+                (-0.39, 0.3482682734640176),
+                (-1.5, 0.06680720126885807),
+                (-3.0, 0.0013498980316300946),
+                (-6.0, 9.86587645037698e-10),
+                (-10.0, 7.619853024160525e-24),
+            ],
+        );
+        assert_eq!(norm_cdf(f64::NEG_INFINITY), 0.0);
+    }
+
+    #[test]
+    fn norm_pdf_values() {
+        assert_values(
+            norm_pdf,
+            &[
+                (0.0, 0.3989422804014327),
+                (-0.39, 0.3697276841114323),
+                (1.5, 0.12951759566589172),
+                (6.0, 6.075882849823285e-9),
+            ],
+        );
+    }
+
+    #[test]
+    fn norm_log_cdf_values() {
+        assert_values(
+            norm_log_cdf,
+            &[
+                (-40.0, -804.6084420137538),
+                (-10.0, -53.23128515051247),
+                (-3.0, -6.607726221510349),
+                (0.0, -std::f64::consts::LN_2),
+                (3.0, -0.0013508099647481938),
+                (6.0, -9.865876455243758e-10),
+            ],
+        );
+    }
+
+    #[test]
+    fn norm_inv_cdf_values() {
+        assert_eq!(norm_inv_cdf(0.5), 0.0);
+        assert_values(
+            norm_inv_cdf,
+            &[
+                (1e-300, -37.0470962993612),
+                (1e-10, -6.361340902404057),
+                (0.001, -3.0902323061678136),
+                (0.025, -1.9599639845400543),
+                (0.3, -0.5244005127080408),
+                (0.7, 0.5244005127080407),
+                (0.975, 1.9599639845400538),
+                (0.999999, 4.753424308817087),
+            ],
+        );
+    }
+
+    #[test]
+    fn norm_inv_cdf_out_of_range() {
+        assert_eq!(norm_inv_cdf(0.0), f64::NEG_INFINITY);
+        assert_eq!(norm_inv_cdf(1.0), f64::INFINITY);
+        assert!(norm_inv_cdf(-0.1).is_nan());
+        assert!(norm_inv_cdf(1.1).is_nan());
+    }
+
+    #[test]
+    fn norm_inv_cdf_inverts_norm_cdf() {
+        for z in [-8.0, -4.0, -1.0, -0.25, 0.25, 1.0, 4.0] {
+            assert!((norm_inv_cdf(norm_cdf(z)) - z).abs() < 1e-13 * z.abs().max(1.0));
+        }
+    }
+
+    // (x, y, rho, P(X < x, Y < y)) integrated numerically with 30 significant digits
+    const BIVARIATE: [(f64, f64, f64, f64); 8] = [
+        (-0.5, 1.2, 0.2, 0.2858440995744852),
+        (1.0, -1.5, 0.6, 0.06651517809416865),
+        (-2.0, -1.0, 0.9, 0.022501572916410806),
+        (0.7, 0.3, 0.95, 0.6124171682436882),
+        (-1.3, 0.4, -0.95, 5.743531717885418e-05),
+        (2.5, -0.5, -0.4, 0.30386861984880603),
+        (0.2, 0.1, -0.8, 0.17232244108126754),
+        (-3.0, 2.0, 0.99, 0.0013498980316300946),
+    ];
+
+    #[test]
+    fn bivariate_norm_cdf_values() {
+        assert!((bivariate_norm_cdf(0.0, 0.0, 0.0) - 0.25).abs() < 1e-15);
+        assert!((bivariate_norm_cdf(0.0, 0.0, 0.5) - 1.0 / 3.0).abs() < 1e-15);
+        assert!((bivariate_norm_cdf(0.0, 0.0, -0.5) - 1.0 / 6.0).abs() < 1e-15);
+        for (x, y, rho, expected) in BIVARIATE {
+            let result = bivariate_norm_cdf(x, y, rho);
+            assert!(
+                (result - expected).abs() < 1e-14,
+                "M({}, {}, {}) = {} is not {}",
+                x,
+                y,
+                rho,
+                result,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn bivariate_norm_cdf_limits() {
+        assert_eq!(bivariate_norm_cdf(0.3, 1.2, 1.0), norm_cdf(0.3));
+        assert!(
+            (bivariate_norm_cdf(0.3, 1.2, -1.0) - (norm_cdf(0.3) - norm_cdf(-1.2))).abs() < 1e-15
+        );
+        assert!((bivariate_norm_cdf(-0.3, 0.2, -1.0)).abs() < 1e-15);
+    }
+}
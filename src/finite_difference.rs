@@ -0,0 +1,619 @@
+use crate::binomial::ExerciseStyle;
+use crate::{BlackScholesModel, Greeks, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+const DEFAULT_SPACE_STEPS: usize = 200;
+const DEFAULT_TIME_STEPS: usize = 200;
+const DEFAULT_RANNACHER_STEPS: usize = 2;
+const DEVIATIONS: f64 = 5.0; // half width of the grid in standard deviations of ln S(T)
+const OMEGA: f64 = 1.2; // over-relaxation factor of projected SOR
+const PENALTY: f64 = 1e8;
+const TOLERANCE: f64 = 1e-10;
+const MAX_ITERATIONS: usize = 1000;
+
+// Scheme selects how the time derivative of the pricing PDE is discretised
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scheme {
+    Explicit,      // forward Euler, stable only for dt <= dx^2 / vol^2, else TooFewSteps
+    Implicit,      // backward Euler, unconditionally stable but first order in time
+    CrankNicolson, // average of both, second order in time
+}
+
+// ExerciseConstraint selects how the early exercise constraint V >= payoff is enforced
+// by the implicit schemes, the explicit scheme simply takes the maximum after each step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExerciseConstraint {
+    ProjectedSor, // projected successive over-relaxation
+    Penalty,      // penalty iteration of Forsyth and Vetzal
+}
+
+// FiniteDifferenceModel solves the Black-Scholes-Merton PDE backwards from expiration
+// on a uniform grid in log stock prices centered at the current stock price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FiniteDifferenceModel {
+    model: BlackScholesModel, // market inputs shared with the closed-form model
+    space_steps: usize,       // number of intervals in ln S, rounded up to even
+    time_steps: usize,        // number of time steps to expiration
+    scheme: Scheme,           // time discretisation
+    exercise: ExerciseStyle,  // european or american exercise
+    constraint: ExerciseConstraint, // early exercise solver of the implicit schemes
+    rannacher_steps: usize,   // initial Crank-Nicolson steps run as implicit half steps
+}
+
+// Grid keeps the values around the current stock price which are needed for Greeks
+struct Grid {
+    dx: f64,          // step in ln S
+    dt: f64,          // step in time
+    now: [f64; 3],    // values one step below, at and one step above the stock
+    before: [f64; 2], // values at the stock one and two time steps before today
+}
+
+impl FiniteDifferenceModel {
+    // new creates a Crank-Nicolson grid for a european option, use with_grid,
+    // with_scheme, with_exercise, with_constraint and with_rannacher_steps to change
+    // the defaults
+    pub fn new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+    ) -> FiniteDifferenceModel {
+        FiniteDifferenceModel::from(BlackScholesModel::new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        ))
+    }
+
+    // try_new creates the grid like new does, but rejects inputs for which the
+    // Black-Scholes formula is undefined
+    pub fn try_new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+    ) -> MathResult<FiniteDifferenceModel> {
+        BlackScholesModel::try_new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        )
+        .map(FiniteDifferenceModel::from)
+    }
+
+    pub fn with_grid(mut self, space_steps: usize, time_steps: usize) -> FiniteDifferenceModel {
+        self.space_steps = space_steps;
+        self.time_steps = time_steps;
+        self
+    }
+
+    pub fn with_scheme(mut self, scheme: Scheme) -> FiniteDifferenceModel {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn with_exercise(mut self, exercise: ExerciseStyle) -> FiniteDifferenceModel {
+        self.exercise = exercise;
+        self
+    }
+
+    pub fn with_constraint(mut self, constraint: ExerciseConstraint) -> FiniteDifferenceModel {
+        self.constraint = constraint;
+        self
+    }
+
+    // with_rannacher_steps sets how many of the first Crank-Nicolson steps are replaced
+    // by two implicit half steps, which damps the oscillations caused by the kink of
+    // the payoff (0 disables the smoothing, other schemes ignore it)
+    pub fn with_rannacher_steps(mut self, steps: usize) -> FiniteDifferenceModel {
+        self.rannacher_steps = steps;
+        self
+    }
+
+    // price calculates the fair value of the option ($$$ per share), at expiration
+    // that's the intrinsic value whatever the exercise style
+    pub fn price(&self) -> MathResult {
+        if self.model.time_to_expire == 0.0 {
+            return Ok(self.intrinsic(self.model.stock));
+        }
+        Ok(self.solve(self.half_width())?.now[1])
+    }
+
+    // greeks takes delta, gamma and theta from the nodes of the grid, the rest of the
+    // sensitivities are calculated by solving the grid again with bumped inputs
+    //
+    // At expiration there's no grid, the greeks are those of the intrinsic value
+    pub fn greeks(&self) -> MathResult<Greeks> {
+        if self.model.time_to_expire == 0.0 {
+            return self.model.greeks();
+        }
+        if self.time_steps < 2 {
+            return Err(MathError::TooFewSteps(self.time_steps));
+        }
+        let grid = self.solve(self.half_width())?;
+        let [down, mid, up] = grid.now;
+        let stock = self.model.stock;
+
+        // derivatives in ln S are turned into derivatives in S with
+        // dV/dS = V_x / S and d2V/dS2 = (V_xx - V_x) / S^2
+        let first = (up - down) / (2.0 * grid.dx);
+        let second = (up - 2.0 * mid + down) / grid.dx.powi(2);
+
+        Ok(Greeks {
+            delta: first / stock,
+            gamma: (second - first) / stock.powi(2),
+            vega: self.bumped(|m, h| m.volatility += h)?,
+            // second order backward difference in time
+            theta: (4.0 * grid.before[0] - grid.before[1] - 3.0 * mid) / (2.0 * grid.dt),
+            rho: self.bumped(|m, h| m.interest_rate += h)?,
+            dividend_rho: self
+                .bumped(|m, h| m.dividend = Some(m.dividend.unwrap_or_default() + h))?,
+        })
+    }
+
+    // bumped keeps the grid of the unbumped model, so that the discretisation error
+    // does not leak into the difference
+    fn bumped(&self, bump: fn(&mut BlackScholesModel, f64)) -> MathResult {
+        let h = 1e-4;
+        let width = self.half_width();
+        let mut up = *self;
+        bump(&mut up.model, h);
+        let mut down = *self;
+        bump(&mut down.model, -h);
+        Ok((up.solve(width)?.now[1] - down.solve(width)?.now[1]) / (2.0 * h))
+    }
+
+    fn space_steps(&self) -> usize {
+        self.space_steps + self.space_steps % 2
+    }
+
+    fn half_width(&self) -> f64 {
+        let m = &self.model;
+        DEVIATIONS * m.volatility * m.time_to_expire.sqrt() + (m.strike / m.stock).ln().abs()
+    }
+
+    fn intrinsic(&self, stock: f64) -> f64 {
+        match self.model.opt {
+            OptionKind::Call => (stock - self.model.strike).max(0.0),
+            OptionKind::Put => (self.model.strike - stock).max(0.0),
+        }
+    }
+
+    // boundaries returns the values at the lowest and highest stock of the grid with
+    // the given time left to expiration, where the option is either worthless or
+    // behaves like a forward
+    fn boundaries(&self, stocks: &[f64], time: f64) -> (f64, f64) {
+        let m = &self.model;
+        let (lowest, highest) = (stocks[0], stocks[stocks.len() - 1]);
+        let discount = E.powf(-m.interest_rate * time);
+        let dividend_discount = E.powf(-m.dividend.unwrap_or_default() * time);
+        let (low, high) = match m.opt {
+            OptionKind::Call => (0.0, highest * dividend_discount - m.strike * discount),
+            OptionKind::Put => (m.strike * discount - lowest * dividend_discount, 0.0),
+        };
+        match self.exercise {
+            ExerciseStyle::European => (low, high),
+            ExerciseStyle::American => (
+                low.max(self.intrinsic(lowest)),
+                high.max(self.intrinsic(highest)),
+            ),
+        }
+    }
+
+    // solve rolls the payoff back to today on a grid spanning width in ln S on either
+    // side of the current stock price
+    fn solve(&self, width: f64) -> MathResult<Grid> {
+        let space_steps = self.space_steps();
+        if space_steps < 2 {
+            return Err(MathError::TooFewSteps(space_steps));
+        }
+        if self.time_steps < 1 {
+            return Err(MathError::TooFewSteps(self.time_steps));
+        }
+        let m = &self.model;
+        let dx = 2.0 * width / space_steps as f64;
+        let dt = m.time_to_expire / self.time_steps as f64;
+        // the explicit scheme amplifies every error on coarser time steps
+        if self.scheme == Scheme::Explicit && dt * m.volatility.powi(2) > dx.powi(2) {
+            return Err(MathError::TooFewSteps(self.time_steps));
+        }
+        let stocks: Vec<f64> = (0..=space_steps)
+            .map(|i| m.stock * E.powf(-width + i as f64 * dx))
+            .collect();
+        let payoff: Vec<f64> = stocks.iter().map(|&s| self.intrinsic(s)).collect();
+
+        // the spatial operator is the same for every time step
+        let drift = m.interest_rate - m.dividend.unwrap_or_default() - m.volatility.powi(2) / 2.0;
+        let diffusion = m.volatility.powi(2) / dx.powi(2);
+        let operator = Operator {
+            lower: diffusion / 2.0 - drift / (2.0 * dx),
+            diagonal: -diffusion - m.interest_rate,
+            upper: diffusion / 2.0 + drift / (2.0 * dx),
+        };
+
+        let theta = match self.scheme {
+            Scheme::Explicit => 0.0,
+            Scheme::Implicit => 1.0,
+            Scheme::CrankNicolson => 0.5,
+        };
+        let smoothed = match self.scheme {
+            Scheme::CrankNicolson => self.rannacher_steps.min(self.time_steps),
+            _ => 0,
+        };
+
+        let mid = space_steps / 2;
+        let mut values = payoff.clone();
+        let mut before = [values[mid]; 2];
+        for step in 0..self.time_steps {
+            before = [values[mid], before[0]];
+            let time = (step + 1) as f64 * dt;
+            if step < smoothed {
+                let half = time - dt / 2.0;
+                let edges = self.boundaries(&stocks, half);
+                values = self.step(&operator, &values, &payoff, edges, dt / 2.0, 1.0)?;
+                let edges = self.boundaries(&stocks, time);
+                values = self.step(&operator, &values, &payoff, edges, dt / 2.0, 1.0)?;
+            } else {
+                let edges = self.boundaries(&stocks, time);
+                values = self.step(&operator, &values, &payoff, edges, dt, theta)?;
+            }
+        }
+
+        Ok(Grid {
+            dx,
+            dt,
+            now: [values[mid - 1], values[mid], values[mid + 1]],
+            before,
+        })
+    }
+
+    // step advances the values by dt with the theta scheme
+    // (I - theta dt L) V_new = (I + (1 - theta) dt L) V_old, edges are the new values at
+    // the lowest and highest stock
+    fn step(
+        &self,
+        operator: &Operator,
+        values: &[f64],
+        payoff: &[f64],
+        edges: (f64, f64),
+        dt: f64,
+        theta: f64,
+    ) -> MathResult<Vec<f64>> {
+        let last = values.len() - 1;
+        let explicit = (1.0 - theta) * dt;
+        let implicit = theta * dt;
+        let (low, high) = edges;
+
+        let mut rhs: Vec<f64> = (1..last)
+            .map(|i| values[i] + explicit * operator.apply(values, i))
+            .collect();
+        let n = rhs.len();
+        rhs[0] += implicit * operator.lower * low;
+        rhs[n - 1] += implicit * operator.upper * high;
+
+        let system = Tridiagonal {
+            lower: -implicit * operator.lower,
+            diagonal: vec![1.0 - implicit * operator.diagonal; n],
+            upper: -implicit * operator.upper,
+        };
+        let floor = &payoff[1..last];
+        let interior = match (self.exercise, self.constraint) {
+            (ExerciseStyle::European, _) => system.solve(&rhs),
+            (ExerciseStyle::American, _) if theta == 0.0 => {
+                rhs.iter().zip(floor).map(|(v, p)| v.max(*p)).collect()
+            }
+            (ExerciseStyle::American, ExerciseConstraint::ProjectedSor) => {
+                system.projected_sor(&rhs, floor, &values[1..last])?
+            }
+            (ExerciseStyle::American, ExerciseConstraint::Penalty) => {
+                system.penalty(&rhs, floor, &values[1..last])?
+            }
+        };
+
+        let mut next = Vec::with_capacity(values.len());
+        next.push(low);
+        next.extend(interior);
+        next.push(high);
+        Ok(next)
+    }
+}
+
+impl From<BlackScholesModel> for FiniteDifferenceModel {
+    fn from(model: BlackScholesModel) -> FiniteDifferenceModel {
+        FiniteDifferenceModel {
+            model,
+            space_steps: DEFAULT_SPACE_STEPS,
+            time_steps: DEFAULT_TIME_STEPS,
+            scheme: Scheme::CrankNicolson,
+            exercise: ExerciseStyle::European,
+            constraint: ExerciseConstraint::ProjectedSor,
+            rannacher_steps: DEFAULT_RANNACHER_STEPS,
+        }
+    }
+}
+
+// Operator is the central difference of the PDE in ln S,
+// L V = vol^2 / 2 V_xx + (r - q - vol^2 / 2) V_x - r V
+struct Operator {
+    lower: f64,
+    diagonal: f64,
+    upper: f64,
+}
+
+impl Operator {
+    fn apply(&self, values: &[f64], i: usize) -> f64 {
+        self.lower * values[i - 1] + self.diagonal * values[i] + self.upper * values[i + 1]
+    }
+}
+
+// Tridiagonal is a system with constant off-diagonals, the diagonal varies once the
+// penalty terms are added
+struct Tridiagonal {
+    lower: f64,
+    diagonal: Vec<f64>,
+    upper: f64,
+}
+
+impl Tridiagonal {
+    // solve runs the Thomas algorithm
+    fn solve(&self, rhs: &[f64]) -> Vec<f64> {
+        let n = rhs.len();
+        let mut upper = vec![0.0; n];
+        let mut x = vec![0.0; n];
+        upper[0] = self.upper / self.diagonal[0];
+        x[0] = rhs[0] / self.diagonal[0];
+        for i in 1..n {
+            let pivot = self.diagonal[i] - self.lower * upper[i - 1];
+            upper[i] = self.upper / pivot;
+            x[i] = (rhs[i] - self.lower * x[i - 1]) / pivot;
+        }
+        for i in (0..n - 1).rev() {
+            x[i] -= upper[i] * x[i + 1];
+        }
+        x
+    }
+
+    // projected_sor solves the linear complementarity problem A x >= b, x >= floor
+    // with equality in at least one of them, starting from the previous values
+    fn projected_sor(&self, rhs: &[f64], floor: &[f64], initial: &[f64]) -> MathResult<Vec<f64>> {
+        let n = rhs.len();
+        let mut x: Vec<f64> = initial.iter().zip(floor).map(|(v, p)| v.max(*p)).collect();
+        for _ in 0..MAX_ITERATIONS {
+            let mut change: f64 = 0.0;
+            for i in 0..n {
+                let mut residual = rhs[i] - self.diagonal[i] * x[i];
+                if i > 0 {
+                    residual -= self.lower * x[i - 1];
+                }
+                if i + 1 < n {
+                    residual -= self.upper * x[i + 1];
+                }
+                let updated = (x[i] + OMEGA * residual / self.diagonal[i]).max(floor[i]);
+                change = change.max((updated - x[i]).abs() / x[i].abs().max(1.0));
+                x[i] = updated;
+            }
+            if change < TOLERANCE {
+                return Ok(x);
+            }
+        }
+        Err(MathError::NoConvergence(MAX_ITERATIONS))
+    }
+
+    // penalty adds a large penalty to the rows where the constraint is violated and
+    // solves again until the set of penalised rows stops changing
+    fn penalty(&self, rhs: &[f64], floor: &[f64], initial: &[f64]) -> MathResult<Vec<f64>> {
+        let mut active: Vec<bool> = initial.iter().zip(floor).map(|(v, p)| v < p).collect();
+        for _ in 0..MAX_ITERATIONS {
+            let system = Tridiagonal {
+                diagonal: self
+                    .diagonal
+                    .iter()
+                    .zip(&active)
+                    .map(|(&d, &a)| if a { d + PENALTY } else { d })
+                    .collect(),
+                ..*self
+            };
+            let penalised: Vec<f64> = rhs
+                .iter()
+                .zip(floor)
+                .zip(&active)
+                .map(|((&b, &p), &a)| if a { b + PENALTY * p } else { b })
+                .collect();
+            let x = system.solve(&penalised);
+            let next: Vec<bool> = x.iter().zip(floor).map(|(v, p)| v < p).collect();
+            if next == active {
+                return Ok(x);
+            }
+            active = next;
+        }
+        Err(MathError::NoConvergence(MAX_ITERATIONS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binomial::{BinomialModel, Lattice};
+    use crate::testing::assert_close;
+
+    fn grid(opt: OptionKind) -> FiniteDifferenceModel {
+        FiniteDifferenceModel::new(opt, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125))
+    }
+
+    #[test]
+    fn european_schemes_converge_to_black_scholes() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let expected = grid(opt).model.price().unwrap();
+            for (scheme, space_steps, time_steps, tolerance) in [
+                // the explicit scheme needs dt <= dx^2 / vol^2 to stay stable
+                (Scheme::Explicit, 200, 1000, 1e-3),
+                (Scheme::Implicit, 400, 1000, 1e-3),
+                (Scheme::CrankNicolson, 400, 200, 1e-3),
+            ] {
+                let result = grid(opt)
+                    .with_scheme(scheme)
+                    .with_grid(space_steps, time_steps)
+                    .price()
+                    .unwrap();
+                assert_close(result, expected, tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn american_put() {
+        // same Hull example as the binomial tree, the limit is about 4.28
+        let model =
+            FiniteDifferenceModel::new(OptionKind::Put, 50.0, 50.0, 0.1, 0.4, 5.0 / 12.0, None)
+                .with_exercise(ExerciseStyle::American);
+        let tree = BinomialModel::new(OptionKind::Put, 50.0, 50.0, 0.1, 0.4, 5.0 / 12.0, None)
+            .with_exercise(ExerciseStyle::American)
+            .with_lattice(Lattice::LeisenReimer)
+            .with_steps(1001)
+            .price()
+            .unwrap();
+
+        let sor = model.price().unwrap();
+        let penalty = model
+            .with_constraint(ExerciseConstraint::Penalty)
+            .price()
+            .unwrap();
+        assert_close(sor, tree, 2e-3);
+        assert_close(penalty, sor, 1e-6);
+        assert!(
+            sor > model
+                .with_exercise(ExerciseStyle::European)
+                .price()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn american_explicit_scheme() {
+        let model =
+            FiniteDifferenceModel::new(OptionKind::Put, 50.0, 50.0, 0.1, 0.4, 5.0 / 12.0, None)
+                .with_exercise(ExerciseStyle::American);
+        let implicit = model.price().unwrap();
+        let explicit = model
+            .with_scheme(Scheme::Explicit)
+            .with_grid(200, 1000)
+            .price()
+            .unwrap();
+        assert_close(explicit, implicit, 2e-3);
+    }
+
+    #[test]
+    fn greeks_from_grid_match_black_scholes() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = grid(opt).with_grid(400, 400);
+            let expected = model.model.greeks().unwrap();
+            let result = model.greeks().unwrap();
+
+            assert_close(result.delta, expected.delta, 1e-4);
+            assert_close(result.gamma, expected.gamma, 1e-4);
+            assert_close(result.theta, expected.theta, 1e-2);
+            assert_close(result.vega, expected.vega, 1e-2);
+            assert_close(result.rho, expected.rho, 1e-2);
+            assert_close(result.dividend_rho, expected.dividend_rho, 1e-2);
+        }
+    }
+
+    #[test]
+    fn rannacher_smoothing_damps_gamma_oscillations() {
+        // at the money with few large time steps plain Crank-Nicolson keeps the kink of
+        // the payoff ringing around the strike
+        let model =
+            FiniteDifferenceModel::new(OptionKind::Call, 100.0, 100.0, 0.05, 0.2, 0.25, None)
+                .with_grid(400, 20);
+        let expected = model.model.gamma().unwrap();
+        let plain = model.with_rannacher_steps(0).greeks().unwrap().gamma;
+        let smoothed = model.greeks().unwrap().gamma;
+
+        assert!((smoothed - expected).abs() < 1e-4);
+        assert!((plain - expected).abs() > 10.0 * (smoothed - expected).abs());
+    }
+
+    #[test]
+    fn err_with_too_few_steps() {
+        let model = grid(OptionKind::Call);
+        assert_eq!(
+            model.with_grid(0, 10).price(),
+            Err(MathError::TooFewSteps(0))
+        );
+        assert_eq!(
+            model.with_grid(10, 0).price(),
+            Err(MathError::TooFewSteps(0))
+        );
+        assert!(model.with_grid(1, 1).price().is_ok());
+        assert_eq!(
+            model.with_grid(10, 1).greeks(),
+            Err(MathError::TooFewSteps(1))
+        );
+    }
+
+    #[test]
+    fn err_with_unstable_explicit_grid() {
+        // dt = 0.0025 is above dx^2 / vol^2 = 0.0014 on the default grid
+        let model = grid(OptionKind::Put).with_scheme(Scheme::Explicit);
+        assert_eq!(model.price(), Err(MathError::TooFewSteps(200)));
+        assert!(model.with_grid(200, 400).price().is_ok());
+    }
+
+    #[test]
+    fn err_with_invalid_inputs() {
+        assert_eq!(
+            FiniteDifferenceModel::try_new(OptionKind::Call, 58.0, 60.0, 0.035, -0.2, 0.5, None),
+            Err(MathError::NonPositiveVolatility(-0.2))
+        );
+        assert_eq!(
+            FiniteDifferenceModel::try_new(OptionKind::Call, 58.0, 60.0, 0.035, 0.2, -1.0, None),
+            Err(MathError::NegativeTimeToExpire(-1.0))
+        );
+        assert!(FiniteDifferenceModel::try_new(
+            OptionKind::Call,
+            58.0,
+            60.0,
+            0.035,
+            0.2,
+            0.5,
+            None
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn at_expiration() {
+        for exercise in [ExerciseStyle::European, ExerciseStyle::American] {
+            // at the money the grid would have no width at all
+            let call =
+                FiniteDifferenceModel::new(OptionKind::Call, 60.0, 60.0, 0.035, 0.2, 0.0, None)
+                    .with_exercise(exercise);
+            assert_eq!(call.price(), Ok(0.0));
+            assert_eq!(
+                call.greeks(),
+                Err(MathError::AtTheMoneyWithoutTimeValue(60.0))
+            );
+
+            let put =
+                FiniteDifferenceModel::new(OptionKind::Put, 62.0, 60.0, 0.035, 0.2, 0.0, None)
+                    .with_exercise(exercise);
+            assert_eq!(put.price(), Ok(2.0));
+            let greeks = put.greeks().unwrap();
+            assert_eq!((greeks.delta, greeks.gamma, greeks.vega), (-1.0, 0.0, 0.0));
+        }
+    }
+}
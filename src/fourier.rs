@@ -0,0 +1,437 @@
+use crate::complex::Complex;
+use crate::quadrature::semi_infinite;
+use crate::{BlackScholesModel, MathError, MathResult, OptionKind};
+use std::f64::consts::{E, PI};
+
+const TOLERANCE: f64 = 1e-10;
+const DEFAULT_FFT_POINTS: usize = 4096;
+const DEFAULT_FFT_SPACING: f64 = 0.25;
+const DEFAULT_DAMPING: f64 = 1.5;
+const DEFAULT_COS_TERMS: usize = 1024;
+const DEFAULT_TRUNCATION: f64 = 10.0;
+
+// CharacteristicFunction describes a model through the characteristic function of the
+// log return over the forward, which is all the Fourier pricers need to know
+//
+// The contract terms of the implementing model (option type and strike) are ignored,
+// the pricers take them separately
+pub trait CharacteristicFunction {
+    // characteristic_function is E[exp(i u ln(S(T) / F))], so it is 1 at u = -i
+    fn characteristic_function(&self, u: Complex) -> Complex;
+
+    // forward is the forward price of the underlying at expiration ($$$ per share)
+    fn forward(&self) -> f64;
+
+    // discount is the value today of $1 paid at expiration
+    fn discount(&self) -> f64;
+
+    // cumulants returns the first, second and fourth cumulants of ln(S(T) / F), by
+    // default from finite differences of the log characteristic function
+    fn cumulants(&self) -> (f64, f64, f64) {
+        let log = |u: f64| self.characteristic_function(Complex::from(u)).ln();
+        let h = 1e-2;
+        let (up, down) = (log(h), log(-h));
+        let c1 = (up - down).im / (2.0 * h);
+        let c2 = -(up + down).re / h.powi(2);
+
+        // the fourth derivative needs a step on the scale of the distribution
+        let h = 0.5 / c2.max(f64::EPSILON).sqrt();
+        let c4 = (log(2.0 * h) - 4.0 * log(h) - 4.0 * log(-h) + log(-2.0 * h)).re / h.powi(4);
+        (c1, c2, c4.max(0.0))
+    }
+}
+
+impl CharacteristicFunction for BlackScholesModel {
+    fn characteristic_function(&self, u: Complex) -> Complex {
+        let variance = self.volatility.powi(2) * self.time_to_expire;
+        (-0.5 * variance * (u * u + Complex::I * u)).exp()
+    }
+
+    fn forward(&self) -> f64 {
+        self.stock * self.dividend_discount() / BlackScholesModel::discount(self)
+    }
+
+    fn discount(&self) -> f64 {
+        BlackScholesModel::discount(self)
+    }
+
+    fn cumulants(&self) -> (f64, f64, f64) {
+        let variance = self.volatility.powi(2) * self.time_to_expire;
+        (-0.5 * variance, variance, 0.0)
+    }
+}
+
+// CarrMadan prices a strike vector with one fast Fourier transform of the damped call
+// price (Carr and Madan, 1999), strikes between the points of the log strike grid are
+// interpolated with cubic polynomials
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CarrMadan {
+    points: usize, // size of the transform, rounded up to a power of two
+    spacing: f64,  // step of the integration grid
+    damping: f64,  // exponential damping of the call price in the log strike
+}
+
+impl CarrMadan {
+    pub fn with_points(mut self, points: usize) -> CarrMadan {
+        self.points = points;
+        self
+    }
+
+    pub fn with_spacing(mut self, spacing: f64) -> CarrMadan {
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn with_damping(mut self, damping: f64) -> CarrMadan {
+        self.damping = damping;
+        self
+    }
+
+    // prices values the options at every strike, puts follow from put-call parity
+    pub fn prices<M: CharacteristicFunction>(
+        &self,
+        model: &M,
+        opt: OptionKind,
+        strikes: &[f64],
+    ) -> MathResult<Vec<f64>> {
+        let n = self.points.max(4).next_power_of_two();
+        let (eta, alpha) = (self.spacing, self.damping);
+        let lambda = 2.0 * PI / (n as f64 * eta);
+        let forward = model.forward();
+        let discount = model.discount();
+        let log_forward = forward.ln();
+        // the log strike grid is centered at the forward
+        let start = log_forward - lambda * n as f64 / 2.0;
+
+        let mut values: Vec<Complex> = (0..n)
+            .map(|j| {
+                let v = j as f64 * eta;
+                let u = Complex::new(v, -(alpha + 1.0));
+                let log_stock = (Complex::I * u * log_forward).exp();
+                let psi = discount * log_stock * model.characteristic_function(u)
+                    / Complex::new(alpha * alpha + alpha - v * v, (2.0 * alpha + 1.0) * v);
+                // Simpson's rule weights
+                let weight = match j {
+                    0 => 1.0 / 3.0,
+                    _ if j % 2 == 1 => 4.0 / 3.0,
+                    _ => 2.0 / 3.0,
+                };
+                Complex::new(0.0, -v * start).exp() * psi * (eta * weight)
+            })
+            .collect();
+        fft(&mut values);
+        let calls: Vec<f64> = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let log_strike = start + lambda * i as f64;
+                E.powf(-alpha * log_strike) / PI * value.re
+            })
+            .collect();
+
+        strikes
+            .iter()
+            .map(|&strike| {
+                let position = (strike.ln() - start) / lambda;
+                if !(position >= 1.0 && position < (n - 2) as f64) {
+                    return Err(MathError::ParameterOutOfBounds("strike", strike));
+                }
+                let call = cubic(&calls, position);
+                Ok(match opt {
+                    OptionKind::Call => call,
+                    OptionKind::Put => call - discount * (forward - strike),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for CarrMadan {
+    fn default() -> CarrMadan {
+        CarrMadan {
+            points: DEFAULT_FFT_POINTS,
+            spacing: DEFAULT_FFT_SPACING,
+            damping: DEFAULT_DAMPING,
+        }
+    }
+}
+
+// Cos prices a strike vector with the Fourier-cosine expansion of Fang and Oosterlee
+// (2008) on a range of log returns sized from the cumulants of the model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cos {
+    terms: usize,    // number of cosine terms
+    truncation: f64, // half width of the range in units of sqrt(c2 + sqrt(c4))
+}
+
+impl Cos {
+    pub fn with_terms(mut self, terms: usize) -> Cos {
+        self.terms = terms;
+        self
+    }
+
+    pub fn with_truncation(mut self, truncation: f64) -> Cos {
+        self.truncation = truncation;
+        self
+    }
+
+    // prices values the options at every strike, calls follow from put-call parity
+    // because the bounded put payoff makes the expansion insensitive to the range
+    pub fn prices<M: CharacteristicFunction>(
+        &self,
+        model: &M,
+        opt: OptionKind,
+        strikes: &[f64],
+    ) -> MathResult<Vec<f64>> {
+        let forward = model.forward();
+        let discount = model.discount();
+        let moneyness: Vec<f64> = strikes.iter().map(|k| (forward / k).ln()).collect();
+        let lowest = moneyness.iter().cloned().fold(f64::INFINITY, f64::min);
+        let highest = moneyness.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        // one range of ln(S(T) / K) covers all strikes, so the payoff coefficients
+        // only scale with the strike
+        let (c1, c2, c4) = model.cumulants();
+        let width = self.truncation * (c2 + c4.sqrt()).sqrt();
+        let a = (c1 + lowest - width).min(-f64::EPSILON);
+        let b = (c1 + highest + width).max(f64::EPSILON);
+
+        let terms: Vec<(f64, Complex, f64)> = (0..self.terms.max(1))
+            .map(|k| {
+                let u = k as f64 * PI / (b - a);
+                let weight = if k == 0 { 0.5 } else { 1.0 };
+                let coefficient = 2.0 / (b - a) * (psi(k, a, b, a, 0.0) - chi(k, a, b, a, 0.0));
+                (
+                    u,
+                    model.characteristic_function(Complex::from(u)),
+                    weight * coefficient,
+                )
+            })
+            .collect();
+
+        strikes
+            .iter()
+            .zip(&moneyness)
+            .map(|(&strike, &x)| {
+                let sum: f64 = terms
+                    .iter()
+                    .map(|&(u, phi, coefficient)| {
+                        (phi * Complex::new(0.0, u * (x - a)).exp()).re * coefficient
+                    })
+                    .sum();
+                let put = discount * strike * sum;
+                Ok(match opt {
+                    OptionKind::Call => put + discount * (forward - strike),
+                    OptionKind::Put => put,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for Cos {
+    fn default() -> Cos {
+        Cos {
+            terms: DEFAULT_COS_TERMS,
+            truncation: DEFAULT_TRUNCATION,
+        }
+    }
+}
+
+// lewis is the integral of Lewis' (2000) single integral formula
+// C = D (F - sqrt(F K) / pi * integral) for the undiscounted call, with the integrand
+// Re[exp(i u x) phi(u - i/2) weight(u)] / (u^2 + 1/4) over u > 0 and x = ln(F / K)
+//
+// A weight other than 1 differentiates under the integral sign, i u for instance is the
+// derivative with respect to x
+pub(crate) fn lewis<M, W>(model: &M, strike: f64, weight: W) -> MathResult
+where
+    M: CharacteristicFunction,
+    W: Fn(f64) -> Complex,
+{
+    let moneyness = (model.forward() / strike).ln();
+    semi_infinite(
+        &|u: f64| {
+            let phase = Complex::new(0.0, u * moneyness).exp();
+            let phi = model.characteristic_function(Complex::new(u, -0.5));
+            (phase * phi * weight(u)).re / (u * u + 0.25)
+        },
+        TOLERANCE,
+    )
+}
+
+// chi is the cosine transform of exp(y) over [c, d] on the range [a, b]
+fn chi(k: usize, a: f64, b: f64, c: f64, d: f64) -> f64 {
+    let w = k as f64 * PI / (b - a);
+    let (at_c, at_d) = (w * (c - a), w * (d - a));
+    (at_d.cos() * d.exp() - at_c.cos() * c.exp() + w * at_d.sin() * d.exp()
+        - w * at_c.sin() * c.exp())
+        / (1.0 + w * w)
+}
+
+// psi is the cosine transform of 1 over [c, d] on the range [a, b]
+fn psi(k: usize, a: f64, b: f64, c: f64, d: f64) -> f64 {
+    if k == 0 {
+        return d - c;
+    }
+    let w = k as f64 * PI / (b - a);
+    ((w * (d - a)).sin() - (w * (c - a)).sin()) / w
+}
+
+// cubic interpolates equally spaced values at a fractional position with the
+// Lagrange polynomial through the two points on either side
+fn cubic(values: &[f64], position: f64) -> f64 {
+    let i = position.floor() as usize;
+    let t = position - i as f64;
+    let (p0, p1, p2, p3) = (values[i - 1], values[i], values[i + 1], values[i + 2]);
+    -t * (t - 1.0) * (t - 2.0) / 6.0 * p0 + (t + 1.0) * (t - 1.0) * (t - 2.0) / 2.0 * p1
+        - (t + 1.0) * t * (t - 2.0) / 2.0 * p2
+        + (t + 1.0) * t * (t - 1.0) / 6.0 * p3
+}
+
+// fft replaces the values by their discrete Fourier transform
+// X_k = sum_j x_j exp(-2 pi i j k / n) with the iterative radix-2 Cooley-Tukey
+// algorithm, the length has to be a power of two
+pub(crate) fn fft(values: &mut [Complex]) {
+    let n = values.len();
+    debug_assert!(n.is_power_of_two());
+
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= n {
+        let angle = -2.0 * PI / length as f64;
+        let root = Complex::new(angle.cos(), angle.sin());
+        for chunk in values.chunks_mut(length) {
+            let mut twiddle = Complex::from(1.0);
+            let (lower, upper) = chunk.split_at_mut(length / 2);
+            for (even, odd) in lower.iter_mut().zip(upper.iter_mut()) {
+                let product = twiddle * *odd;
+                *odd = *even - product;
+                *even = *even + product;
+                twiddle = twiddle * root;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heston::{HestonModel, HestonParameters};
+    use crate::testing::assert_close;
+
+    const STRIKES: [f64; 5] = [80.0, 90.0, 100.0, 110.0, 120.0];
+
+    fn bsm(opt: OptionKind, strike: f64) -> BlackScholesModel {
+        BlackScholesModel::new(opt, strike, 100.0, 0.03, 0.25, 0.75, Some(0.01))
+    }
+
+    fn assert_prices(result: &[f64], expected: &[f64], tolerance: f64) {
+        for (&result, &expected) in result.iter().zip(expected) {
+            assert_close(result, expected, tolerance);
+        }
+    }
+
+    #[test]
+    fn fft_matches_direct_transform() {
+        let values: Vec<Complex> = (0..16)
+            .map(|j| Complex::new((j as f64).sin(), (j * j) as f64 / 10.0))
+            .collect();
+        let mut result = values.clone();
+        fft(&mut result);
+        for (k, result) in result.iter().enumerate() {
+            let expected = values
+                .iter()
+                .enumerate()
+                .fold(Complex::from(0.0), |sum, (j, x)| {
+                    let angle = -2.0 * PI * (j * k) as f64 / 16.0;
+                    sum + *x * Complex::new(angle.cos(), angle.sin())
+                });
+            assert!((*result - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn black_scholes_characteristic_function_matches_price() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let expected: Vec<f64> = STRIKES
+                .iter()
+                .map(|&k| bsm(opt, k).price().unwrap())
+                .collect();
+            let model = bsm(opt, 100.0);
+            let fft = CarrMadan::default().prices(&model, opt, &STRIKES).unwrap();
+            let cos = Cos::default().prices(&model, opt, &STRIKES).unwrap();
+            assert_prices(&fft, &expected, 1e-6);
+            assert_prices(&cos, &expected, 1e-10);
+        }
+    }
+
+    #[test]
+    fn numerical_cumulants_match_black_scholes() {
+        struct Numerical(BlackScholesModel);
+        impl CharacteristicFunction for Numerical {
+            fn characteristic_function(&self, u: Complex) -> Complex {
+                self.0.characteristic_function(u)
+            }
+            fn forward(&self) -> f64 {
+                CharacteristicFunction::forward(&self.0)
+            }
+            fn discount(&self) -> f64 {
+                CharacteristicFunction::discount(&self.0)
+            }
+        }
+        let model = bsm(OptionKind::Call, 100.0);
+        let (c1, c2, c4) = Numerical(model).cumulants();
+        let (e1, e2, _) = model.cumulants();
+        assert!((c1 - e1).abs() < 1e-10 && (c2 - e2).abs() < 1e-8 && c4.abs() < 1e-8);
+    }
+
+    #[test]
+    fn heston_strike_vector() {
+        let parameters = HestonParameters {
+            v0: 0.04,
+            kappa: 4.0,
+            theta: 0.25,
+            sigma: 1.0,
+            rho: -0.5,
+        };
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = HestonModel::new(opt, 100.0, 100.0, 0.01, 1.0, Some(0.02), parameters);
+            let expected: Vec<f64> = STRIKES
+                .iter()
+                .map(|&k| {
+                    HestonModel::new(opt, k, 100.0, 0.01, 1.0, Some(0.02), parameters)
+                        .price()
+                        .unwrap()
+                })
+                .collect();
+            let fft = CarrMadan::default().prices(&model, opt, &STRIKES).unwrap();
+            let cos = Cos::default().prices(&model, opt, &STRIKES).unwrap();
+            assert_prices(&fft, &expected, 1e-6);
+            assert_prices(&cos, &expected, 1e-7);
+        }
+    }
+
+    #[test]
+    fn err_with_strike_off_the_grid() {
+        let model = bsm(OptionKind::Call, 100.0);
+        let result = CarrMadan::default()
+            .with_points(64)
+            .prices(&model, OptionKind::Call, &[1e12]);
+        assert_eq!(result, Err(MathError::ParameterOutOfBounds("strike", 1e12)));
+    }
+}
@@ -0,0 +1,401 @@
+use crate::distributions::{norm_cdf, norm_inv_cdf, norm_pdf};
+use crate::{solver, BlackScholesModel, Greeks, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+// PremiumCurrency tells in which currency of the pair the premium is paid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PremiumCurrency {
+    Domestic, // domestic currency per unit of foreign notional (pips)
+    Foreign,  // foreign currency per unit of foreign notional (% of notional)
+}
+
+// DeltaConvention selects how FX deltas are quoted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeltaConvention {
+    Spot,                   // hedge with spot, premium paid in domestic currency
+    Forward,                // hedge with a forward, premium paid in domestic currency
+    PremiumAdjustedSpot,    // spot delta less the premium paid in foreign currency
+    PremiumAdjustedForward, // forward delta less the premium paid in foreign currency
+}
+
+// GarmanKohlhagenModel prices european FX options, it's the Black-Scholes model where
+// the foreign interest rate plays the role of the dividend yield
+//
+// The spot and the strike are quoted in domestic currency per unit of foreign currency
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GarmanKohlhagenModel {
+    opt: OptionKind,     // option type (call or put on the foreign currency)
+    strike: f64,         // strike rate (domestic per foreign)
+    spot: f64,           // spot rate (domestic per foreign)
+    domestic_rate: f64,  // continuously compounded domestic interest rate (% p.a.)
+    foreign_rate: f64,   // continuously compounded foreign interest rate (% p.a.)
+    volatility: f64,     // volatility of the exchange rate (% p.a.)
+    time_to_expire: f64, // time to expiration (% of year)
+}
+
+impl GarmanKohlhagenModel {
+    pub fn new(
+        opt: OptionKind,
+        strike: f64,
+        spot: f64,
+        domestic_rate: f64,
+        foreign_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+    ) -> GarmanKohlhagenModel {
+        GarmanKohlhagenModel {
+            opt,
+            strike,
+            spot,
+            domestic_rate,
+            foreign_rate,
+            volatility,
+            time_to_expire,
+        }
+    }
+
+    // try_new creates the model like new does, but rejects inputs for which the
+    // formula is undefined
+    pub fn try_new(
+        opt: OptionKind,
+        strike: f64,
+        spot: f64,
+        domestic_rate: f64,
+        foreign_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+    ) -> MathResult<GarmanKohlhagenModel> {
+        BlackScholesModel::try_new(
+            opt,
+            strike,
+            spot,
+            domestic_rate,
+            volatility,
+            time_to_expire,
+            Some(foreign_rate),
+        )?;
+        Ok(GarmanKohlhagenModel::new(
+            opt,
+            strike,
+            spot,
+            domestic_rate,
+            foreign_rate,
+            volatility,
+            time_to_expire,
+        ))
+    }
+
+    pub fn strike(&self) -> f64 {
+        self.strike
+    }
+
+    pub fn with_strike(mut self, strike: f64) -> GarmanKohlhagenModel {
+        self.strike = strike;
+        self
+    }
+
+    // forward calculates the outright forward rate (domestic per foreign)
+    pub fn forward(&self) -> f64 {
+        self.spot * E.powf((self.domestic_rate - self.foreign_rate) * self.time_to_expire)
+    }
+
+    // price calculates the premium in domestic currency per unit of foreign notional
+    pub fn price(&self) -> MathResult {
+        self.as_black_scholes().price()
+    }
+
+    // premium calculates the price per unit of foreign notional in the given currency
+    pub fn premium(&self, currency: PremiumCurrency) -> MathResult {
+        match currency {
+            PremiumCurrency::Domestic => self.price(),
+            PremiumCurrency::Foreign => Ok(self.price()? / self.spot),
+        }
+    }
+
+    // greeks calculates the sensitivities of the domestic premium, rho is taken with
+    // respect to the domestic rate and dividend rho with respect to the foreign one
+    pub fn greeks(&self) -> MathResult<Greeks> {
+        self.as_black_scholes().greeks()
+    }
+
+    // foreign_rho calculates the rate of change of the price with respect to the
+    // foreign interest rate
+    pub fn foreign_rho(&self) -> MathResult {
+        self.as_black_scholes().dividend_rho()
+    }
+
+    // delta calculates the delta quoted with the given market convention
+    //
+    // At expiration or without volatility the option holds either one forward or none,
+    // at the money that's undefined and an error
+    pub fn delta(&self, convention: DeltaConvention) -> MathResult {
+        let model = self.as_black_scholes();
+        let without_time_value = model.without_time_value()?;
+        let (d1, d2) = model.d1_d2();
+        let sign = self.sign();
+        // signed probability of exercise in the spot (d1) or domestic (d2) measure
+        let exercise = |d: f64| without_time_value.unwrap_or_else(|| sign * norm_cdf(sign * d));
+        let foreign_discount = E.powf(-self.foreign_rate * self.time_to_expire);
+        let moneyness = self.strike / self.forward();
+
+        match convention {
+            DeltaConvention::Spot => Ok(foreign_discount * exercise(d1)),
+            DeltaConvention::Forward => Ok(exercise(d1)),
+            DeltaConvention::PremiumAdjustedSpot => Ok(foreign_discount * moneyness * exercise(d2)),
+            DeltaConvention::PremiumAdjustedForward => Ok(moneyness * exercise(d2)),
+        }
+    }
+
+    // strike_for_delta calculates the strike at which the option has the requested delta
+    // (e.g. 0.25 for a 25 delta call or -0.1 for a 10 delta put), the model's own strike
+    // is ignored
+    pub fn strike_for_delta(&self, delta: f64, convention: DeltaConvention) -> MathResult {
+        let sign = self.sign();
+        let deviation = self.volatility * self.time_to_expire.sqrt();
+        let forward = self.forward();
+
+        match convention {
+            DeltaConvention::Spot | DeltaConvention::Forward => {
+                let undiscounted = match convention {
+                    DeltaConvention::Spot => {
+                        delta * E.powf(self.foreign_rate * self.time_to_expire)
+                    }
+                    _ => delta,
+                };
+                let probability = sign * undiscounted;
+                if probability <= 0.0 || probability >= 1.0 {
+                    return Err(MathError::DeltaOutOfBounds(delta));
+                }
+                let d1 = sign * norm_inv_cdf(probability);
+                Ok(forward * E.powf(-d1 * deviation + deviation.powi(2) / 2.0))
+            }
+            DeltaConvention::PremiumAdjustedSpot | DeltaConvention::PremiumAdjustedForward => {
+                if sign * delta <= 0.0 {
+                    return Err(MathError::DeltaOutOfBounds(delta));
+                }
+                let target = |strike| {
+                    self.with_strike(strike)
+                        .delta(convention)
+                        .map(|d| d - delta)
+                };
+                let (lower, upper) = match self.opt {
+                    // premium-adjusted call deltas peak at the strike where
+                    // deviation N(d2) = n(d2), only strikes above it are quoted
+                    OptionKind::Call => {
+                        let d2 = solver::brent(
+                            |d2| Ok(deviation * norm_cdf(d2) - norm_pdf(d2)),
+                            -10.0,
+                            10.0,
+                        )?;
+                        let lower = forward * E.powf(-d2 * deviation - deviation.powi(2) / 2.0);
+                        if target(lower)? < 0.0 {
+                            return Err(MathError::DeltaOutOfBounds(delta));
+                        }
+                        (lower, forward * E.powf(10.0 * deviation))
+                    }
+                    OptionKind::Put => (
+                        forward * E.powf(-10.0 * deviation),
+                        forward * E.powf(10.0 * deviation),
+                    ),
+                };
+                solver::brent(target, lower, upper).map_err(|_| MathError::DeltaOutOfBounds(delta))
+            }
+        }
+    }
+
+    // atm_strike calculates the delta-neutral straddle strike, at which the call and
+    // the put have deltas of the same size under the given convention
+    pub fn atm_strike(&self, convention: DeltaConvention) -> f64 {
+        let variance = self.volatility.powi(2) * self.time_to_expire;
+        match convention {
+            DeltaConvention::Spot | DeltaConvention::Forward => {
+                self.forward() * E.powf(variance / 2.0)
+            }
+            DeltaConvention::PremiumAdjustedSpot | DeltaConvention::PremiumAdjustedForward => {
+                self.forward() * E.powf(-variance / 2.0)
+            }
+        }
+    }
+
+    fn sign(&self) -> f64 {
+        match self.opt {
+            OptionKind::Call => 1.0,
+            OptionKind::Put => -1.0,
+        }
+    }
+
+    fn as_black_scholes(&self) -> BlackScholesModel {
+        BlackScholesModel::new(
+            self.opt,
+            self.strike,
+            self.spot,
+            self.domestic_rate,
+            self.volatility,
+            self.time_to_expire,
+            Some(self.foreign_rate),
+        )
+    }
+}
+
+// implied_volatility calculates the volatility at which GarmanKohlhagenModel::price
+// matches the observed premium in domestic currency per unit of foreign notional
+pub fn implied_volatility(
+    opt: OptionKind,
+    strike: f64,
+    spot: f64,
+    domestic_rate: f64,
+    foreign_rate: f64,
+    time_to_expire: f64,
+    premium: f64,
+) -> MathResult {
+    crate::implied_volatility(
+        opt,
+        strike,
+        spot,
+        domestic_rate,
+        time_to_expire,
+        Some(foreign_rate),
+        premium,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_close;
+
+    const CONVENTIONS: [DeltaConvention; 4] = [
+        DeltaConvention::Spot,
+        DeltaConvention::Forward,
+        DeltaConvention::PremiumAdjustedSpot,
+        DeltaConvention::PremiumAdjustedForward,
+    ];
+
+    fn eurusd(opt: OptionKind) -> GarmanKohlhagenModel {
+        GarmanKohlhagenModel::new(opt, 1.35, 1.3465, 0.0294, 0.0346, 0.1825, 1.0)
+    }
+
+    #[test]
+    fn call_price() {
+        // Haug, The Complete Guide to Option Pricing Formulas: S = 1.56, K = 1.60,
+        // 6 months, domestic rate 6%, foreign rate 8%, vol 12%
+        let model = GarmanKohlhagenModel::new(OptionKind::Call, 1.6, 1.56, 0.06, 0.08, 0.12, 0.5);
+        assert_close(model.price().unwrap(), 0.0291, 1e-4);
+    }
+
+    #[test]
+    fn premium_in_foreign_currency() {
+        let model = eurusd(OptionKind::Put);
+        let domestic = model.premium(PremiumCurrency::Domestic).unwrap();
+        let foreign = model.premium(PremiumCurrency::Foreign).unwrap();
+        assert_close(foreign * 1.3465, domestic, 1e-15);
+    }
+
+    #[test]
+    fn foreign_rho_matches_finite_difference() {
+        let model = eurusd(OptionKind::Call);
+        let h = 1e-5;
+        let mut up = model;
+        up.foreign_rate += h;
+        let mut down = model;
+        down.foreign_rate -= h;
+        let expected = (up.price().unwrap() - down.price().unwrap()) / (2.0 * h);
+
+        assert_close(model.foreign_rho().unwrap(), expected, 1e-8);
+        assert_eq!(
+            model.greeks().unwrap().dividend_rho,
+            model.foreign_rho().unwrap()
+        );
+    }
+
+    #[test]
+    fn premium_adjusted_delta_deducts_foreign_premium() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = eurusd(opt);
+            let spot = model.delta(DeltaConvention::Spot).unwrap();
+            let adjusted = model.delta(DeltaConvention::PremiumAdjustedSpot).unwrap();
+            let premium = model.premium(PremiumCurrency::Foreign).unwrap();
+            assert_close(adjusted, spot - premium, 1e-14);
+
+            let forward = model.delta(DeltaConvention::Forward).unwrap();
+            let discount = E.powf(-model.foreign_rate * model.time_to_expire);
+            assert_close(spot, forward * discount, 1e-15);
+        }
+    }
+
+    #[test]
+    fn delta_without_time_value() {
+        let expired =
+            GarmanKohlhagenModel::new(OptionKind::Put, 1.3, 1.25, 0.0294, 0.0346, 0.1825, 0.0);
+        for (convention, expected) in CONVENTIONS.iter().zip([-1.0, -1.0, -1.04, -1.04]) {
+            assert_close(expired.delta(*convention).unwrap(), expected, 1e-12);
+            assert_eq!(expired.with_strike(1.2).delta(*convention), Ok(0.0));
+            assert_eq!(
+                expired.with_strike(1.25).delta(*convention),
+                Err(MathError::AtTheMoneyWithoutTimeValue(1.25))
+            );
+        }
+    }
+
+    #[test]
+    fn strike_for_delta_round_trips() {
+        for convention in CONVENTIONS {
+            for (opt, delta) in [
+                (OptionKind::Call, 0.25),
+                (OptionKind::Call, 0.1),
+                (OptionKind::Put, -0.25),
+                (OptionKind::Put, -0.1),
+            ] {
+                let model = eurusd(opt);
+                let strike = model.strike_for_delta(delta, convention).unwrap();
+                let result = model.with_strike(strike).delta(convention).unwrap();
+                assert_close(result, delta, 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn delta_strikes_are_ordered() {
+        let put = eurusd(OptionKind::Put)
+            .strike_for_delta(-0.25, DeltaConvention::Spot)
+            .unwrap();
+        let call = eurusd(OptionKind::Call)
+            .strike_for_delta(0.25, DeltaConvention::Spot)
+            .unwrap();
+        let atm = eurusd(OptionKind::Call).atm_strike(DeltaConvention::Spot);
+        assert!(put < atm && atm < call);
+    }
+
+    #[test]
+    fn atm_strike_is_delta_neutral() {
+        for convention in CONVENTIONS {
+            let strike = eurusd(OptionKind::Call).atm_strike(convention);
+            let call = eurusd(OptionKind::Call).with_strike(strike);
+            let put = eurusd(OptionKind::Put).with_strike(strike);
+            let straddle = call.delta(convention).unwrap() + put.delta(convention).unwrap();
+            assert_close(straddle, 0.0, 1e-14);
+        }
+    }
+
+    #[test]
+    fn implied_volatility_recovers_model_volatility() {
+        let premium = eurusd(OptionKind::Call).price().unwrap();
+        let result =
+            implied_volatility(OptionKind::Call, 1.35, 1.3465, 0.0294, 0.0346, 1.0, premium)
+                .unwrap();
+        assert_close(result, 0.1825, 1e-10);
+    }
+
+    #[test]
+    fn err_with_unreachable_delta() {
+        let call = eurusd(OptionKind::Call);
+        assert_eq!(
+            call.strike_for_delta(-0.25, DeltaConvention::Spot),
+            Err(MathError::DeltaOutOfBounds(-0.25))
+        );
+        assert_eq!(
+            call.strike_for_delta(0.99, DeltaConvention::PremiumAdjustedSpot),
+            Err(MathError::DeltaOutOfBounds(0.99))
+        );
+    }
+}
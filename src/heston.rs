@@ -0,0 +1,396 @@
+use crate::complex::Complex;
+use crate::fourier::{lewis, CharacteristicFunction};
+use crate::solver::levenberg_marquardt;
+use crate::{check_finite, BlackScholesModel, MathError, MathResult, OptionKind, VolQuote};
+use std::f64::consts::{E, PI};
+
+// HestonParameters describe the variance process
+// dv = kappa (theta - v) dt + sigma sqrt(v) dW, with d<W, Z> = rho dt against the stock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HestonParameters {
+    pub v0: f64,    // initial variance
+    pub kappa: f64, // speed of mean reversion
+    pub theta: f64, // long-run variance
+    pub sigma: f64, // volatility of variance
+    pub rho: f64,   // correlation between stock and variance
+}
+
+// HestonModel prices european options under Heston's (1993) stochastic volatility
+// model by integrating its characteristic function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HestonModel {
+    opt: OptionKind,              // option type (call or put)
+    strike: f64,                  // strike price ($$$ per share)
+    stock: f64,                   // underlying price ($$$ per share)
+    interest_rate: f64,           // continuously compounded risk-free interest rate (% p.a.)
+    time_to_expire: f64,          // time to expiration (% of year)
+    dividend: Option<f64>,        // continuously compounded dividend yield (% p.a.)
+    parameters: HestonParameters, // variance process
+}
+
+impl HestonModel {
+    pub fn new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+        parameters: HestonParameters,
+    ) -> HestonModel {
+        HestonModel {
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            time_to_expire,
+            dividend,
+            parameters,
+        }
+    }
+
+    // try_new creates the model like new does, but rejects market inputs for which the
+    // Black-Scholes formula is undefined and parameters outside their domain
+    pub fn try_new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+        parameters: HestonParameters,
+    ) -> MathResult<HestonModel> {
+        BlackScholesModel::try_new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            parameters.v0.sqrt(),
+            time_to_expire,
+            dividend,
+        )?;
+        check_parameters(&parameters)?;
+        Ok(HestonModel::new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            time_to_expire,
+            dividend,
+            parameters,
+        ))
+    }
+
+    pub fn parameters(&self) -> HestonParameters {
+        self.parameters
+    }
+
+    // price calculates the fair value of the option ($$$ per share) with Lewis' (2000)
+    // single integral, puts follow from put-call parity
+    pub fn price(&self) -> MathResult {
+        let discount = self.discount();
+        let forward = self.forward();
+        let call = if self.time_to_expire == 0.0 {
+            (forward - self.strike).max(0.0)
+        } else {
+            let integral = lewis(self, self.strike, |_| Complex::from(1.0))?;
+            forward - (forward * self.strike).sqrt() / PI * integral
+        };
+        Ok(match self.opt {
+            OptionKind::Call => discount * call,
+            OptionKind::Put => discount * (call - forward + self.strike),
+        })
+    }
+}
+
+impl CharacteristicFunction for HestonModel {
+    // characteristic_function is in the "little trap" form of Albrecher et al. (2007)
+    // which keeps the complex logarithm on its principal branch
+    fn characteristic_function(&self, u: Complex) -> Complex {
+        let HestonParameters {
+            v0,
+            kappa,
+            theta,
+            sigma,
+            rho,
+        } = self.parameters;
+        let t = self.time_to_expire;
+        let iu = Complex::I * u;
+
+        let beta = kappa - rho * sigma * iu;
+        let d = (beta * beta + sigma * sigma * (iu + u * u)).sqrt();
+        let g = (beta - d) / (beta + d);
+        let decay = (-d * t).exp();
+        let c = kappa * theta / sigma.powi(2)
+            * ((beta - d) * t - 2.0 * ((1.0 - g * decay) / (1.0 - g)).ln());
+        let dv = (beta - d) / sigma.powi(2) * (1.0 - decay) / (1.0 - g * decay);
+        (c + dv * v0).exp()
+    }
+
+    fn forward(&self) -> f64 {
+        let carry = self.interest_rate - self.dividend.unwrap_or_default();
+        self.stock * E.powf(carry * self.time_to_expire)
+    }
+
+    fn discount(&self) -> f64 {
+        E.powf(-self.interest_rate * self.time_to_expire)
+    }
+}
+
+// check_parameters rejects parameters outside their domain, the initial variance like
+// the squared volatility of the Black-Scholes model
+fn check_parameters(p: &HestonParameters) -> MathResult<()> {
+    let volatility = p.v0.sqrt();
+    check_finite("volatility", volatility)?;
+    if volatility <= 0.0 {
+        return Err(MathError::NonPositiveVolatility(volatility));
+    }
+    for &(name, value) in &[
+        ("kappa", p.kappa),
+        ("theta", p.theta),
+        ("sigma", p.sigma),
+        ("rho", p.rho),
+    ] {
+        check_finite(name, value)?;
+    }
+    if p.kappa <= 0.0 {
+        return Err(MathError::ParameterOutOfBounds("kappa", p.kappa));
+    }
+    if p.theta <= 0.0 {
+        return Err(MathError::ParameterOutOfBounds("theta", p.theta));
+    }
+    if p.sigma <= 0.0 {
+        return Err(MathError::ParameterOutOfBounds("sigma", p.sigma));
+    }
+    if p.rho.abs() > 1.0 {
+        return Err(MathError::ParameterOutOfBounds("rho", p.rho));
+    }
+    Ok(())
+}
+
+// calibrate fits the Heston parameters to the quoted implied volatilities with
+// Levenberg-Marquardt, starting from the initial guess
+//
+// The residuals are the price differences divided by the Black-Scholes vega of the
+// quotes, a first order approximation of the implied volatility errors which avoids
+// inverting every trial price
+pub fn calibrate(
+    stock: f64,
+    interest_rate: f64,
+    dividend: Option<f64>,
+    quotes: &[VolQuote],
+    initial: HestonParameters,
+) -> MathResult<HestonParameters> {
+    let markets = quotes
+        .iter()
+        .map(|q| {
+            let model = BlackScholesModel::try_new(
+                OptionKind::Call,
+                q.strike,
+                stock,
+                interest_rate,
+                q.volatility,
+                q.time_to_expire,
+                dividend,
+            )?;
+            Ok((q, model.price()?, model.vega()?.max(1e-8)))
+        })
+        .collect::<MathResult<Vec<_>>>()?;
+
+    // positive parameters are fitted through their logarithm and the correlation
+    // through its inverse hyperbolic tangent, so every step stays in the domain
+    let unpack = |x: &[f64]| HestonParameters {
+        v0: x[0].exp(),
+        kappa: x[1].exp(),
+        theta: x[2].exp(),
+        sigma: x[3].exp(),
+        rho: x[4].tanh(),
+    };
+    let residuals = |x: &[f64]| {
+        let parameters = unpack(x);
+        markets
+            .iter()
+            .map(|&(q, price, vega)| {
+                let model = HestonModel::new(
+                    OptionKind::Call,
+                    q.strike,
+                    stock,
+                    interest_rate,
+                    q.time_to_expire,
+                    dividend,
+                    parameters,
+                );
+                Ok((model.price()? - price) / vega)
+            })
+            .collect()
+    };
+    // the start has to be inside the domain before taking logarithms
+    check_parameters(&initial)?;
+    let rho = initial.rho.clamp(-0.999, 0.999);
+    let start = [
+        initial.v0.ln(),
+        initial.kappa.ln(),
+        initial.theta.ln(),
+        initial.sigma.ln(),
+        0.5 * ((1.0 + rho) / (1.0 - rho)).ln(),
+    ];
+    Ok(unpack(&levenberg_marquardt(residuals, &start)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Lewis, Option Valuation under Stochastic Volatility: S = 100, r = 1%, q = 2%,
+    // 1 year, v0 = 0.04, kappa = 4, theta = 0.25, sigma = 1, rho = -0.5
+    const LEWIS: HestonParameters = HestonParameters {
+        v0: 0.04,
+        kappa: 4.0,
+        theta: 0.25,
+        sigma: 1.0,
+        rho: -0.5,
+    };
+
+    fn heston(opt: OptionKind, strike: f64) -> HestonModel {
+        HestonModel::new(opt, strike, 100.0, 0.01, 1.0, Some(0.02), LEWIS)
+    }
+
+    #[test]
+    fn call_prices() {
+        for &(strike, expected) in &[
+            (80.0, 26.774758743998854),
+            (90.0, 20.93334900059671),
+            (100.0, 16.070154917028834),
+            (110.0, 12.132211516709845),
+            (120.0, 9.024913483457836),
+        ] {
+            let result = heston(OptionKind::Call, strike).price().unwrap();
+            assert!(
+                (result - expected).abs() < 1e-9,
+                "{} is not {}",
+                result,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn put_call_parity() {
+        let call = heston(OptionKind::Call, 95.0).price().unwrap();
+        let put = heston(OptionKind::Put, 95.0).price().unwrap();
+        let parity = 100.0 * E.powf(-0.02) - 95.0 * E.powf(-0.01);
+        assert!((call - put - parity).abs() < 1e-12);
+    }
+
+    #[test]
+    fn characteristic_function_is_a_martingale() {
+        let model = heston(OptionKind::Call, 100.0);
+        let one = Complex::from(1.0);
+        assert!((model.characteristic_function(Complex::from(0.0)) - one).abs() < 1e-15);
+        assert!((model.characteristic_function(-Complex::I) - one).abs() < 1e-14);
+    }
+
+    #[test]
+    fn constant_variance_is_black_scholes() {
+        let parameters = HestonParameters {
+            v0: 0.04,
+            kappa: 1.0,
+            theta: 0.04,
+            sigma: 1e-4,
+            rho: 0.0,
+        };
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = HestonModel::new(opt, 58.0, 60.0, 0.035, 0.5, Some(0.0125), parameters);
+            let expected = BlackScholesModel::new(opt, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125))
+                .price()
+                .unwrap();
+            assert!((model.price().unwrap() - expected).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn calibration_recovers_parameters() {
+        let truth = HestonParameters {
+            v0: 0.05,
+            kappa: 2.0,
+            theta: 0.06,
+            sigma: 0.5,
+            rho: -0.7,
+        };
+        let mut quotes = Vec::new();
+        for &time_to_expire in &[0.25, 1.0, 2.0] {
+            for &strike in &[80.0, 90.0, 100.0, 110.0, 120.0] {
+                let premium = HestonModel::new(
+                    OptionKind::Call,
+                    strike,
+                    100.0,
+                    0.02,
+                    time_to_expire,
+                    None,
+                    truth,
+                )
+                .price()
+                .unwrap();
+                let volatility = crate::implied_volatility(
+                    OptionKind::Call,
+                    strike,
+                    100.0,
+                    0.02,
+                    time_to_expire,
+                    None,
+                    premium,
+                )
+                .unwrap();
+                quotes.push(VolQuote {
+                    strike,
+                    time_to_expire,
+                    volatility,
+                });
+            }
+        }
+
+        let initial = HestonParameters {
+            v0: 0.04,
+            kappa: 1.0,
+            theta: 0.04,
+            sigma: 0.3,
+            rho: -0.3,
+        };
+        let result = calibrate(100.0, 0.02, None, &quotes, initial).unwrap();
+        assert!((result.v0 - truth.v0).abs() < 1e-4);
+        assert!((result.kappa - truth.kappa).abs() < 1e-2);
+        assert!((result.theta - truth.theta).abs() < 1e-4);
+        assert!((result.sigma - truth.sigma).abs() < 1e-3);
+        assert!((result.rho - truth.rho).abs() < 1e-3);
+    }
+
+    #[test]
+    fn err_with_invalid_parameters() {
+        let invalid = HestonParameters { rho: -1.5, ..LEWIS };
+        let result = HestonModel::try_new(OptionKind::Call, 100.0, 100.0, 0.01, 1.0, None, invalid);
+        assert_eq!(result, Err(MathError::ParameterOutOfBounds("rho", -1.5)));
+
+        let invalid = HestonParameters { v0: 0.0, ..LEWIS };
+        let result = HestonModel::try_new(OptionKind::Call, 100.0, 100.0, 0.01, 1.0, None, invalid);
+        assert_eq!(result, Err(MathError::NonPositiveVolatility(0.0)));
+    }
+
+    #[test]
+    fn err_calibrating_from_invalid_parameters() {
+        let quotes = [VolQuote {
+            strike: 100.0,
+            time_to_expire: 1.0,
+            volatility: 0.2,
+        }];
+        let invalid = HestonParameters { v0: 0.0, ..LEWIS };
+        let result = calibrate(100.0, 0.01, None, &quotes, invalid);
+        assert_eq!(result, Err(MathError::NonPositiveVolatility(0.0)));
+
+        let invalid = HestonParameters {
+            sigma: -0.1,
+            ..LEWIS
+        };
+        let result = calibrate(100.0, 0.01, None, &quotes, invalid);
+        assert_eq!(result, Err(MathError::ParameterOutOfBounds("sigma", -0.1)));
+    }
+}
@@ -0,0 +1,870 @@
+use crate::complex::Complex;
+use crate::fourier::{lewis, CharacteristicFunction};
+use crate::solver::levenberg_marquardt;
+use crate::{check_finite, BlackScholesModel, Greeks, MathError, MathResult, OptionKind, VolQuote};
+use std::f64::consts::{E, PI};
+
+const MAX_TERMS: usize = 1000;
+
+// MertonJumps describe the compound Poisson jumps of Merton's (1976) model, the log of
+// every jump size is normally distributed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MertonJumps {
+    pub intensity: f64,  // expected number of jumps per year
+    pub mean: f64,       // mean of the log jump size
+    pub volatility: f64, // standard deviation of the log jump size
+}
+
+// MertonModel adds lognormal jumps to the diffusion of the Black-Scholes model, the
+// drift is compensated so that the discounted stock stays a martingale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MertonModel {
+    model: BlackScholesModel, // contract and diffusion, the volatility excludes the jumps
+    jumps: MertonJumps,       // jump process
+}
+
+impl MertonModel {
+    pub fn new(model: BlackScholesModel, jumps: MertonJumps) -> MertonModel {
+        MertonModel { model, jumps }
+    }
+
+    // try_new creates the model like new does, but rejects market inputs for which the
+    // Black-Scholes formula is undefined and jumps outside their domain
+    pub fn try_new(model: BlackScholesModel, jumps: MertonJumps) -> MathResult<MertonModel> {
+        validate(&model)?;
+        check_finite("intensity", jumps.intensity)?;
+        check_finite("mean", jumps.mean)?;
+        check_finite("jump_volatility", jumps.volatility)?;
+        if jumps.intensity < 0.0 {
+            return Err(MathError::ParameterOutOfBounds(
+                "intensity",
+                jumps.intensity,
+            ));
+        }
+        if jumps.volatility < 0.0 {
+            return Err(MathError::ParameterOutOfBounds(
+                "jump_volatility",
+                jumps.volatility,
+            ));
+        }
+        Ok(MertonModel::new(model, jumps))
+    }
+
+    pub fn jumps(&self) -> MertonJumps {
+        self.jumps
+    }
+
+    // price calculates the fair value of the option ($$$ per share) as Merton's series of
+    // Black-Scholes prices, conditional on the number of jumps until expiration
+    pub fn price(&self) -> MathResult {
+        // no jump can happen any more at expiration
+        if self.model.time_to_expire == 0.0 {
+            return self.model.price();
+        }
+        let mut price = 0.0;
+        self.series(|weight, term| {
+            price += weight * term.price()?;
+            Ok(())
+        })?;
+        Ok(price)
+    }
+
+    // greeks calculates the sensitivities by differentiating the series term by term,
+    // vega is with respect to the volatility of the diffusion
+    //
+    // At expiration the greeks are those of the intrinsic value
+    pub fn greeks(&self) -> MathResult<Greeks> {
+        if self.model.time_to_expire == 0.0 {
+            return self.model.greeks();
+        }
+        let MertonJumps {
+            intensity,
+            volatility: jump_volatility,
+            ..
+        } = self.jumps;
+        let (stock, volatility) = (self.model.stock, self.model.volatility);
+        let time_to_expire = self.model.time_to_expire;
+        let drift = intensity * self.compensator();
+
+        let mut greeks = Greeks {
+            delta: 0.0,
+            gamma: 0.0,
+            vega: 0.0,
+            theta: 0.0,
+            rho: 0.0,
+            dividend_rho: 0.0,
+        };
+        let mut jumps = 0.0;
+        self.series(|weight, term| {
+            let term_greeks = term.greeks()?;
+            let scale = term.stock / stock;
+            // the time to expiration also moves the Poisson weight, the stock of the
+            // term and the jump part of its variance
+            let weight_decay = if jumps > 0.0 {
+                jumps / time_to_expire - intensity
+            } else {
+                -intensity
+            };
+            let variance_decay = if jumps > 0.0 {
+                -jumps * jump_volatility.powi(2) / (2.0 * time_to_expire.powi(2) * term.volatility)
+            } else {
+                0.0
+            };
+            let change = weight_decay * term.price()?
+                - term_greeks.theta
+                - term_greeks.delta * drift * term.stock
+                + term_greeks.vega * variance_decay;
+
+            greeks.delta += weight * term_greeks.delta * scale;
+            greeks.gamma += weight * term_greeks.gamma * scale * scale;
+            greeks.vega += weight * term_greeks.vega * volatility / term.volatility;
+            greeks.theta -= weight * change;
+            greeks.rho += weight * term_greeks.rho;
+            greeks.dividend_rho += weight * term_greeks.dividend_rho;
+            jumps += 1.0;
+            Ok(())
+        })?;
+        Ok(greeks)
+    }
+
+    // series visits the Black-Scholes model conditional on n jumps with its Poisson
+    // weight, n = 0, 1, ... until the weights are negligible
+    //
+    // With n jumps the stock moves by exp(n (mean + jump_volatility^2 / 2)) on top of
+    // the compensating drift, and the variance grows by n jump_volatility^2
+    fn series<F>(&self, mut visit: F) -> MathResult<()>
+    where
+        F: FnMut(f64, BlackScholesModel) -> MathResult<()>,
+    {
+        let MertonJumps {
+            intensity,
+            mean,
+            volatility,
+        } = self.jumps;
+        let time_to_expire = self.model.time_to_expire;
+        let expected_jumps = intensity * time_to_expire;
+        let drift = -intensity * self.compensator() * time_to_expire;
+
+        let mut weight = E.powf(-expected_jumps);
+        for n in 0..MAX_TERMS {
+            let jumps = n as f64;
+            let term = if n == 0 {
+                BlackScholesModel {
+                    stock: self.model.stock * E.powf(drift),
+                    ..self.model
+                }
+            } else {
+                BlackScholesModel {
+                    stock: self.model.stock
+                        * E.powf(drift + jumps * (mean + 0.5 * volatility.powi(2))),
+                    volatility: (self.model.volatility.powi(2)
+                        + jumps * volatility.powi(2) / time_to_expire)
+                        .sqrt(),
+                    ..self.model
+                }
+            };
+            visit(weight, term)?;
+
+            if jumps >= expected_jumps && weight < 1e-18 {
+                return Ok(());
+            }
+            weight *= expected_jumps / (jumps + 1.0);
+        }
+        Err(MathError::NoConvergence(MAX_TERMS))
+    }
+
+    // exponent is the characteristic exponent of ln(S(T) / F) per year
+    fn exponent(&self, u: Complex) -> Complex {
+        let MertonJumps {
+            intensity,
+            mean,
+            volatility,
+        } = self.jumps;
+        let iu = Complex::I * u;
+        let diffusion = -0.5 * self.model.volatility.powi(2) * (u * u + iu);
+        let jump = (iu * mean - 0.5 * volatility.powi(2) * u * u).exp() - 1.0;
+        diffusion - iu * intensity * self.compensator() + intensity * jump
+    }
+
+    // compensator is the expected relative jump size E[J - 1]
+    fn compensator(&self) -> f64 {
+        E.powf(self.jumps.mean + 0.5 * self.jumps.volatility.powi(2)) - 1.0
+    }
+}
+
+impl CharacteristicFunction for MertonModel {
+    fn characteristic_function(&self, u: Complex) -> Complex {
+        (self.exponent(u) * self.model.time_to_expire).exp()
+    }
+
+    fn forward(&self) -> f64 {
+        CharacteristicFunction::forward(&self.model)
+    }
+
+    fn discount(&self) -> f64 {
+        CharacteristicFunction::discount(&self.model)
+    }
+
+    fn cumulants(&self) -> (f64, f64, f64) {
+        let MertonJumps {
+            intensity,
+            mean,
+            volatility,
+        } = self.jumps;
+        let t = self.model.time_to_expire;
+        let (variance, jump_variance) = (self.model.volatility.powi(2), volatility.powi(2));
+        (
+            (-0.5 * variance - intensity * self.compensator() + intensity * mean) * t,
+            (variance + intensity * (mean * mean + jump_variance)) * t,
+            intensity
+                * (mean.powi(4) + 6.0 * mean * mean * jump_variance + 3.0 * jump_variance.powi(2))
+                * t,
+        )
+    }
+}
+
+// KouJumps describe the compound Poisson jumps of Kou's (2002) model, the log of every
+// jump size is exponentially distributed upwards and downwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KouJumps {
+    pub intensity: f64,   // expected number of jumps per year
+    pub probability: f64, // probability of an upward jump
+    pub up_rate: f64,     // rate of the upward log jumps, above 1 (mean 1 / up_rate)
+    pub down_rate: f64,   // rate of the downward log jumps (mean 1 / down_rate)
+}
+
+// KouModel adds double exponential jumps to the diffusion of the Black-Scholes model,
+// the drift is compensated so that the discounted stock stays a martingale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KouModel {
+    model: BlackScholesModel, // contract and diffusion, the volatility excludes the jumps
+    jumps: KouJumps,          // jump process
+}
+
+impl KouModel {
+    pub fn new(model: BlackScholesModel, jumps: KouJumps) -> KouModel {
+        KouModel { model, jumps }
+    }
+
+    // try_new creates the model like new does, but rejects market inputs for which the
+    // Black-Scholes formula is undefined and jumps outside their domain
+    pub fn try_new(model: BlackScholesModel, jumps: KouJumps) -> MathResult<KouModel> {
+        validate(&model)?;
+        for &(name, value) in &[
+            ("intensity", jumps.intensity),
+            ("probability", jumps.probability),
+            ("up_rate", jumps.up_rate),
+            ("down_rate", jumps.down_rate),
+        ] {
+            check_finite(name, value)?;
+        }
+        if jumps.intensity < 0.0 {
+            return Err(MathError::ParameterOutOfBounds(
+                "intensity",
+                jumps.intensity,
+            ));
+        }
+        if !(0.0..=1.0).contains(&jumps.probability) {
+            return Err(MathError::ParameterOutOfBounds(
+                "probability",
+                jumps.probability,
+            ));
+        }
+        // the expected jump is infinite for up_rate <= 1
+        if jumps.up_rate <= 1.0 {
+            return Err(MathError::ParameterOutOfBounds("up_rate", jumps.up_rate));
+        }
+        if jumps.down_rate <= 0.0 {
+            return Err(MathError::ParameterOutOfBounds(
+                "down_rate",
+                jumps.down_rate,
+            ));
+        }
+        Ok(KouModel::new(model, jumps))
+    }
+
+    pub fn jumps(&self) -> KouJumps {
+        self.jumps
+    }
+
+    // price calculates the fair value of the option ($$$ per share) with Lewis' single
+    // integral over the characteristic function, puts follow from put-call parity
+    pub fn price(&self) -> MathResult {
+        let forward = CharacteristicFunction::forward(self);
+        let strike = self.model.strike;
+        let call = if self.model.time_to_expire == 0.0 {
+            (forward - strike).max(0.0)
+        } else {
+            let integral = lewis(self, strike, |_| Complex::from(1.0))?;
+            forward - (forward * strike).sqrt() / PI * integral
+        };
+        Ok(self.model.discount()
+            * match self.model.opt {
+                OptionKind::Call => call,
+                OptionKind::Put => call - forward + strike,
+            })
+    }
+
+    // greeks calculates the sensitivities by differentiating Lewis' integral under the
+    // integral sign, vega is with respect to the volatility of the diffusion
+    //
+    // The price depends on the stock only through the forward, and the characteristic
+    // exponent does not depend on the time to expiration. At expiration the greeks are
+    // those of the intrinsic value
+    pub fn greeks(&self) -> MathResult<Greeks> {
+        if self.model.time_to_expire == 0.0 {
+            return self.model.greeks();
+        }
+        let BlackScholesModel {
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        } = self.model;
+        let forward = CharacteristicFunction::forward(self);
+        let discount = self.model.discount();
+        let price = self.price()?;
+        let root = (forward * strike).sqrt();
+
+        let level = lewis(self, strike, |_| Complex::from(1.0))?;
+        let slope = lewis(self, strike, |u| Complex::new(0.0, u))?;
+        let curvature = lewis(self, strike, |u| Complex::from(-u * u))?;
+        let variance = lewis(self, strike, |u| Complex::from(u * u + 0.25))?;
+        let decay = lewis(self, strike, |u| self.exponent(Complex::new(u, -0.5)))?;
+
+        // first and second derivative of the undiscounted price in the forward
+        let forward_delta = 1.0
+            - (strike / forward).sqrt() / PI * (0.5 * level + slope)
+            - match opt {
+                OptionKind::Call => 0.0,
+                OptionKind::Put => 1.0,
+            };
+        let forward_gamma = strike.sqrt() / (PI * forward.powf(1.5)) * (0.25 * level - curvature);
+        let carry = interest_rate - dividend.unwrap_or_default();
+
+        Ok(Greeks {
+            delta: discount * forward_delta * forward / stock,
+            gamma: discount * forward_gamma * (forward / stock).powi(2),
+            vega: discount * root / PI * volatility * time_to_expire * variance,
+            theta: interest_rate * price - discount * forward_delta * forward * carry
+                + discount * root / PI * decay,
+            rho: -time_to_expire * price + discount * forward_delta * forward * time_to_expire,
+            dividend_rho: -discount * forward_delta * forward * time_to_expire,
+        })
+    }
+
+    // exponent is the characteristic exponent of ln(S(T) / F) per year
+    fn exponent(&self, u: Complex) -> Complex {
+        let KouJumps {
+            intensity,
+            probability,
+            up_rate,
+            down_rate,
+        } = self.jumps;
+        let iu = Complex::I * u;
+        let diffusion = -0.5 * self.model.volatility.powi(2) * (u * u + iu);
+        let jump = probability * up_rate / (up_rate - iu)
+            + (1.0 - probability) * down_rate / (down_rate + iu)
+            - 1.0;
+        diffusion - iu * intensity * self.compensator() + intensity * jump
+    }
+
+    // compensator is the expected relative jump size E[J - 1]
+    fn compensator(&self) -> f64 {
+        let KouJumps {
+            probability,
+            up_rate,
+            down_rate,
+            ..
+        } = self.jumps;
+        probability * up_rate / (up_rate - 1.0)
+            + (1.0 - probability) * down_rate / (down_rate + 1.0)
+            - 1.0
+    }
+}
+
+impl CharacteristicFunction for KouModel {
+    fn characteristic_function(&self, u: Complex) -> Complex {
+        (self.exponent(u) * self.model.time_to_expire).exp()
+    }
+
+    fn forward(&self) -> f64 {
+        CharacteristicFunction::forward(&self.model)
+    }
+
+    fn discount(&self) -> f64 {
+        CharacteristicFunction::discount(&self.model)
+    }
+
+    fn cumulants(&self) -> (f64, f64, f64) {
+        let KouJumps {
+            intensity,
+            probability,
+            up_rate,
+            down_rate,
+        } = self.jumps;
+        // k-th moment of the log jump size
+        let moment = |k: i32, factorial: f64| {
+            factorial
+                * (probability / up_rate.powi(k) + (1.0 - probability) * (-1.0 / down_rate).powi(k))
+        };
+        let t = self.model.time_to_expire;
+        let variance = self.model.volatility.powi(2);
+        (
+            (-0.5 * variance - intensity * self.compensator() + intensity * moment(1, 1.0)) * t,
+            (variance + intensity * moment(2, 2.0)) * t,
+            intensity * moment(4, 24.0) * t,
+        )
+    }
+}
+
+// calibrate_merton fits the diffusion volatility and the jumps of Merton's model to
+// the quoted implied volatilities, starting from the initial guess
+pub fn calibrate_merton(
+    stock: f64,
+    interest_rate: f64,
+    dividend: Option<f64>,
+    quotes: &[VolQuote],
+    initial: (f64, MertonJumps),
+) -> MathResult<(f64, MertonJumps)> {
+    // positive parameters are fitted through their logarithm
+    let unpack = |x: &[f64]| {
+        let jumps = MertonJumps {
+            intensity: x[1].exp(),
+            mean: x[2],
+            volatility: x[3].exp(),
+        };
+        (x[0].exp(), jumps)
+    };
+    let (volatility, jumps) = initial;
+    let start = [
+        volatility.ln(),
+        jumps.intensity.ln(),
+        jumps.mean,
+        jumps.volatility.ln(),
+    ];
+    let x = calibrate(
+        stock,
+        interest_rate,
+        dividend,
+        quotes,
+        &start,
+        |x, contract| {
+            let (volatility, jumps) = unpack(x);
+            MertonModel::new(
+                BlackScholesModel {
+                    volatility,
+                    ..contract
+                },
+                jumps,
+            )
+            .price()
+        },
+    )?;
+    Ok(unpack(&x))
+}
+
+// calibrate_kou fits the diffusion volatility and the jumps of Kou's model to the quoted
+// implied volatilities, starting from the initial guess
+pub fn calibrate_kou(
+    stock: f64,
+    interest_rate: f64,
+    dividend: Option<f64>,
+    quotes: &[VolQuote],
+    initial: (f64, KouJumps),
+) -> MathResult<(f64, KouJumps)> {
+    // positive parameters are fitted through their logarithm, the probability through
+    // its logit and the up rate through the logarithm of its excess over 1
+    let unpack = |x: &[f64]| {
+        let jumps = KouJumps {
+            intensity: x[1].exp(),
+            probability: 1.0 / (1.0 + E.powf(-x[2])),
+            up_rate: 1.0 + x[3].exp(),
+            down_rate: x[4].exp(),
+        };
+        (x[0].exp(), jumps)
+    };
+    let (volatility, jumps) = initial;
+    let probability = jumps.probability.clamp(1e-6, 1.0 - 1e-6);
+    let start = [
+        volatility.ln(),
+        jumps.intensity.ln(),
+        (probability / (1.0 - probability)).ln(),
+        (jumps.up_rate - 1.0).ln(),
+        jumps.down_rate.ln(),
+    ];
+    let x = calibrate(
+        stock,
+        interest_rate,
+        dividend,
+        quotes,
+        &start,
+        |x, contract| {
+            let (volatility, jumps) = unpack(x);
+            KouModel::new(
+                BlackScholesModel {
+                    volatility,
+                    ..contract
+                },
+                jumps,
+            )
+            .price()
+        },
+    )?;
+    Ok(unpack(&x))
+}
+
+// calibrate runs Levenberg-Marquardt on the price differences divided by the
+// Black-Scholes vega of the quotes, like heston::calibrate, where price values the call
+// of a quote (as a Black-Scholes model at the quoted volatility) with the parameters x
+fn calibrate<P>(
+    stock: f64,
+    interest_rate: f64,
+    dividend: Option<f64>,
+    quotes: &[VolQuote],
+    start: &[f64],
+    price: P,
+) -> MathResult<Vec<f64>>
+where
+    P: Fn(&[f64], BlackScholesModel) -> MathResult,
+{
+    let markets = quotes
+        .iter()
+        .map(|q| {
+            let model = BlackScholesModel::try_new(
+                OptionKind::Call,
+                q.strike,
+                stock,
+                interest_rate,
+                q.volatility,
+                q.time_to_expire,
+                dividend,
+            )?;
+            Ok((model, model.price()?, model.vega()?.max(1e-8)))
+        })
+        .collect::<MathResult<Vec<_>>>()?;
+    let residuals = |x: &[f64]| {
+        markets
+            .iter()
+            .map(|&(contract, market, vega)| Ok((price(x, contract)? - market) / vega))
+            .collect()
+    };
+    levenberg_marquardt(residuals, start)
+}
+
+// validate applies the checks of BlackScholesModel::try_new to an existing model
+fn validate(model: &BlackScholesModel) -> MathResult<()> {
+    BlackScholesModel::try_new(
+        model.opt,
+        model.strike,
+        model.stock,
+        model.interest_rate,
+        model.volatility,
+        model.time_to_expire,
+        model.dividend,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fourier::{CarrMadan, Cos};
+    use crate::testing::{assert_close, finite_difference_greeks};
+    use crate::OptionKind;
+
+    const STRIKES: [f64; 4] = [85.0, 95.0, 105.0, 115.0];
+
+    fn merton(intensity: f64) -> MertonModel {
+        let model = BlackScholesModel::new(OptionKind::Put, 100.0, 100.0, 0.05, 0.2, 0.5, None);
+        let jumps = MertonJumps {
+            intensity,
+            mean: -0.1,
+            volatility: 0.15,
+        };
+        MertonModel::new(model, jumps)
+    }
+
+    #[test]
+    fn characteristic_function_is_a_martingale() {
+        let one = Complex::from(1.0);
+        assert!((merton(1.0).characteristic_function(-Complex::I) - one).abs() < 1e-15);
+    }
+
+    #[test]
+    fn cumulants_match_characteristic_function() {
+        struct Numerical(MertonModel);
+        impl CharacteristicFunction for Numerical {
+            fn characteristic_function(&self, u: Complex) -> Complex {
+                self.0.characteristic_function(u)
+            }
+            fn forward(&self) -> f64 {
+                self.0.forward()
+            }
+            fn discount(&self) -> f64 {
+                self.0.discount()
+            }
+        }
+        let model = merton(1.0);
+        let (c1, c2, _) = Numerical(model).cumulants();
+        let (e1, e2, _) = model.cumulants();
+        assert!((c1 - e1).abs() < 1e-6 && (c2 - e2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn no_jumps_is_black_scholes() {
+        let result = Cos::default()
+            .prices(&merton(0.0), OptionKind::Put, &STRIKES)
+            .unwrap();
+        for (result, &strike) in result.iter().zip(&STRIKES) {
+            let expected =
+                BlackScholesModel::new(OptionKind::Put, strike, 100.0, 0.05, 0.2, 0.5, None)
+                    .price()
+                    .unwrap();
+            assert!((result - expected).abs() < 1e-10);
+        }
+    }
+
+    fn kou(opt: OptionKind) -> KouModel {
+        let model = BlackScholesModel::new(opt, 98.0, 100.0, 0.05, 0.16, 0.5, Some(0.01));
+        let jumps = KouJumps {
+            intensity: 1.0,
+            probability: 0.4,
+            up_rate: 10.0,
+            down_rate: 5.0,
+        };
+        KouModel::new(model, jumps)
+    }
+
+    // assert_greeks compares the greeks with central differences of the price in the
+    // market inputs of the underlying Black-Scholes model
+    fn assert_greeks<P: Fn(&BlackScholesModel) -> f64>(
+        greeks: Greeks,
+        model: BlackScholesModel,
+        price: P,
+    ) {
+        let expected = finite_difference_greeks(&model, price);
+        assert_close(greeks.delta, expected.delta, 1e-6);
+        assert_close(greeks.gamma, expected.gamma, 1e-5);
+        assert_close(greeks.vega, expected.vega, 1e-5);
+        assert_close(greeks.theta, expected.theta, 1e-5);
+        assert_close(greeks.rho, expected.rho, 1e-5);
+        assert_close(greeks.dividend_rho, expected.dividend_rho, 1e-5);
+    }
+
+    #[test]
+    fn merton_series_matches_fourier_pricer() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let expected = Cos::default().prices(&merton(1.0), opt, &STRIKES).unwrap();
+            for (&strike, expected) in STRIKES.iter().zip(&expected) {
+                let mut model = merton(1.0);
+                model.model.opt = opt;
+                model.model.strike = strike;
+                let result = model.price().unwrap();
+                assert!(
+                    (result - expected).abs() < 1e-9,
+                    "{} is not {}",
+                    result,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn merton_greeks_match_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let mut model = merton(1.0);
+            model.model.opt = opt;
+            model.model.dividend = Some(0.02);
+            assert_greeks(model.greeks().unwrap(), model.model, |m| {
+                MertonModel::new(*m, model.jumps).price().unwrap()
+            });
+        }
+    }
+
+    #[test]
+    fn kou_matches_fourier_pricer() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = kou(opt);
+            let expected = Cos::default().prices(&model, opt, &[98.0]).unwrap()[0];
+            let result = model.price().unwrap();
+            assert!(
+                (result - expected).abs() < 1e-8,
+                "{} is not {}",
+                result,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn kou_without_jumps_is_black_scholes() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let mut model = kou(opt);
+            model.jumps.intensity = 0.0;
+            let expected = model.model.price().unwrap();
+            assert!((model.price().unwrap() - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn kou_greeks_match_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = kou(opt);
+            assert_greeks(model.greeks().unwrap(), model.model, |m| {
+                KouModel::new(*m, model.jumps).price().unwrap()
+            });
+        }
+    }
+
+    #[test]
+    fn at_expiration() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let mut model = BlackScholesModel::new(opt, 98.0, 100.0, 0.05, 0.16, 0.0, Some(0.01));
+            let expected = model.greeks().unwrap();
+            let mut merton = merton(1.0);
+            merton.model = model;
+            let mut kou = kou(opt);
+            kou.model = model;
+            for (price, greeks) in [
+                (merton.price(), merton.greeks()),
+                (kou.price(), kou.greeks()),
+            ] {
+                assert_eq!(price, model.price());
+                assert_eq!(greeks, Ok(expected));
+            }
+
+            // the kink of the payoff
+            model.strike = 100.0;
+            merton.model = model;
+            kou.model = model;
+            for greeks in [merton.greeks(), kou.greeks()] {
+                assert_eq!(greeks, Err(MathError::AtTheMoneyWithoutTimeValue(100.0)));
+            }
+        }
+    }
+
+    fn chain<P: Fn(BlackScholesModel) -> f64>(price: P) -> Vec<VolQuote> {
+        let mut quotes = Vec::new();
+        for &time_to_expire in &[0.25, 1.0] {
+            for &strike in &[80.0, 90.0, 100.0, 110.0, 120.0] {
+                let contract = BlackScholesModel::new(
+                    OptionKind::Call,
+                    strike,
+                    100.0,
+                    0.03,
+                    0.2,
+                    time_to_expire,
+                    None,
+                );
+                let volatility = crate::implied_volatility(
+                    OptionKind::Call,
+                    strike,
+                    100.0,
+                    0.03,
+                    time_to_expire,
+                    None,
+                    price(contract),
+                )
+                .unwrap();
+                quotes.push(VolQuote {
+                    strike,
+                    time_to_expire,
+                    volatility,
+                });
+            }
+        }
+        quotes
+    }
+
+    #[test]
+    fn calibration_recovers_merton_parameters() {
+        let truth = MertonJumps {
+            intensity: 0.8,
+            mean: -0.15,
+            volatility: 0.1,
+        };
+        let quotes = chain(|contract| {
+            let contract = BlackScholesModel {
+                volatility: 0.18,
+                ..contract
+            };
+            MertonModel::new(contract, truth).price().unwrap()
+        });
+        let initial = MertonJumps {
+            intensity: 0.3,
+            mean: -0.05,
+            volatility: 0.2,
+        };
+        let (volatility, jumps) =
+            calibrate_merton(100.0, 0.03, None, &quotes, (0.25, initial)).unwrap();
+        assert!((volatility - 0.18).abs() < 1e-4);
+        assert!((jumps.intensity - truth.intensity).abs() < 1e-3);
+        assert!((jumps.mean - truth.mean).abs() < 1e-4);
+        assert!((jumps.volatility - truth.volatility).abs() < 1e-4);
+    }
+
+    #[test]
+    fn calibration_recovers_kou_parameters() {
+        let truth = kou(OptionKind::Call).jumps;
+        let quotes = chain(|contract| {
+            let contract = BlackScholesModel {
+                volatility: 0.16,
+                ..contract
+            };
+            KouModel::new(contract, truth).price().unwrap()
+        });
+        let initial = KouJumps {
+            intensity: 0.5,
+            probability: 0.5,
+            up_rate: 8.0,
+            down_rate: 8.0,
+        };
+        let (volatility, jumps) =
+            calibrate_kou(100.0, 0.03, None, &quotes, (0.2, initial)).unwrap();
+        assert!((volatility - 0.16).abs() < 1e-4);
+        assert!((jumps.intensity - truth.intensity).abs() < 1e-3);
+        assert!((jumps.probability - truth.probability).abs() < 1e-3);
+        assert!((jumps.up_rate - truth.up_rate).abs() < 1e-2);
+        assert!((jumps.down_rate - truth.down_rate).abs() < 1e-2);
+    }
+
+    #[test]
+    fn err_with_invalid_jumps() {
+        let model = kou(OptionKind::Call);
+        let jumps = KouJumps {
+            up_rate: 0.5,
+            ..model.jumps
+        };
+        assert_eq!(
+            KouModel::try_new(model.model, jumps),
+            Err(MathError::ParameterOutOfBounds("up_rate", 0.5))
+        );
+        let jumps = MertonJumps {
+            intensity: -1.0,
+            ..merton(1.0).jumps
+        };
+        assert_eq!(
+            MertonModel::try_new(model.model, jumps),
+            Err(MathError::ParameterOutOfBounds("intensity", -1.0))
+        );
+    }
+
+    #[test]
+    fn fourier_pricers_agree() {
+        let model = merton(1.0);
+        let fft = CarrMadan::default()
+            .prices(&model, OptionKind::Call, &STRIKES)
+            .unwrap();
+        let cos = Cos::default()
+            .prices(&model, OptionKind::Call, &STRIKES)
+            .unwrap();
+        for (fft, cos) in fft.iter().zip(&cos) {
+            assert!((fft - cos).abs() < 1e-6, "{} is not {}", fft, cos);
+        }
+    }
+}
@@ -1,14 +1,176 @@
+use distributions::{norm_cdf, norm_pdf};
+use std::error::Error;
 use std::f64::consts::{E, PI};
+use std::fmt;
+use surface::VolSurface;
 
-#[derive(Debug)]
+pub mod american;
+pub mod asian;
+pub mod bachelier;
+pub mod barrier;
+pub mod binomial;
+pub mod black76;
+pub mod complex;
+pub mod digital;
+pub mod distributions;
+pub mod finite_difference;
+pub mod fourier;
+pub mod fx;
+pub mod heston;
+pub mod jump_diffusion;
+mod linalg;
+pub mod local_volatility;
+pub mod longstaff_schwartz;
+pub mod monte_carlo;
+mod quadrature;
+mod random;
+pub mod sabr;
+mod solver;
+pub mod strategy;
+pub mod surface;
+pub mod svi;
+#[cfg(test)]
+mod testing;
+pub mod variance_gamma;
+
+// MathError describes why a calculation was rejected, carrying the offending value
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MathError {
-    NonPositiveStrike,
-    NonPositiveStock,
-    NonPositivePremium,
+    NonPositiveStrike(f64),            // strike price is zero or negative
+    NonPositiveStock(f64),             // underlying price is zero or negative
+    NonPositivePremium(f64),           // premium is zero or negative
+    NonPositiveVolatility(f64),        // volatility is zero or negative
+    NegativeTimeToExpire(f64),         // time to expiration is negative
+    NonFiniteInput(&'static str, f64), // named input is NaN or infinite
+    PremiumOutOfBounds {
+        premium: f64, // observed premium
+        lower: f64,   // lowest arbitrage-free premium
+        upper: f64,   // highest arbitrage-free premium
+    },
+    RootNotBracketed(f64, f64), // function has the same sign at both ends
+    NoConvergence(usize),       // solver gave up after that many iterations
+    TooFewSteps(usize),         // lattice has too few time steps
+    DeltaOutOfBounds(f64),      // no strike has the requested delta
+    TooFewPaths(usize),         // simulation has too few paths for a standard error
+    TooManyDimensions(usize),   // quasi-random sequence is not tabulated that far
+    TooFewQuotes(usize),        // surface or fit has too few market quotes
+    DuplicateStrike(f64),       // expiration is quoted twice at that strike
+    AtTheMoneyWithoutTimeValue(f64), // greeks are undefined at the kink of the payoff
+    ParameterOutOfBounds(&'static str, f64), // named model parameter is outside its domain
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            MathError::NonPositiveStrike(strike) => {
+                write!(f, "strike price must be positive, got {}", strike)
+            }
+            MathError::NonPositiveStock(stock) => {
+                write!(f, "underlying price must be positive, got {}", stock)
+            }
+            MathError::NonPositivePremium(premium) => {
+                write!(f, "premium must be positive, got {}", premium)
+            }
+            MathError::NonPositiveVolatility(volatility) => {
+                write!(f, "volatility must be positive, got {}", volatility)
+            }
+            MathError::NegativeTimeToExpire(time) => {
+                write!(f, "time to expiration must not be negative, got {}", time)
+            }
+            MathError::NonFiniteInput(name, value) => {
+                write!(f, "{} must be a finite number, got {}", name, value)
+            }
+            MathError::PremiumOutOfBounds {
+                premium,
+                lower,
+                upper,
+            } => write!(
+                f,
+                "premium {} is outside of the no-arbitrage bounds ({}, {})",
+                premium, lower, upper
+            ),
+            MathError::RootNotBracketed(lower, upper) => {
+                write!(f, "no solution between {} and {}", lower, upper)
+            }
+            MathError::NoConvergence(iterations) => {
+                write!(f, "solver did not converge after {} iterations", iterations)
+            }
+            MathError::TooFewSteps(steps) => {
+                write!(f, "not enough time steps in the lattice, got {}", steps)
+            }
+            MathError::DeltaOutOfBounds(delta) => {
+                write!(f, "no strike has a delta of {}", delta)
+            }
+            MathError::TooFewPaths(paths) => {
+                write!(f, "not enough simulated paths, got {}", paths)
+            }
+            MathError::TooFewQuotes(quotes) => {
+                write!(f, "not enough market quotes, got {}", quotes)
+            }
+            MathError::DuplicateStrike(strike) => {
+                write!(f, "strike {} is quoted twice for one expiration", strike)
+            }
+            MathError::AtTheMoneyWithoutTimeValue(stock) => write!(
+                f,
+                "greeks are undefined at the money without time value, got underlying price {}",
+                stock
+            ),
+            MathError::ParameterOutOfBounds(name, value) => {
+                write!(f, "{} is out of bounds, got {}", name, value)
+            }
+            MathError::TooManyDimensions(dimensions) => write!(
+                f,
+                "sobol sequence supports at most {} dimensions, got {}",
+                random::MAX_DIMENSIONS,
+                dimensions
+            ),
+        }
+    }
+}
+
+impl Error for MathError {}
+
+pub type MathResult<T = f64> = Result<T, MathError>;
+
+// Greeks holds the first-order sensitivities of an option price
+//
+// Vega, rho and dividend rho are per unit (1.0 = 100%) change of the input,
+// theta is the change in value per year as time passes (i.e. -dV/dT)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Greeks {
+    pub delta: f64,        // dV/dS
+    pub gamma: f64,        // d2V/dS2
+    pub vega: f64,         // dV/dvolatility
+    pub theta: f64,        // -dV/dT
+    pub rho: f64,          // dV/dinterest_rate
+    pub dividend_rho: f64, // dV/ddividend
 }
 
-pub type MathResult = Result<f64, MathError>;
+// HigherOrderGreeks holds the second- and third-order sensitivities of an option price
+//
+// Time derivatives (charm, veta, color) follow the theta convention and are the
+// change per year as time passes (i.e. -d/dT)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HigherOrderGreeks {
+    pub vanna: f64,  // d2V/dSdvolatility
+    pub volga: f64,  // d2V/dvolatility2 (vomma)
+    pub charm: f64,  // -d2V/dSdT
+    pub veta: f64,   // -d2V/dvolatilitydT
+    pub speed: f64,  // d3V/dS3
+    pub zomma: f64,  // d3V/dS2dvolatility
+    pub color: f64,  // -d3V/dS2dT
+    pub ultima: f64, // d3V/dvolatility3
+}
 
+// VolQuote is an implied volatility observed in the market, the input of calibrations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolQuote {
+    pub strike: f64,         // strike price ($$$ per share)
+    pub time_to_expire: f64, // time to expiration (% of year)
+    pub volatility: f64,     // Black-Scholes implied volatility (% p.a.)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BlackScholesModel {
     opt: OptionKind,       // option type (call or put)
     strike: f64,           // strike price ($$$ per share)
@@ -39,31 +201,434 @@ impl BlackScholesModel {
             dividend,
         }
     }
+
+    // try_new creates the model like new does, but rejects inputs for which the
+    // Black-Scholes formula is undefined
+    pub fn try_new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+    ) -> MathResult<BlackScholesModel> {
+        check_finite("strike", strike)?;
+        check_finite("stock", stock)?;
+        check_finite("interest_rate", interest_rate)?;
+        check_finite("volatility", volatility)?;
+        check_finite("time_to_expire", time_to_expire)?;
+        check_finite("dividend", dividend.unwrap_or_default())?;
+        if strike <= 0.0 {
+            return Err(MathError::NonPositiveStrike(strike));
+        }
+        if stock <= 0.0 {
+            return Err(MathError::NonPositiveStock(stock));
+        }
+        if volatility <= 0.0 {
+            return Err(MathError::NonPositiveVolatility(volatility));
+        }
+        if time_to_expire < 0.0 {
+            return Err(MathError::NegativeTimeToExpire(time_to_expire));
+        }
+        Ok(BlackScholesModel::new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        ))
+    }
+
+    // from_surface creates the model with the market inputs of the surface and its
+    // implied volatility at the strike and time to expiration
+    pub fn from_surface(
+        opt: OptionKind,
+        strike: f64,
+        time_to_expire: f64,
+        surface: &VolSurface,
+    ) -> MathResult<BlackScholesModel> {
+        BlackScholesModel::try_new(
+            opt,
+            strike,
+            surface.stock(),
+            surface.interest_rate(),
+            surface.volatility(strike, time_to_expire)?,
+            time_to_expire,
+            surface.dividend(),
+        )
+    }
+
+    // price calculates the fair value of the option ($$$ per share), at expiration or
+    // without volatility that's the discounted intrinsic value of the forward
     pub fn price(&self) -> MathResult {
+        if self.volatility * self.time_to_expire.sqrt() == 0.0 {
+            let pv_stock = self.stock * self.dividend_discount();
+            let pv_strike = self.strike * self.discount();
+            return match self.opt {
+                OptionKind::Call => Ok((pv_stock - pv_strike).max(0.0)),
+                OptionKind::Put => Ok((pv_strike - pv_stock).max(0.0)),
+            };
+        }
+        let (d1, d2) = self.d1_d2();
+
+        match self.opt {
+            OptionKind::Call => Ok(self.stock * self.dividend_discount() * norm_cdf(d1)
+                - self.strike * self.discount() * norm_cdf(d2)),
+            OptionKind::Put => Ok(self.strike * self.discount() * norm_cdf(-d2)
+                - self.stock * self.dividend_discount() * norm_cdf(-d1)),
+        }
+    }
+
+    // delta calculates the rate of change of the option price with respect to the
+    // underlying price
+    pub fn delta(&self) -> MathResult {
+        if let Some(intrinsic) = self.without_time_value()? {
+            return Ok(intrinsic * self.dividend_discount());
+        }
+        let (d1, _) = self.d1_d2();
+        match self.opt {
+            OptionKind::Call => Ok(self.dividend_discount() * norm_cdf(d1)),
+            OptionKind::Put => Ok(-self.dividend_discount() * norm_cdf(-d1)),
+        }
+    }
+
+    // gamma calculates the rate of change of delta with respect to the underlying price,
+    // it's the same for calls and puts
+    pub fn gamma(&self) -> MathResult {
+        if self.without_time_value()?.is_some() {
+            return Ok(0.0);
+        }
+        let (d1, _) = self.d1_d2();
+        Ok(self.dividend_discount() * norm_pdf(d1)
+            / (self.stock * self.volatility * self.time_to_expire.sqrt()))
+    }
+
+    // vega calculates the rate of change of the option price with respect to the
+    // volatility, it's the same for calls and puts
+    pub fn vega(&self) -> MathResult {
+        if self.without_time_value()?.is_some() {
+            return Ok(0.0);
+        }
+        let (d1, _) = self.d1_d2();
+        Ok(self.stock * self.dividend_discount() * norm_pdf(d1) * self.time_to_expire.sqrt())
+    }
+
+    // theta calculates the time decay of the option price per year
+    pub fn theta(&self) -> MathResult {
+        if let Some(intrinsic) = self.without_time_value()? {
+            return Ok(intrinsic
+                * (self.dividend.unwrap_or_default() * self.stock * self.dividend_discount()
+                    - self.interest_rate * self.strike * self.discount()));
+        }
         let dividend = self.dividend.unwrap_or_default();
+        let (d1, d2) = self.d1_d2();
+        let decay = -self.stock * self.dividend_discount() * norm_pdf(d1) * self.volatility
+            / (2.0 * self.time_to_expire.sqrt());
+
+        match self.opt {
+            OptionKind::Call => Ok(decay
+                - self.interest_rate * self.strike * self.discount() * norm_cdf(d2)
+                + dividend * self.stock * self.dividend_discount() * norm_cdf(d1)),
+            OptionKind::Put => Ok(decay
+                + self.interest_rate * self.strike * self.discount() * norm_cdf(-d2)
+                - dividend * self.stock * self.dividend_discount() * norm_cdf(-d1)),
+        }
+    }
 
-        let d1 = (self.stock / self.strike).ln() + self.interest_rate - dividend
-            + self.volatility.powi(2) / 2.0 * self.time_to_expire / self.volatility
-                * self.time_to_expire.sqrt();
-        let d2 = d1 - self.volatility * self.time_to_expire.sqrt();
+    // rho calculates the rate of change of the option price with respect to the
+    // risk-free interest rate
+    pub fn rho(&self) -> MathResult {
+        if let Some(intrinsic) = self.without_time_value()? {
+            return Ok(intrinsic * self.strike * self.time_to_expire * self.discount());
+        }
+        let (_, d2) = self.d1_d2();
+        let pv_strike = self.strike * self.time_to_expire * self.discount();
+        match self.opt {
+            OptionKind::Call => Ok(pv_strike * norm_cdf(d2)),
+            OptionKind::Put => Ok(-pv_strike * norm_cdf(-d2)),
+        }
+    }
 
+    // dividend_rho calculates the rate of change of the option price with respect to the
+    // continuously compounded dividend yield
+    pub fn dividend_rho(&self) -> MathResult {
+        if let Some(intrinsic) = self.without_time_value()? {
+            return Ok(-intrinsic * self.stock * self.time_to_expire * self.dividend_discount());
+        }
+        let (d1, _) = self.d1_d2();
+        let pv_stock = self.stock * self.time_to_expire * self.dividend_discount();
         match self.opt {
-            OptionKind::Call => Ok(self.stock
-                * E.powf(-dividend * self.time_to_expire)
-                * norm_dist(d1)
-                - self.strike * E.powf(-self.interest_rate * self.time_to_expire) * norm_dist(d2)),
-            OptionKind::Put => Ok(self.strike
-                * E.powf(-self.interest_rate * self.time_to_expire)
-                * norm_dist(-d2)
-                - self.stock * E.powf(-dividend * self.time_to_expire) * norm_dist(-d1)),
+            OptionKind::Call => Ok(-pv_stock * norm_cdf(d1)),
+            OptionKind::Put => Ok(pv_stock * norm_cdf(-d1)),
         }
     }
+
+    // greeks calculates all first-order sensitivities of the option price at once
+    pub fn greeks(&self) -> MathResult<Greeks> {
+        Ok(Greeks {
+            delta: self.delta()?,
+            gamma: self.gamma()?,
+            vega: self.vega()?,
+            theta: self.theta()?,
+            rho: self.rho()?,
+            dividend_rho: self.dividend_rho()?,
+        })
+    }
+
+    // vanna calculates the rate of change of delta with respect to the volatility,
+    // it's the same for calls and puts
+    pub fn vanna(&self) -> MathResult {
+        if self.without_time_value()?.is_some() {
+            return Ok(0.0);
+        }
+        let (d1, d2) = self.d1_d2();
+        Ok(-self.dividend_discount() * norm_pdf(d1) * d2 / self.volatility)
+    }
+
+    // volga (vomma) calculates the rate of change of vega with respect to the volatility,
+    // it's the same for calls and puts
+    pub fn volga(&self) -> MathResult {
+        if self.without_time_value()?.is_some() {
+            return Ok(0.0);
+        }
+        let (d1, d2) = self.d1_d2();
+        Ok(self.vega()? * d1 * d2 / self.volatility)
+    }
+
+    // charm calculates the decay of delta per year
+    pub fn charm(&self) -> MathResult {
+        if let Some(intrinsic) = self.without_time_value()? {
+            return Ok(intrinsic * self.dividend.unwrap_or_default() * self.dividend_discount());
+        }
+        let dividend = self.dividend.unwrap_or_default();
+        let carry = self.interest_rate - dividend;
+        let (d1, d2) = self.d1_d2();
+        let call_charm = -self.dividend_discount()
+            * (norm_pdf(d1)
+                * (carry / (self.volatility * self.time_to_expire.sqrt())
+                    - d2 / (2.0 * self.time_to_expire))
+                - dividend * norm_cdf(d1));
+
+        match self.opt {
+            OptionKind::Call => Ok(call_charm),
+            OptionKind::Put => Ok(call_charm - dividend * self.dividend_discount()),
+        }
+    }
+
+    // veta calculates the decay of vega per year, it's the same for calls and puts
+    pub fn veta(&self) -> MathResult {
+        if self.without_time_value()?.is_some() {
+            return Ok(0.0);
+        }
+        let dividend = self.dividend.unwrap_or_default();
+        let carry = self.interest_rate - dividend;
+        let (d1, d2) = self.d1_d2();
+        Ok(self.vega()?
+            * (dividend + carry * d1 / (self.volatility * self.time_to_expire.sqrt())
+                - (1.0 + d1 * d2) / (2.0 * self.time_to_expire)))
+    }
+
+    // speed calculates the rate of change of gamma with respect to the underlying price,
+    // it's the same for calls and puts
+    pub fn speed(&self) -> MathResult {
+        if self.without_time_value()?.is_some() {
+            return Ok(0.0);
+        }
+        let (d1, _) = self.d1_d2();
+        Ok(-self.gamma()? / self.stock
+            * (1.0 + d1 / (self.volatility * self.time_to_expire.sqrt())))
+    }
+
+    // zomma calculates the rate of change of gamma with respect to the volatility,
+    // it's the same for calls and puts
+    pub fn zomma(&self) -> MathResult {
+        if self.without_time_value()?.is_some() {
+            return Ok(0.0);
+        }
+        let (d1, d2) = self.d1_d2();
+        Ok(self.gamma()? * (d1 * d2 - 1.0) / self.volatility)
+    }
+
+    // color calculates the decay of gamma per year, it's the same for calls and puts
+    pub fn color(&self) -> MathResult {
+        if self.without_time_value()?.is_some() {
+            return Ok(0.0);
+        }
+        let dividend = self.dividend.unwrap_or_default();
+        let carry = self.interest_rate - dividend;
+        let (d1, d2) = self.d1_d2();
+        Ok(self.gamma()?
+            * (dividend
+                + carry * d1 / (self.volatility * self.time_to_expire.sqrt())
+                + (1.0 - d1 * d2) / (2.0 * self.time_to_expire)))
+    }
+
+    // ultima calculates the rate of change of volga with respect to the volatility,
+    // it's the same for calls and puts
+    pub fn ultima(&self) -> MathResult {
+        if self.without_time_value()?.is_some() {
+            return Ok(0.0);
+        }
+        let (d1, d2) = self.d1_d2();
+        Ok(-self.vega()? / self.volatility.powi(2)
+            * (d1 * d2 * (1.0 - d1 * d2) + d1.powi(2) + d2.powi(2)))
+    }
+
+    // higher_order_greeks calculates all second- and third-order sensitivities at once
+    pub fn higher_order_greeks(&self) -> MathResult<HigherOrderGreeks> {
+        Ok(HigherOrderGreeks {
+            vanna: self.vanna()?,
+            volga: self.volga()?,
+            charm: self.charm()?,
+            veta: self.veta()?,
+            speed: self.speed()?,
+            zomma: self.zomma()?,
+            color: self.color()?,
+            ultima: self.ultima()?,
+        })
+    }
+
+    // without_time_value returns the number of forwards the option holds when there's
+    // no time value left, at expiration or without volatility, which is 1 (calls) or -1
+    // (puts) in the money and 0 out of it, so that the greeks are those of the forward
+    //
+    // At the money the payoff has a kink and gamma is infinite, that's an error
+    fn without_time_value(&self) -> MathResult<Option<f64>> {
+        if self.volatility * self.time_to_expire.sqrt() != 0.0 {
+            return Ok(None);
+        }
+        let forward_value = self.stock * self.dividend_discount() - self.strike * self.discount();
+        if forward_value == 0.0 {
+            return Err(MathError::AtTheMoneyWithoutTimeValue(self.stock));
+        }
+        let direction = match self.opt {
+            OptionKind::Call => 1.0,
+            OptionKind::Put => -1.0,
+        };
+        Ok(Some(if direction * forward_value > 0.0 {
+            direction
+        } else {
+            0.0
+        }))
+    }
+
+    fn d1_d2(&self) -> (f64, f64) {
+        let dividend = self.dividend.unwrap_or_default();
+        let vol_sqrt_time = self.volatility * self.time_to_expire.sqrt();
+
+        let d1 = ((self.stock / self.strike).ln()
+            + (self.interest_rate - dividend + self.volatility.powi(2) / 2.0)
+                * self.time_to_expire)
+            / vol_sqrt_time;
+        (d1, d1 - vol_sqrt_time)
+    }
+
+    fn discount(&self) -> f64 {
+        E.powf(-self.interest_rate * self.time_to_expire)
+    }
+
+    fn dividend_discount(&self) -> f64 {
+        E.powf(-self.dividend.unwrap_or_default() * self.time_to_expire)
+    }
+}
+
+// implied_volatility calculates the volatility at which BlackScholesModel::price matches
+// the observed premium ($$$ per share)
+//
+// The premium has to be strictly within the no-arbitrage bounds, i.e. above the discounted
+// forward intrinsic value and below the discounted stock (call) or strike (put) price
+pub fn implied_volatility(
+    opt: OptionKind,
+    strike: f64,
+    stock: f64,
+    interest_rate: f64,
+    time_to_expire: f64,
+    dividend: Option<f64>,
+    premium: f64,
+) -> MathResult {
+    check_finite("strike", strike)?;
+    check_finite("stock", stock)?;
+    check_finite("interest_rate", interest_rate)?;
+    check_finite("time_to_expire", time_to_expire)?;
+    check_finite("dividend", dividend.unwrap_or_default())?;
+    check_finite("premium", premium)?;
+    if strike <= 0.0 {
+        return Err(MathError::NonPositiveStrike(strike));
+    }
+    if stock <= 0.0 {
+        return Err(MathError::NonPositiveStock(stock));
+    }
+    if time_to_expire < 0.0 {
+        return Err(MathError::NegativeTimeToExpire(time_to_expire));
+    }
+    if premium <= 0.0 {
+        return Err(MathError::NonPositivePremium(premium));
+    }
+
+    let model = |volatility| {
+        BlackScholesModel::new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        )
+    };
+    let pv_stock = stock * E.powf(-dividend.unwrap_or_default() * time_to_expire);
+    let pv_strike = strike * E.powf(-interest_rate * time_to_expire);
+    let (lower, upper) = match opt {
+        OptionKind::Call => ((pv_stock - pv_strike).max(0.0), pv_stock),
+        OptionKind::Put => ((pv_strike - pv_stock).max(0.0), pv_strike),
+    };
+    // at expiration the only arbitrage-free premium is the intrinsic value
+    let upper = if time_to_expire == 0.0 { lower } else { upper };
+    if premium <= lower || premium >= upper {
+        return Err(MathError::PremiumOutOfBounds {
+            premium,
+            lower,
+            upper,
+        });
+    }
+
+    // Manaster-Koehler starting point, which is the inflection point of the price in
+    // volatility, falling back to Brenner-Subrahmanyam for options close to the money
+    let guess = (2.0 * (pv_stock / pv_strike).ln().abs() / time_to_expire)
+        .sqrt()
+        .max(premium / pv_stock * (2.0 * PI / time_to_expire).sqrt());
+
+    let mut upper = 1.0;
+    while model(upper).price()? < premium {
+        upper *= 2.0;
+        if upper > MAX_VOLATILITY {
+            return Err(MathError::RootNotBracketed(MIN_VOLATILITY, MAX_VOLATILITY));
+        }
+    }
+
+    solver::newton_bisection(
+        |volatility| {
+            let model = model(volatility);
+            Ok((model.price()? - premium, model.vega()?))
+        },
+        MIN_VOLATILITY,
+        upper,
+        guess,
+    )
 }
 
+const MIN_VOLATILITY: f64 = 1e-8;
+const MAX_VOLATILITY: f64 = 1e3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum OptionKind {
     Call,
     Put,
 }
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Position {
     Long,
     Short,
@@ -75,12 +640,14 @@ pub enum Position {
 // It takes strike price and premium in $$$ per share and returns break-even point
 // regarding to the option type (opt)
 pub fn break_even_point(opt: OptionKind, strike: f64, premium: Option<f64>) -> MathResult {
+    check_finite("strike", strike)?;
     if strike < 0.0 {
-        return Err(MathError::NonPositiveStrike);
+        return Err(MathError::NonPositiveStrike(strike));
     }
     let premium = premium.unwrap_or_default();
+    check_finite("premium", premium)?;
     if premium < 0.0 {
-        return Err(MathError::NonPositivePremium);
+        return Err(MathError::NonPositivePremium(premium));
     }
     match opt {
         OptionKind::Call => Ok(strike + premium),
@@ -99,15 +666,18 @@ pub fn payoff(
     stock: f64,
     premium: Option<f64>,
 ) -> MathResult {
+    check_finite("strike", strike)?;
+    check_finite("stock", stock)?;
     if strike < 0.0 {
-        return Err(MathError::NonPositiveStrike);
+        return Err(MathError::NonPositiveStrike(strike));
     }
     if stock < 0.0 {
-        return Err(MathError::NonPositiveStock);
+        return Err(MathError::NonPositiveStock(stock));
     }
     let premium = premium.unwrap_or_default();
+    check_finite("premium", premium)?;
     if premium < 0.0 {
-        return Err(MathError::NonPositivePremium);
+        return Err(MathError::NonPositivePremium(premium));
     }
 
     match pos {
@@ -122,20 +692,19 @@ pub fn payoff(
     }
 }
 
-fn norm_dist(z: f64) -> f64 {
-    let t = 1.0 / (1.0 + 0.2316419 * z.abs());
-    let t2 = t.powi(2);
-    let y = t
-        * (0.319381530 - 0.356563782 * t + (1.781477937 - 1.821255978 * t + 1.330274429 * t2) * t2);
-
-    if z > 0.0 {
-        return 1.0 - (-((2.0 * PI).ln() + z.powi(2)) * 0.5).exp() * y;
+// check_finite rejects NaN and infinite values of the named input
+fn check_finite(name: &'static str, value: f64) -> MathResult<()> {
+    if value.is_finite() {
+        Ok(())
+    } else {
+        Err(MathError::NonFiniteInput(name, value))
     }
-    return (-((2.0 * PI).ln() + -z.powi(2)) * 0.5).exp() * y;
 }
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{assert_relative, central_diff};
 
     #[test]
     fn err_with_negative_strike() {
@@ -174,37 +743,487 @@ mod tests {
     }
 
     #[test]
-    fn positive_norm_dist() {
-        let result = norm_dist(0.39);
-        assert_eq!(result, 0.6517316779654632);
+    fn call_price() {
+        let bsm =
+            BlackScholesModel::new(OptionKind::Call, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125));
+        let result = bsm.price().unwrap();
+
+        assert_eq!(result, 4.769028980407889);
+    }
+    #[test]
+    fn put_price() {
+        let bsm =
+            BlackScholesModel::new(OptionKind::Put, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125));
+        let result = bsm.price().unwrap();
+
+        assert_eq!(result, 2.136689211578446);
+    }
+
+    fn bsm(opt: OptionKind) -> BlackScholesModel {
+        BlackScholesModel::new(opt, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125))
+    }
+
+    fn price_of(model: &BlackScholesModel) -> f64 {
+        model.price().unwrap()
+    }
+
+    fn bump_stock(model: &mut BlackScholesModel, h: f64) {
+        model.stock += h;
+    }
+
+    fn bump_volatility(model: &mut BlackScholesModel, h: f64) {
+        model.volatility += h;
+    }
+
+    fn bump_time(model: &mut BlackScholesModel, h: f64) {
+        model.time_to_expire += h;
     }
 
     #[test]
-    fn zero_norm_dist() {
-        let result = norm_dist(0.0);
-        assert_eq!(result, 0.49999999947519136);
+    fn delta_matches_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = central_diff(&model, 1e-3, bump_stock, price_of);
+            assert_relative(model.delta().unwrap(), expected, 1e-7);
+        }
     }
 
     #[test]
-    fn negative_norm_dist() {
-        let result = norm_dist(-0.39);
-        assert_eq!(result, 0.4054806781620218);
+    fn gamma_matches_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let h = 1e-2;
+            let mut up = model;
+            up.stock += h;
+            let mut down = model;
+            down.stock -= h;
+            let expected = (up.price().unwrap() - 2.0 * model.price().unwrap()
+                + down.price().unwrap())
+                / h.powi(2);
+            assert_relative(model.gamma().unwrap(), expected, 1e-7);
+        }
     }
 
     #[test]
-    fn call_price() {
-        let bsm =
-            BlackScholesModel::new(OptionKind::Call, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125));
-        let result = bsm.price().unwrap();
+    fn vega_matches_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = central_diff(&model, 1e-4, bump_volatility, price_of);
+            assert_relative(model.vega().unwrap(), expected, 1e-7);
+        }
+    }
 
-        assert_eq!(result, 4.556957304081674);
+    #[test]
+    fn theta_matches_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = -central_diff(&model, 1e-4, bump_time, price_of);
+            assert_relative(model.theta().unwrap(), expected, 1e-7);
+        }
     }
+
     #[test]
-    fn put_price() {
-        let bsm =
-            BlackScholesModel::new(OptionKind::Put, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125));
-        let result = bsm.price().unwrap();
+    fn rho_matches_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = central_diff(&model, 1e-4, |m, h| m.interest_rate += h, price_of);
+            assert_relative(model.rho().unwrap(), expected, 1e-7);
+        }
+    }
+
+    #[test]
+    fn dividend_rho_matches_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = central_diff(
+                &model,
+                1e-4,
+                |m, h| m.dividend = Some(m.dividend.unwrap_or_default() + h),
+                price_of,
+            );
+            assert_relative(model.dividend_rho().unwrap(), expected, 1e-7);
+        }
+    }
+
+    #[test]
+    fn call_greeks() {
+        // reference values are calculated with 40 significant digits
+        let greeks = bsm(OptionKind::Call).greeks().unwrap();
+
+        assert_relative(greeks.delta, 0.6476638903377431, 1e-6);
+        assert_relative(greeks.gamma, 0.0433016761734983, 1e-6);
+        assert_relative(greeks.vega, 15.588603422459388, 1e-6);
+        assert_relative(greeks.theta, -3.825150922133555, 1e-6);
+        assert_relative(greeks.rho, 17.04540221992835, 1e-6);
+        assert_relative(greeks.dividend_rho, -19.429916710132292, 1e-6);
+    }
+
+    #[test]
+    fn greeks_collects_all_sensitivities() {
+        let model = bsm(OptionKind::Put);
+        let greeks = model.greeks().unwrap();
+
+        assert_eq!(greeks.delta, model.delta().unwrap());
+        assert_eq!(greeks.gamma, model.gamma().unwrap());
+        assert_eq!(greeks.vega, model.vega().unwrap());
+        assert_eq!(greeks.theta, model.theta().unwrap());
+        assert_eq!(greeks.rho, model.rho().unwrap());
+        assert_eq!(greeks.dividend_rho, model.dividend_rho().unwrap());
+    }
+
+    #[test]
+    fn vanna_matches_nested_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = central_diff(&model, 1e-3, bump_volatility, |m| {
+                central_diff(m, 1e-2, bump_stock, price_of)
+            });
+            assert_relative(model.vanna().unwrap(), expected, 1e-3);
+        }
+    }
+
+    #[test]
+    fn volga_matches_nested_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = central_diff(&model, 1e-3, bump_volatility, |m| {
+                central_diff(m, 1e-3, bump_volatility, price_of)
+            });
+            assert_relative(model.volga().unwrap(), expected, 1e-3);
+        }
+    }
+
+    #[test]
+    fn charm_matches_nested_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = -central_diff(&model, 1e-3, bump_time, |m| {
+                central_diff(m, 1e-2, bump_stock, price_of)
+            });
+            assert_relative(model.charm().unwrap(), expected, 1e-3);
+        }
+    }
+
+    #[test]
+    fn veta_matches_nested_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = -central_diff(&model, 1e-3, bump_time, |m| {
+                central_diff(m, 1e-3, bump_volatility, price_of)
+            });
+            assert_relative(model.veta().unwrap(), expected, 1e-3);
+        }
+    }
+
+    #[test]
+    fn speed_matches_nested_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = central_diff(&model, 1e-1, bump_stock, |m| {
+                central_diff(m, 1e-1, bump_stock, |m| {
+                    central_diff(m, 1e-1, bump_stock, price_of)
+                })
+            });
+            assert_relative(model.speed().unwrap(), expected, 1e-3);
+        }
+    }
+
+    #[test]
+    fn zomma_matches_nested_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = central_diff(&model, 1e-3, bump_volatility, |m| {
+                central_diff(m, 1e-1, bump_stock, |m| {
+                    central_diff(m, 1e-1, bump_stock, price_of)
+                })
+            });
+            assert_relative(model.zomma().unwrap(), expected, 1e-3);
+        }
+    }
+
+    #[test]
+    fn color_matches_nested_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = -central_diff(&model, 1e-3, bump_time, |m| {
+                central_diff(m, 1e-1, bump_stock, |m| {
+                    central_diff(m, 1e-1, bump_stock, price_of)
+                })
+            });
+            assert_relative(model.color().unwrap(), expected, 1e-3);
+        }
+    }
+
+    #[test]
+    fn ultima_matches_nested_finite_difference() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model = bsm(opt);
+            let expected = central_diff(&model, 1e-3, bump_volatility, |m| {
+                central_diff(m, 1e-3, bump_volatility, |m| {
+                    central_diff(m, 1e-3, bump_volatility, price_of)
+                })
+            });
+            assert_relative(model.ultima().unwrap(), expected, 1e-3);
+        }
+    }
+
+    #[test]
+    fn higher_order_greeks_collects_all_sensitivities() {
+        let model = bsm(OptionKind::Call);
+        let greeks = model.higher_order_greeks().unwrap();
+
+        assert_eq!(greeks.vanna, model.vanna().unwrap());
+        assert_eq!(greeks.volga, model.volga().unwrap());
+        assert_eq!(greeks.charm, model.charm().unwrap());
+        assert_eq!(greeks.veta, model.veta().unwrap());
+        assert_eq!(greeks.speed, model.speed().unwrap());
+        assert_eq!(greeks.zomma, model.zomma().unwrap());
+        assert_eq!(greeks.color, model.color().unwrap());
+        assert_eq!(greeks.ultima, model.ultima().unwrap());
+    }
+
+    #[test]
+    fn implied_volatility_recovers_model_volatility() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            for volatility in [0.05, 0.2, 0.75, 2.0] {
+                let premium =
+                    BlackScholesModel::new(opt, 58.0, 60.0, 0.035, volatility, 0.5, Some(0.0125))
+                        .price()
+                        .unwrap();
+                let result =
+                    implied_volatility(opt, 58.0, 60.0, 0.035, 0.5, Some(0.0125), premium).unwrap();
+                assert_relative(result, volatility, 1e-8);
+            }
+        }
+    }
+
+    #[test]
+    fn implied_volatility_deep_out_of_the_money() {
+        let premium = BlackScholesModel::new(OptionKind::Call, 120.0, 60.0, 0.035, 0.3, 0.25, None)
+            .price()
+            .unwrap();
+        let result =
+            implied_volatility(OptionKind::Call, 120.0, 60.0, 0.035, 0.25, None, premium).unwrap();
+        assert_relative(result, 0.3, 1e-6);
+    }
+
+    #[test]
+    fn err_implied_volatility_below_intrinsic() {
+        let result = implied_volatility(OptionKind::Put, 70.0, 60.0, 0.035, 0.5, None, 8.0);
+        assert!(matches!(result, Err(MathError::PremiumOutOfBounds { .. })));
+    }
+
+    #[test]
+    fn err_implied_volatility_above_upper_bound() {
+        let result = implied_volatility(OptionKind::Call, 58.0, 60.0, 0.035, 0.5, None, 60.0);
+        assert!(matches!(result, Err(MathError::PremiumOutOfBounds { .. })));
+    }
 
-        assert_eq!(result, 1.758568520665552);
+    #[test]
+    fn err_carries_offending_value() {
+        let result = payoff(Position::Long, OptionKind::Put, 50.0, -1.5, None);
+        assert_eq!(result, Err(MathError::NonPositiveStock(-1.5)));
+    }
+
+    #[test]
+    fn err_with_non_finite_input() {
+        let result = break_even_point(OptionKind::Call, f64::INFINITY, None);
+        assert_eq!(
+            result,
+            Err(MathError::NonFiniteInput("strike", f64::INFINITY))
+        );
+    }
+
+    #[test]
+    fn err_implied_volatility_at_expiration() {
+        let result = implied_volatility(OptionKind::Call, 58.0, 60.0, 0.035, 0.0, None, 2.5);
+        assert_eq!(
+            result,
+            Err(MathError::PremiumOutOfBounds {
+                premium: 2.5,
+                lower: 2.0,
+                upper: 2.0
+            })
+        );
+    }
+
+    #[test]
+    fn err_displays_context() {
+        let err = MathError::PremiumOutOfBounds {
+            premium: 60.0,
+            lower: 2.5,
+            upper: 59.5,
+        };
+        assert_eq!(
+            err.to_string(),
+            "premium 60 is outside of the no-arbitrage bounds (2.5, 59.5)"
+        );
+        assert_eq!(
+            MathError::NonPositiveStrike(-50.0).to_string(),
+            "strike price must be positive, got -50"
+        );
+    }
+
+    #[test]
+    fn err_converts_to_boxed_error() {
+        fn price() -> Result<f64, Box<dyn Error>> {
+            Ok(break_even_point(OptionKind::Put, -50.0, None)?)
+        }
+        assert!(price().is_err());
+    }
+
+    #[test]
+    fn try_new_accepts_valid_inputs() {
+        let model =
+            BlackScholesModel::try_new(OptionKind::Call, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125))
+                .unwrap();
+        assert_eq!(model, bsm(OptionKind::Call));
+        assert!(
+            BlackScholesModel::try_new(OptionKind::Put, 58.0, 60.0, -0.01, 0.2, 0.0, None).is_ok()
+        );
+    }
+
+    #[test]
+    fn try_new_rejects_invalid_inputs() {
+        let try_new = |strike, stock, volatility, time_to_expire, dividend| {
+            BlackScholesModel::try_new(
+                OptionKind::Call,
+                strike,
+                stock,
+                0.035,
+                volatility,
+                time_to_expire,
+                dividend,
+            )
+        };
+
+        assert_eq!(
+            try_new(0.0, 60.0, 0.2, 0.5, None),
+            Err(MathError::NonPositiveStrike(0.0))
+        );
+        assert_eq!(
+            try_new(58.0, -60.0, 0.2, 0.5, None),
+            Err(MathError::NonPositiveStock(-60.0))
+        );
+        assert_eq!(
+            try_new(58.0, 60.0, 0.0, 0.5, None),
+            Err(MathError::NonPositiveVolatility(0.0))
+        );
+        assert_eq!(
+            try_new(58.0, 60.0, 0.2, -0.5, None),
+            Err(MathError::NegativeTimeToExpire(-0.5))
+        );
+        assert_eq!(
+            try_new(58.0, 60.0, f64::INFINITY, 0.5, None),
+            Err(MathError::NonFiniteInput("volatility", f64::INFINITY))
+        );
+        assert!(matches!(
+            try_new(58.0, 60.0, 0.2, 0.5, Some(f64::NAN)),
+            Err(MathError::NonFiniteInput("dividend", _))
+        ));
+    }
+
+    #[test]
+    fn price_at_expiration_is_intrinsic() {
+        let call = BlackScholesModel::new(OptionKind::Call, 58.0, 60.0, 0.035, 0.2, 0.0, None);
+        let put = BlackScholesModel::new(OptionKind::Put, 58.0, 60.0, 0.035, 0.2, 0.0, None);
+
+        assert_eq!(call.price().unwrap(), 2.0);
+        assert_eq!(put.price().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn price_without_volatility_is_discounted_forward_intrinsic() {
+        let call =
+            BlackScholesModel::new(OptionKind::Call, 58.0, 60.0, 0.035, 0.0, 0.5, Some(0.0125));
+        let put =
+            BlackScholesModel::new(OptionKind::Put, 62.0, 60.0, 0.035, 0.0, 0.5, Some(0.0125));
+
+        let pv_stock = 60.0 * E.powf(-0.0125 * 0.5);
+        assert_relative(
+            call.price().unwrap(),
+            pv_stock - 58.0 * E.powf(-0.035 * 0.5),
+            1e-15,
+        );
+        assert_relative(
+            put.price().unwrap(),
+            62.0 * E.powf(-0.035 * 0.5) - pv_stock,
+            1e-15,
+        );
+        // and it's the limit of vanishing volatility
+        let mut almost = call;
+        almost.volatility = 1e-6;
+        assert_relative(almost.price().unwrap(), call.price().unwrap(), 1e-12);
+    }
+
+    #[test]
+    fn greeks_at_expiration_are_those_of_intrinsic_value() {
+        let call = BlackScholesModel::try_new(OptionKind::Call, 100.0, 110.0, 0.05, 0.2, 0.0, None)
+            .unwrap();
+        let greeks = call.greeks().unwrap();
+        assert_eq!(
+            greeks,
+            Greeks {
+                delta: 1.0,
+                gamma: 0.0,
+                vega: 0.0,
+                theta: -0.05 * 100.0,
+                rho: 0.0,
+                dividend_rho: 0.0,
+            }
+        );
+        let higher = call.higher_order_greeks().unwrap();
+        assert_eq!(higher.vanna, 0.0);
+        assert_eq!(higher.charm, 0.0);
+        assert_eq!(higher.ultima, 0.0);
+
+        let put = BlackScholesModel {
+            opt: OptionKind::Put,
+            ..call
+        };
+        let greeks = put.greeks().unwrap();
+        assert_eq!((greeks.delta, greeks.theta), (0.0, 0.0));
+
+        let at_the_money = BlackScholesModel {
+            stock: 100.0,
+            ..call
+        };
+        assert_eq!(
+            at_the_money.greeks(),
+            Err(MathError::AtTheMoneyWithoutTimeValue(100.0))
+        );
+        assert_eq!(
+            at_the_money.higher_order_greeks(),
+            Err(MathError::AtTheMoneyWithoutTimeValue(100.0))
+        );
+    }
+
+    #[test]
+    fn greeks_without_volatility_are_the_limit() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            for strike in [55.0, 65.0] {
+                let model =
+                    BlackScholesModel::new(opt, strike, 60.0, 0.035, 0.0, 0.5, Some(0.0125));
+                let almost = BlackScholesModel {
+                    volatility: 1e-6,
+                    ..model
+                };
+                let (result, expected) = (model.greeks().unwrap(), almost.greeks().unwrap());
+                assert_relative(result.delta, expected.delta, 1e-12);
+                assert_relative(result.gamma, expected.gamma, 1e-12);
+                assert_relative(result.vega, expected.vega, 1e-12);
+                assert_relative(result.theta, expected.theta, 1e-12);
+                assert_relative(result.rho, expected.rho, 1e-12);
+                assert_relative(result.dividend_rho, expected.dividend_rho, 1e-12);
+
+                let (result, expected) = (
+                    model.higher_order_greeks().unwrap(),
+                    almost.higher_order_greeks().unwrap(),
+                );
+                assert_relative(result.charm, expected.charm, 1e-12);
+                assert_relative(result.vanna, expected.vanna, 1e-12);
+                assert_relative(result.color, expected.color, 1e-12);
+            }
+        }
     }
 }
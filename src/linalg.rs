@@ -0,0 +1,82 @@
+// solve solves the square system a x = b by Gaussian elimination with partial
+// pivoting, None when the matrix is singular
+pub(crate) fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for column in 0..n {
+        let pivot = (column..n).fold(column, |best, row| {
+            if a[row][column].abs() > a[best][column].abs() {
+                row
+            } else {
+                best
+            }
+        });
+        if a[pivot][column].abs() <= f64::EPSILON * a[pivot].iter().map(|v| v.abs()).sum::<f64>() {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let (top, bottom) = a.split_at_mut(column + 1);
+        let pivot_row = &top[column];
+        for (offset, row) in bottom.iter_mut().enumerate() {
+            let factor = row[column] / pivot_row[column];
+            for (value, pivot_value) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor * pivot_value;
+            }
+            b[column + 1 + offset] -= factor * b[column];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let tail: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - tail) / a[row][row];
+    }
+    Some(x)
+}
+
+// least_squares fits the coefficients minimising the squared residuals of
+// rows * coefficients = targets through the normal equations
+pub(crate) fn least_squares(rows: &[Vec<f64>], targets: &[f64]) -> Option<Vec<f64>> {
+    let n = rows.first()?.len();
+    let mut normal = vec![vec![0.0; n]; n];
+    let mut rhs = vec![0.0; n];
+    for (row, target) in rows.iter().zip(targets) {
+        for i in 0..n {
+            rhs[i] += row[i] * target;
+            for j in 0..n {
+                normal[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    solve(normal, rhs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solves_with_pivoting() {
+        let a = vec![
+            vec![0.0, 2.0, 1.0],
+            vec![1.0, 1.0, 1.0],
+            vec![2.0, 1.0, 3.0],
+        ];
+        let x = solve(a, vec![7.0, 6.0, 13.0]).unwrap();
+        for (result, expected) in x.iter().zip([1.0, 2.0, 3.0].iter()) {
+            assert!((result - expected).abs() < 1e-12);
+        }
+        assert_eq!(
+            solve(vec![vec![1.0, 2.0], vec![2.0, 4.0]], vec![1.0, 2.0]),
+            None
+        );
+    }
+
+    #[test]
+    fn fits_a_line() {
+        let rows: Vec<Vec<f64>> = (0..5).map(|x| vec![1.0, x as f64]).collect();
+        let targets = [1.1, 2.9, 5.1, 6.9, 9.0];
+        let fit = least_squares(&rows, &targets).unwrap();
+        assert!((fit[0] - 1.04).abs() < 1e-12);
+        assert!((fit[1] - 1.98).abs() < 1e-12);
+    }
+}
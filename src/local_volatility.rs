@@ -0,0 +1,426 @@
+use crate::surface::VolSurface;
+use crate::{BlackScholesModel, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+const DEFAULT_SPACE_STEPS: usize = 200;
+const DEFAULT_TIME_STEPS: usize = 200;
+const RANNACHER_STEPS: usize = 2;
+const DEVIATIONS: f64 = 5.0;
+const STRIKE_BUMP: f64 = 1e-3;
+const TIME_BUMP: f64 = 1e-4;
+
+// LocalVolatility is a volatility function of the stock price and time, under which
+// the stock follows dS / S = (r - q) dt + vol(S, t) dW
+pub trait LocalVolatility {
+    // local_volatility is vol(S, t) (% p.a.) at the stock price and time
+    fn local_volatility(&self, stock: f64, time: f64) -> MathResult;
+}
+
+impl<L: LocalVolatility + ?Sized> LocalVolatility for &L {
+    fn local_volatility(&self, stock: f64, time: f64) -> MathResult {
+        (**self).local_volatility(stock, time)
+    }
+}
+
+// the implied volatility surface gives Dupire's local volatility through Gatheral's
+// form in the total implied variance w(k, T) at the log-moneyness k = ln(K / F(T))
+//
+//   vol^2 = w_T / (1 - k w_k / w + (-1/4 - 1/w + k^2 / w^2) w_k^2 / 4 + w_kk / 2)
+//
+// with the derivatives taken by central differences, the surface should be smooth
+// (a spline or SVI with linear wings) for them to make sense
+impl LocalVolatility for VolSurface {
+    fn local_volatility(&self, stock: f64, time: f64) -> MathResult {
+        let carry = self.interest_rate() - self.dividend().unwrap_or_default();
+        let forward = |t: f64| self.stock() * E.powf(carry * t);
+        let k = (stock / forward(time)).ln();
+        let total_variance = |k: f64, t: f64| -> MathResult {
+            if t == 0.0 {
+                return Ok(0.0);
+            }
+            Ok(self.volatility(forward(t) * E.powf(k), t)?.powi(2) * t)
+        };
+
+        // one-sided in time at the start
+        let (early, late) = ((time - TIME_BUMP).max(0.0), time + TIME_BUMP);
+        let w_t = (total_variance(k, late)? - total_variance(k, early)?) / (late - early);
+        let t = time.max(TIME_BUMP);
+        let h = STRIKE_BUMP;
+        let (down, w, up) = (
+            total_variance(k - h, t)?,
+            total_variance(k, t)?,
+            total_variance(k + h, t)?,
+        );
+        let w_k = (up - down) / (2.0 * h);
+        let w_kk = (up - 2.0 * w + down) / (h * h);
+
+        let denominator =
+            1.0 - k * w_k / w + (-0.25 - 1.0 / w + k * k / (w * w)) * w_k * w_k / 4.0 + w_kk / 2.0;
+        local_volatility(w_t, denominator)
+    }
+}
+
+// CallPriceSurface gives Dupire's local volatility from the prices of calls C(K, T)
+//
+//   vol^2 = (C_T + (r - q) K C_K + q C) / (K^2 C_KK / 2)
+//
+// with the derivatives taken by central differences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallPriceSurface<C> {
+    interest_rate: f64,    // continuously compounded risk-free interest rate (% p.a.)
+    dividend: Option<f64>, // continuously compounded dividend yield (% p.a.)
+    prices: C,             // call price ($$$ per share) by strike and time to expiration
+}
+
+impl<C: Fn(f64, f64) -> MathResult> CallPriceSurface<C> {
+    pub fn new(interest_rate: f64, dividend: Option<f64>, prices: C) -> CallPriceSurface<C> {
+        CallPriceSurface {
+            interest_rate,
+            dividend,
+            prices,
+        }
+    }
+}
+
+impl<C: Fn(f64, f64) -> MathResult> LocalVolatility for CallPriceSurface<C> {
+    fn local_volatility(&self, stock: f64, time: f64) -> MathResult {
+        let dividend = self.dividend.unwrap_or_default();
+        let strike = stock;
+        let t = time.max(TIME_BUMP);
+        let (early, late) = (t - TIME_BUMP, t + TIME_BUMP);
+        let c_t = ((self.prices)(strike, late)? - (self.prices)(strike, early)?) / (late - early);
+
+        let h = STRIKE_BUMP * strike;
+        let (down, c, up) = (
+            (self.prices)(strike - h, t)?,
+            (self.prices)(strike, t)?,
+            (self.prices)(strike + h, t)?,
+        );
+        let c_k = (up - down) / (2.0 * h);
+        let c_kk = (up - 2.0 * c + down) / (h * h);
+
+        let numerator = c_t + (self.interest_rate - dividend) * strike * c_k + dividend * c;
+        local_volatility(numerator, strike * strike * c_kk / 2.0)
+    }
+}
+
+// local_volatility divides the time by the strike derivative part of Dupire's formula,
+// both have to be positive for an arbitrage-free surface
+fn local_volatility(numerator: f64, denominator: f64) -> MathResult {
+    if denominator.is_nan() || denominator <= 0.0 {
+        return Err(MathError::ParameterOutOfBounds("density", denominator));
+    }
+    if numerator < 0.0 {
+        return Err(MathError::ParameterOutOfBounds(
+            "calendar_spread",
+            numerator,
+        ));
+    }
+    Ok((numerator / denominator).sqrt())
+}
+
+// LocalVolatilityModel prices european options under a local volatility function with
+// the Crank-Nicolson scheme in ln S, started by two pairs of implicit half steps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalVolatilityModel<L> {
+    model: BlackScholesModel, // contract and market inputs, the volatility sizes the grid
+    local_volatility: L,      // volatility function of the stock price and time
+    space_steps: usize,       // number of intervals in ln S, rounded up to an even number
+    time_steps: usize,        // number of time intervals
+}
+
+impl<L: LocalVolatility> LocalVolatilityModel<L> {
+    // new creates a 200 x 200 grid, use with_grid to change it
+    //
+    // The grid spans 5 standard deviations of the volatility of the model around the
+    // stock, its at-the-money implied volatility is a good choice
+    pub fn new(model: BlackScholesModel, local_volatility: L) -> LocalVolatilityModel<L> {
+        LocalVolatilityModel {
+            model,
+            local_volatility,
+            space_steps: DEFAULT_SPACE_STEPS,
+            time_steps: DEFAULT_TIME_STEPS,
+        }
+    }
+
+    pub fn with_grid(mut self, space_steps: usize, time_steps: usize) -> LocalVolatilityModel<L> {
+        self.space_steps = space_steps;
+        self.time_steps = time_steps;
+        self
+    }
+
+    // price calculates the fair value of the option ($$$ per share), at expiration that's
+    // the intrinsic value
+    pub fn price(&self) -> MathResult {
+        let m = &self.model;
+        BlackScholesModel::try_new(
+            m.opt,
+            m.strike,
+            m.stock,
+            m.interest_rate,
+            m.volatility,
+            m.time_to_expire,
+            m.dividend,
+        )?;
+        if m.time_to_expire == 0.0 {
+            return m.price();
+        }
+        let space_steps = self.space_steps + self.space_steps % 2;
+        if space_steps < 2 {
+            return Err(MathError::TooFewSteps(space_steps));
+        }
+        if self.time_steps < 1 {
+            return Err(MathError::TooFewSteps(self.time_steps));
+        }
+        let dividend = m.dividend.unwrap_or_default();
+        let width =
+            DEVIATIONS * m.volatility * m.time_to_expire.sqrt() + (m.strike / m.stock).ln().abs();
+        let dx = 2.0 * width / space_steps as f64;
+        let dt = m.time_to_expire / self.time_steps as f64;
+        let stocks: Vec<f64> = (0..=space_steps)
+            .map(|i| m.stock * E.powf(-width + i as f64 * dx))
+            .collect();
+        let mut values: Vec<f64> = stocks
+            .iter()
+            .map(|&s| match m.opt {
+                OptionKind::Call => (s - m.strike).max(0.0),
+                OptionKind::Put => (m.strike - s).max(0.0),
+            })
+            .collect();
+
+        // substeps of (time to expiration at the end, length, implicitness)
+        let mut substeps = Vec::with_capacity(self.time_steps + RANNACHER_STEPS);
+        for step in 0..self.time_steps {
+            let left = (step + 1) as f64 * dt;
+            if step < RANNACHER_STEPS {
+                substeps.push((left - dt / 2.0, dt / 2.0, 1.0));
+                substeps.push((left, dt / 2.0, 1.0));
+            } else {
+                substeps.push((left, dt, 0.5));
+            }
+        }
+
+        for (left, length, theta) in substeps {
+            // the coefficients are frozen at the middle of the substep in calendar time
+            let time = m.time_to_expire - left + length / 2.0;
+            let operator = stocks[1..space_steps]
+                .iter()
+                .map(|&s| {
+                    let variance = self.local_volatility.local_volatility(s, time)?.powi(2);
+                    let drift = m.interest_rate - dividend - variance / 2.0;
+                    let diffusion = variance / (dx * dx);
+                    Ok([
+                        diffusion / 2.0 - drift / (2.0 * dx),
+                        -diffusion - m.interest_rate,
+                        diffusion / 2.0 + drift / (2.0 * dx),
+                    ])
+                })
+                .collect::<MathResult<Vec<[f64; 3]>>>()?;
+
+            let (lowest, highest) = (stocks[0], stocks[space_steps]);
+            let discount = E.powf(-m.interest_rate * left);
+            let dividend_discount = E.powf(-dividend * left);
+            let (low, high) = match m.opt {
+                OptionKind::Call => (0.0, highest * dividend_discount - m.strike * discount),
+                OptionKind::Put => (m.strike * discount - lowest * dividend_discount, 0.0),
+            };
+
+            let (explicit, implicit) = ((1.0 - theta) * length, theta * length);
+            let n = space_steps - 1;
+            let mut rhs: Vec<f64> = (0..n)
+                .map(|j| {
+                    let [a, b, c] = operator[j];
+                    let applied = a * values[j] + b * values[j + 1] + c * values[j + 2];
+                    values[j + 1] + explicit * applied
+                })
+                .collect();
+            rhs[0] += implicit * operator[0][0] * low;
+            rhs[n - 1] += implicit * operator[n - 1][2] * high;
+            let interior = thomas(&operator, implicit, &rhs);
+
+            values[0] = low;
+            values[1..space_steps].copy_from_slice(&interior);
+            values[space_steps] = high;
+        }
+        Ok(values[space_steps / 2])
+    }
+}
+
+// thomas solves (I - dt L) x = rhs for the rows of the operator (lower, diagonal, upper)
+fn thomas(operator: &[[f64; 3]], dt: f64, rhs: &[f64]) -> Vec<f64> {
+    let n = rhs.len();
+    let mut upper = vec![0.0; n];
+    let mut x = vec![0.0; n];
+    let diagonal = |i: usize| 1.0 - dt * operator[i][1];
+    upper[0] = -dt * operator[0][2] / diagonal(0);
+    x[0] = rhs[0] / diagonal(0);
+    for i in 1..n {
+        let lower = -dt * operator[i][0];
+        let pivot = diagonal(i) - lower * upper[i - 1];
+        upper[i] = -dt * operator[i][2] / pivot;
+        x[i] = (rhs[i] - lower * x[i - 1]) / pivot;
+    }
+    for i in (0..n - 1).rev() {
+        x[i] -= upper[i] * x[i + 1];
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::surface::{Extrapolation, StrikeInterpolation};
+    use crate::svi::SsviParameters;
+    use crate::VolQuote;
+
+    const SURFACE: SsviParameters = SsviParameters {
+        rho: -0.6,
+        eta: 1.2,
+        gamma: 0.4,
+    };
+
+    // quotes from SSVI slices with an at-the-money variance of 4% a year, a skew which
+    // flattens with the time to expiration and no arbitrage
+    fn quotes() -> Vec<VolQuote> {
+        let mut quotes = Vec::new();
+        for &time_to_expire in &[0.25, 0.5, 1.0, 2.0] {
+            let forward = 100.0 * E.powf(0.02 * time_to_expire);
+            let smile = crate::svi::SviSmile::new(
+                forward,
+                time_to_expire,
+                SURFACE.slice(0.04 * time_to_expire),
+            );
+            for &strike in &[60.0, 70.0, 80.0, 90.0, 100.0, 110.0, 120.0, 140.0, 170.0] {
+                quotes.push(VolQuote {
+                    strike,
+                    time_to_expire,
+                    volatility: smile.volatility(strike).unwrap(),
+                });
+            }
+        }
+        quotes
+    }
+
+    fn surface() -> VolSurface {
+        VolSurface::new(100.0, 0.03, Some(0.01), &quotes())
+            .unwrap()
+            .with_interpolation(StrikeInterpolation::Svi)
+            .unwrap()
+            .with_extrapolation(Extrapolation::Linear)
+    }
+
+    #[test]
+    fn flat_surface_is_black_scholes() {
+        let quotes: Vec<VolQuote> = [0.5, 1.0]
+            .iter()
+            .flat_map(|&time_to_expire| {
+                [80.0, 100.0, 120.0].iter().map(move |&strike| VolQuote {
+                    strike,
+                    time_to_expire,
+                    volatility: 0.25,
+                })
+            })
+            .collect();
+        let surface = VolSurface::new(100.0, 0.03, None, &quotes).unwrap();
+        for &(stock, time) in &[(70.0, 0.0), (100.0, 0.3), (130.0, 1.5)] {
+            let result = surface.local_volatility(stock, time).unwrap();
+            assert!((result - 0.25).abs() < 1e-6, "{} is not 0.25", result);
+        }
+
+        let model = BlackScholesModel::new(OptionKind::Call, 105.0, 100.0, 0.03, 0.25, 1.0, None);
+        let result = LocalVolatilityModel::new(model, &surface).price().unwrap();
+        assert!((result - model.price().unwrap()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn implied_and_price_surfaces_agree() {
+        let surface = surface();
+        let prices = CallPriceSurface::new(0.03, Some(0.01), |strike, time| {
+            BlackScholesModel::from_surface(OptionKind::Call, strike, time, &surface)?.price()
+        });
+        for &(stock, time) in &[(85.0, 0.4), (100.0, 0.75), (115.0, 1.5)] {
+            let implied = surface.local_volatility(stock, time).unwrap();
+            let result = prices.local_volatility(stock, time).unwrap();
+            assert!(
+                (result - implied).abs() < 1e-4,
+                "{} is not {}",
+                result,
+                implied
+            );
+        }
+    }
+
+    #[test]
+    fn reprices_vanillas() {
+        // the grid reprices every quote to within 5 basis points of implied volatility,
+        // far inside a bid-ask spread, deep in the wings where vega vanishes the
+        // absolute error of the grid takes over
+        let surface = surface();
+        for q in quotes().iter() {
+            for opt in [OptionKind::Call, OptionKind::Put] {
+                let model =
+                    BlackScholesModel::from_surface(opt, q.strike, q.time_to_expire, &surface)
+                        .unwrap();
+                let expected = model.price().unwrap();
+                let tolerance = 5e-4 * model.vega().unwrap() + 2e-4;
+                let result = LocalVolatilityModel::new(model, &surface).price().unwrap();
+                assert!(
+                    (result - expected).abs() < tolerance,
+                    "{:?} {} {}: {} is not within {} of {}",
+                    opt,
+                    q.strike,
+                    q.time_to_expire,
+                    result,
+                    tolerance,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn err_with_butterfly_arbitrage() {
+        // a price surface which is linear in the strike has no density
+        let prices = CallPriceSurface::new(0.0, None, |strike, time| Ok(100.0 - strike + time));
+        assert!(matches!(
+            prices.local_volatility(100.0, 1.0),
+            Err(MathError::ParameterOutOfBounds("density", _))
+        ));
+    }
+
+    #[test]
+    fn err_with_too_few_steps() {
+        let model = BlackScholesModel::new(OptionKind::Call, 100.0, 100.0, 0.0, 0.2, 1.0, None);
+        let surface = surface();
+        let result = LocalVolatilityModel::new(model, &surface)
+            .with_grid(0, 10)
+            .price();
+        assert_eq!(result, Err(MathError::TooFewSteps(0)));
+        let result = LocalVolatilityModel::new(model, &surface)
+            .with_grid(10, 0)
+            .price();
+        assert_eq!(result, Err(MathError::TooFewSteps(0)));
+    }
+
+    #[test]
+    fn err_with_non_positive_volatility() {
+        // the volatility of the model sizes the grid
+        let model = BlackScholesModel::new(OptionKind::Call, 100.0, 100.0, 0.0, 0.0, 1.0, None);
+        let result = LocalVolatilityModel::new(model, surface()).price();
+        assert_eq!(result, Err(MathError::NonPositiveVolatility(0.0)));
+    }
+
+    #[test]
+    fn at_expiration() {
+        // at the money the grid would have no width at all
+        for &(opt, strike, expected) in &[
+            (OptionKind::Call, 100.0, 0.0),
+            (OptionKind::Call, 95.0, 5.0),
+            (OptionKind::Put, 105.0, 5.0),
+        ] {
+            let model = BlackScholesModel::new(opt, strike, 100.0, 0.03, 0.2, 0.0, None);
+            let result = LocalVolatilityModel::new(model, surface()).price();
+            assert_eq!(result, Ok(expected));
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use crate::linalg::least_squares;
+use crate::monte_carlo::{estimate, Estimate, MonteCarloModel};
+use crate::{BlackScholesModel, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+const DEFAULT_PATHS: usize = 50_000;
+const DEFAULT_EXERCISE_DATES: usize = 50;
+const DEFAULT_DEGREE: usize = 3;
+const DEFAULT_SEED: u64 = 0;
+
+// Basis selects the functions of the stock (scaled by the strike) which the
+// continuation value is regressed on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Basis {
+    Polynomial, // 1, x, x^2, ...
+    Laguerre,   // 1 and the weighted Laguerre polynomials exp(-x/2) L_n(x)
+}
+
+// AmericanEstimate holds the two Longstaff-Schwartz estimates of an american price
+//
+// The in-sample estimate exercises on the paths which the rule was fitted to, the
+// lower bound applies the fitted rule to independent paths, and since no rule beats
+// the optimal one it is biased low
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmericanEstimate {
+    pub in_sample: Estimate,   // regression and valuation on the same paths
+    pub lower_bound: Estimate, // valuation of the fitted rule on fresh paths
+}
+
+// LongstaffSchwartzModel prices options which can be exercised on a schedule of dates
+// up to expiration (bermudan, or american in the limit of many dates) by regressing
+// the value of holding on to the option on the simulated stock prices
+#[derive(Debug, Clone, PartialEq)]
+pub struct LongstaffSchwartzModel {
+    model: BlackScholesModel, // market inputs shared with the closed-form model
+    paths: usize,             // number of simulated paths for each estimate
+    exercise_times: Vec<f64>, // exercise dates (years from today), the last at expiration
+    seed: u64,                // seed of the random number generator
+    antithetic: bool,         // pair every path with its mirror image
+    basis: Basis,             // regression functions
+    degree: usize,            // highest order of the regression functions
+}
+
+impl LongstaffSchwartzModel {
+    // new creates a cubic polynomial regression with 50 equally spaced exercise dates,
+    // use with_paths, with_exercise_dates, with_exercise_schedule, with_seed,
+    // with_antithetic, with_basis and with_degree to change the defaults
+    pub fn new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+    ) -> LongstaffSchwartzModel {
+        LongstaffSchwartzModel::from(BlackScholesModel::new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        ))
+    }
+
+    pub fn with_paths(mut self, paths: usize) -> LongstaffSchwartzModel {
+        self.paths = paths;
+        self
+    }
+
+    // with_exercise_dates sets how many equally spaced dates the option can be
+    // exercised on, 1 makes it european
+    pub fn with_exercise_dates(mut self, exercise_dates: usize) -> LongstaffSchwartzModel {
+        self.exercise_times = equally_spaced(exercise_dates, self.model.time_to_expire);
+        self
+    }
+
+    // with_exercise_schedule sets the increasing times (years from today) the option
+    // can be exercised on besides expiration, which is added when it's not the last
+    pub fn with_exercise_schedule(mut self, times: &[f64]) -> LongstaffSchwartzModel {
+        let expiration = self.model.time_to_expire;
+        self.exercise_times = times.to_vec();
+        if times.last().map_or(true, |&last| last < expiration) {
+            self.exercise_times.push(expiration);
+        }
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> LongstaffSchwartzModel {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_antithetic(mut self, antithetic: bool) -> LongstaffSchwartzModel {
+        self.antithetic = antithetic;
+        self
+    }
+
+    pub fn with_basis(mut self, basis: Basis) -> LongstaffSchwartzModel {
+        self.basis = basis;
+        self
+    }
+
+    pub fn with_degree(mut self, degree: usize) -> LongstaffSchwartzModel {
+        self.degree = degree;
+        self
+    }
+
+    // price fits the exercise rule backwards through the dates on one set of paths and
+    // values it on that set and on a second, independent one
+    pub fn price(&self) -> MathResult<AmericanEstimate> {
+        let (samples, paths) = self.simulate(self.seed)?;
+        let rate = self.model.interest_rate;
+        let times = &self.exercise_times;
+        let dates = times.len();
+        // discount from every date to the one before, today being date 0
+        let discount = |date: usize| {
+            let before = if date == 1 { 0.0 } else { times[date - 2] };
+            E.powf(-rate * (times[date - 1] - before))
+        };
+
+        // value of every path discounted to the date being looked at, starting with the
+        // payoff at expiration
+        let mut values: Vec<f64> = paths.iter().map(|p| self.intrinsic(p[dates])).collect();
+        let mut rules = vec![None; dates];
+        for date in (1..dates).rev() {
+            values.iter_mut().for_each(|v| *v *= discount(date + 1));
+
+            let in_the_money: Vec<usize> = (0..paths.len())
+                .filter(|&i| self.intrinsic(paths[i][date]) > 0.0)
+                .collect();
+            let rows: Vec<Vec<f64>> = in_the_money
+                .iter()
+                .map(|&i| self.basis_at(paths[i][date]))
+                .collect();
+            let targets: Vec<f64> = in_the_money.iter().map(|&i| values[i]).collect();
+            rules[date] = least_squares(&rows, &targets);
+
+            for &i in &in_the_money {
+                let stock = paths[i][date];
+                if self.exercises(&rules[date], stock) {
+                    values[i] = self.intrinsic(stock);
+                }
+            }
+        }
+        values.iter_mut().for_each(|v| *v *= discount(1));
+        let in_sample = self.average(&samples, &values);
+
+        let (samples, paths) = self.simulate(self.seed.wrapping_add(1))?;
+        let values: Vec<f64> = paths
+            .iter()
+            .map(|path| {
+                let date = (1..dates)
+                    .find(|&date| self.exercises(&rules[date], path[date]))
+                    .unwrap_or(dates);
+                E.powf(-rate * times[date - 1]) * self.intrinsic(path[date])
+            })
+            .collect();
+        Ok(AmericanEstimate {
+            in_sample,
+            lower_bound: self.average(&samples, &values),
+        })
+    }
+
+    // check_schedule rejects exercise dates which aren't increasing within the life of
+    // the option
+    fn check_schedule(&self) -> MathResult<()> {
+        let mut previous = 0.0;
+        for &time in &self.exercise_times {
+            if time.is_nan() || time <= previous || time > self.model.time_to_expire {
+                return Err(MathError::ParameterOutOfBounds("exercise time", time));
+            }
+            previous = time;
+        }
+        Ok(())
+    }
+
+    fn intrinsic(&self, stock: f64) -> f64 {
+        match self.model.opt {
+            OptionKind::Call => (stock - self.model.strike).max(0.0),
+            OptionKind::Put => (self.model.strike - stock).max(0.0),
+        }
+    }
+
+    // exercises tells if exercising beats the fitted continuation value, dates without
+    // a fit (too few paths in the money) are never exercised on
+    fn exercises(&self, rule: &Option<Vec<f64>>, stock: f64) -> bool {
+        let intrinsic = self.intrinsic(stock);
+        match rule {
+            Some(coefficients) if intrinsic > 0.0 => {
+                let continuation: f64 = coefficients
+                    .iter()
+                    .zip(self.basis_at(stock))
+                    .map(|(c, f)| c * f)
+                    .sum();
+                intrinsic > continuation
+            }
+            _ => false,
+        }
+    }
+
+    fn basis_at(&self, stock: f64) -> Vec<f64> {
+        let x = stock / self.model.strike;
+        let mut functions = Vec::with_capacity(self.degree + 1);
+        functions.push(1.0);
+        match self.basis {
+            Basis::Polynomial => {
+                for n in 1..=self.degree {
+                    functions.push(x.powi(n as i32));
+                }
+            }
+            Basis::Laguerre => {
+                let weight = E.powf(-x / 2.0);
+                let (mut previous, mut current) = (0.0, 1.0);
+                for n in 0..self.degree {
+                    functions.push(weight * current);
+                    let next = ((2 * n + 1) as f64 - x) * current - n as f64 * previous;
+                    previous = current;
+                    current = next / (n + 1) as f64;
+                }
+            }
+        }
+        functions
+    }
+
+    // simulate returns the sample number and the stock prices on every date (today
+    // first) of each path
+    fn simulate(&self, seed: u64) -> MathResult<(Vec<usize>, Vec<Vec<f64>>)> {
+        self.check_schedule()?;
+        let mut samples = Vec::with_capacity(self.paths);
+        let mut paths = Vec::with_capacity(self.paths);
+        MonteCarloModel::from(self.model)
+            .with_paths(self.paths)
+            .with_seed(seed)
+            .with_antithetic(self.antithetic)
+            .for_each_path_at(&self.exercise_times, |sample, path| {
+                samples.push(sample);
+                paths.push(path.to_vec());
+            })?;
+        Ok((samples, paths))
+    }
+
+    // average combines the values of the paths which belong to the same sample before
+    // estimating the mean
+    fn average(&self, samples: &[usize], values: &[f64]) -> Estimate {
+        let count = samples.last().map_or(0, |last| last + 1);
+        let mut totals = vec![0.0; count];
+        for (&sample, value) in samples.iter().zip(values) {
+            totals[sample] += value;
+        }
+        if self.antithetic {
+            totals.iter_mut().for_each(|t| *t /= 2.0);
+        }
+        estimate(&totals)
+    }
+}
+
+impl From<BlackScholesModel> for LongstaffSchwartzModel {
+    fn from(model: BlackScholesModel) -> LongstaffSchwartzModel {
+        LongstaffSchwartzModel {
+            exercise_times: equally_spaced(DEFAULT_EXERCISE_DATES, model.time_to_expire),
+            model,
+            paths: DEFAULT_PATHS,
+            seed: DEFAULT_SEED,
+            antithetic: false,
+            basis: Basis::Polynomial,
+            degree: DEFAULT_DEGREE,
+        }
+    }
+}
+
+fn equally_spaced(dates: usize, time_to_expire: f64) -> Vec<f64> {
+    (1..=dates)
+        .map(|date| date as f64 / dates as f64 * time_to_expire)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binomial::{BinomialModel, ExerciseStyle, Lattice};
+    use crate::MathError;
+
+    // first case of Longstaff and Schwartz (2001), table 1: S = 36, K = 40, r = 6%,
+    // vol = 20%, 1 year and 50 exercise dates, where they estimate 4.472
+    fn put() -> LongstaffSchwartzModel {
+        LongstaffSchwartzModel::new(OptionKind::Put, 40.0, 36.0, 0.06, 0.2, 1.0, None)
+            .with_paths(20_000)
+            .with_antithetic(true)
+    }
+
+    fn american_tree() -> f64 {
+        BinomialModel::from(put().model)
+            .with_exercise(ExerciseStyle::American)
+            .with_lattice(Lattice::LeisenReimer)
+            .with_steps(1001)
+            .price()
+            .unwrap()
+    }
+
+    #[test]
+    fn american_put_matches_binomial_tree() {
+        let tree = american_tree();
+        for basis in [Basis::Polynomial, Basis::Laguerre] {
+            let result = put().with_basis(basis).price().unwrap();
+            for estimate in [result.in_sample, result.lower_bound] {
+                // 50 exercise dates are worth slightly less than continuous exercise
+                assert!(
+                    (estimate.price - tree).abs() < 4.0 * estimate.standard_error + 0.02,
+                    "{:?} is not close to {}",
+                    estimate,
+                    tree
+                );
+            }
+            assert!(result.lower_bound.price < tree + 3.0 * result.lower_bound.standard_error);
+        }
+    }
+
+    #[test]
+    fn single_exercise_date_is_european() {
+        let model = put().with_exercise_dates(1);
+        let expected = model.model.price().unwrap();
+        let result = model.price().unwrap();
+        for estimate in [result.in_sample, result.lower_bound] {
+            assert!((estimate.price - expected).abs() < 4.0 * estimate.standard_error);
+        }
+    }
+
+    #[test]
+    fn more_exercise_dates_are_worth_more() {
+        let quarterly = put().with_exercise_dates(4).price().unwrap().lower_bound;
+        let weekly = put().with_exercise_dates(52).price().unwrap().lower_bound;
+        let european = put().model.price().unwrap();
+        assert!(european < quarterly.price && quarterly.price < weekly.price);
+    }
+
+    #[test]
+    fn uneven_exercise_schedule() {
+        // exercising at 0.3 years or expiration is worth the better of the intrinsic
+        // value and the european put at 0.3 years, integrated over the stock price then
+        let early = 0.3;
+        let m = put().model;
+        let n = 2000;
+        let h = 16.0 / n as f64;
+        let mut expected = 0.0;
+        for i in 0..=n {
+            let z = -8.0 + i as f64 * h;
+            let stock = m.stock
+                * E.powf(
+                    (m.interest_rate - m.volatility.powi(2) / 2.0) * early
+                        + m.volatility * early.sqrt() * z,
+                );
+            let european = BlackScholesModel::new(
+                OptionKind::Put,
+                m.strike,
+                stock,
+                m.interest_rate,
+                m.volatility,
+                m.time_to_expire - early,
+                None,
+            );
+            let value = (m.strike - stock).max(european.price().unwrap());
+            let weight = if i == 0 || i == n {
+                1.0
+            } else {
+                (2 + 2 * (i % 2)) as f64
+            };
+            expected += weight * h / 3.0 * value * E.powf(-z * z / 2.0);
+        }
+        expected *= E.powf(-m.interest_rate * early) / (2.0 * std::f64::consts::PI).sqrt();
+
+        let result = put().with_exercise_schedule(&[early]).price().unwrap();
+        for estimate in [result.in_sample, result.lower_bound] {
+            assert!(
+                (estimate.price - expected).abs() < 4.0 * estimate.standard_error,
+                "{:?} is not close to {}",
+                estimate,
+                expected
+            );
+        }
+        assert!(expected > m.price().unwrap() + 0.1);
+    }
+
+    #[test]
+    fn laguerre_basis() {
+        let model = put().with_basis(Basis::Laguerre).with_degree(2);
+        // 1, exp(-x/2), exp(-x/2) (1 - x)
+        let x: f64 = 0.9;
+        let expected = [1.0, E.powf(-x / 2.0), E.powf(-x / 2.0) * (1.0 - x)];
+        for (result, expected) in model.basis_at(36.0).iter().zip(expected.iter()) {
+            assert!((result - expected).abs() < 1e-15);
+        }
+    }
+
+    #[test]
+    fn err_with_too_few_paths_or_dates() {
+        assert_eq!(put().with_paths(2).price(), Err(MathError::TooFewPaths(2)));
+        assert_eq!(
+            put().with_exercise_dates(0).price(),
+            Err(MathError::TooFewSteps(0))
+        );
+        assert_eq!(
+            put().with_exercise_schedule(&[0.5, 0.25]).price(),
+            Err(MathError::ParameterOutOfBounds("exercise time", 0.25))
+        );
+        assert_eq!(
+            put().with_exercise_schedule(&[0.5, 1.5]).price(),
+            Err(MathError::ParameterOutOfBounds("exercise time", 1.5))
+        );
+    }
+}
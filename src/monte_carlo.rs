@@ -0,0 +1,409 @@
+use crate::random::{BrownianBridge, Sobol, Xoshiro256, MAX_DIMENSIONS};
+use crate::{BlackScholesModel, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+const DEFAULT_PATHS: usize = 100_000;
+const DEFAULT_STEPS: usize = 1;
+const DEFAULT_SEED: u64 = 0;
+const REPLICATIONS: usize = 16; // independently shifted Sobol sequences
+
+// Sampling selects where the normal draws driving the paths come from
+//
+// The Sobol sequence takes one dimension per time step and its direction numbers are
+// tabulated for the first 32 dimensions only, paths with more steps take pseudo-random
+// draws for the rest, which the Brownian bridge spends on the finest details
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    PseudoRandom, // xoshiro256** draws, paths built from their increments
+    Sobol,        // randomly shifted Sobol points, paths built with a Brownian bridge
+}
+
+// Estimate is a simulated price with the standard error of the estimate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub price: f64,          // mean discounted payoff ($$$ per share)
+    pub standard_error: f64, // standard deviation of the mean ($$$ per share)
+}
+
+// MonteCarloModel simulates the stock as a geometric Brownian motion with the drift of
+// the cost of carry, on equally spaced steps up to expiration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonteCarloModel {
+    model: BlackScholesModel, // market inputs shared with the closed-form model
+    paths: usize,             // number of simulated paths
+    steps: usize,             // number of time steps per path
+    seed: u64,                // seed of the random number generator
+    antithetic: bool,         // pair every path with its mirror image
+    control_variate: bool,    // correct with the european option of the model
+    sampling: Sampling,       // pseudo-random or quasi-random draws
+}
+
+impl MonteCarloModel {
+    // new creates a pseudo-random simulation of a single step, use with_paths,
+    // with_steps, with_seed, with_antithetic, with_control_variate and with_sampling to
+    // change the defaults
+    pub fn new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+    ) -> MonteCarloModel {
+        MonteCarloModel::from(BlackScholesModel::new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        ))
+    }
+
+    pub fn with_paths(mut self, paths: usize) -> MonteCarloModel {
+        self.paths = paths;
+        self
+    }
+
+    // with_steps sets the number of time steps per path
+    pub fn with_steps(mut self, steps: usize) -> MonteCarloModel {
+        self.steps = steps;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> MonteCarloModel {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_antithetic(mut self, antithetic: bool) -> MonteCarloModel {
+        self.antithetic = antithetic;
+        self
+    }
+
+    // with_control_variate uses the payoff of the european option of the model, whose
+    // value is known from BlackScholesModel::price, to cancel part of the noise
+    pub fn with_control_variate(mut self, control_variate: bool) -> MonteCarloModel {
+        self.control_variate = control_variate;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> MonteCarloModel {
+        self.sampling = sampling;
+        self
+    }
+
+    // price estimates the value of the european option of the model
+    pub fn price(&self) -> MathResult<Estimate> {
+        self.price_with(|path| self.intrinsic(path[path.len() - 1]))
+    }
+
+    // price_with estimates the value of any payoff paid at expiration, the payoff gets
+    // the stock prices at every step with today's price first
+    //
+    // Pseudo-random standard errors come from the spread of the paths, Sobol standard
+    // errors from the spread between independently shifted sequences
+    pub fn price_with<F: Fn(&[f64]) -> f64>(&self, payoff: F) -> MathResult<Estimate> {
+        let discount = self.model.discount();
+        let mut values = Vec::new();
+        let mut controls = Vec::new();
+        self.for_each_path(|sample, path| {
+            if values.len() == sample {
+                values.push(0.0);
+                controls.push(0.0);
+            }
+            values[sample] += discount * payoff(path);
+            if self.control_variate {
+                controls[sample] += discount * self.intrinsic(path[path.len() - 1]);
+            }
+        })?;
+        if self.antithetic {
+            values.iter_mut().for_each(|v| *v /= 2.0);
+            controls.iter_mut().for_each(|c| *c /= 2.0);
+        }
+
+        if self.control_variate {
+            let beta = covariance(&controls, &values) / covariance(&controls, &controls);
+            if beta.is_finite() {
+                let expected = self.model.price()?;
+                for (value, control) in values.iter_mut().zip(&controls) {
+                    *value -= beta * (control - expected);
+                }
+            }
+        }
+
+        Ok(match self.sampling {
+            Sampling::PseudoRandom => estimate(&values),
+            Sampling::Sobol => {
+                let size = values.len() / REPLICATIONS;
+                estimate(&values.chunks(size).map(mean).collect::<Vec<_>>())
+            }
+        })
+    }
+
+    fn intrinsic(&self, stock: f64) -> f64 {
+        match self.model.opt {
+            OptionKind::Call => (stock - self.model.strike).max(0.0),
+            OptionKind::Put => (self.model.strike - stock).max(0.0),
+        }
+    }
+
+    // samples returns the number of independent samples, an antithetic pair counts as
+    // one sample and Sobol samples are split evenly between the replications
+    fn samples(&self) -> MathResult<usize> {
+        let samples = if self.antithetic {
+            self.paths / 2
+        } else {
+            self.paths
+        };
+        match self.sampling {
+            Sampling::PseudoRandom if samples >= 2 => Ok(samples),
+            Sampling::Sobol if samples >= REPLICATIONS => Ok(samples - samples % REPLICATIONS),
+            _ => Err(MathError::TooFewPaths(self.paths)),
+        }
+    }
+
+    // for_each_path simulates the paths and hands each of them to visit together with
+    // the number of its sample, both paths of an antithetic pair share a sample
+    pub(crate) fn for_each_path<F: FnMut(usize, &[f64])>(&self, visit: F) -> MathResult<()> {
+        let time = self.model.time_to_expire;
+        let times: Vec<f64> = (1..=self.steps)
+            .map(|i| i as f64 / self.steps as f64 * time)
+            .collect();
+        self.for_each_path_at(&times, visit)
+    }
+
+    // for_each_path_at simulates the paths on the increasing times (years from today)
+    // instead of the equally spaced steps
+    pub(crate) fn for_each_path_at<F: FnMut(usize, &[f64])>(
+        &self,
+        times: &[f64],
+        mut visit: F,
+    ) -> MathResult<()> {
+        let steps = times.len();
+        if steps < 1 {
+            return Err(MathError::TooFewSteps(steps));
+        }
+        let samples = self.samples()?;
+        let m = &self.model;
+        let intervals: Vec<f64> = (0..steps)
+            .map(|i| times[i] - if i == 0 { 0.0 } else { times[i - 1] })
+            .collect();
+        let carry = m.interest_rate - m.dividend.unwrap_or_default();
+        let drifts: Vec<f64> = intervals
+            .iter()
+            .map(|dt| (carry - m.volatility.powi(2) / 2.0) * dt)
+            .collect();
+
+        let mut rng = Xoshiro256::new(self.seed);
+        let bridge = BrownianBridge::new(times);
+        let mut sobol = None;
+        let mut normals = vec![0.0; steps];
+        let mut increments = vec![0.0; steps];
+        let mut path = vec![m.stock; steps + 1];
+
+        for sample in 0..samples {
+            match self.sampling {
+                Sampling::PseudoRandom => {
+                    for ((z, dw), dt) in normals
+                        .iter_mut()
+                        .zip(increments.iter_mut())
+                        .zip(&intervals)
+                    {
+                        *z = rng.normal();
+                        *dw = dt.sqrt() * *z;
+                    }
+                }
+                Sampling::Sobol => {
+                    let dimensions = steps.min(MAX_DIMENSIONS);
+                    if sample % (samples / REPLICATIONS) == 0 {
+                        sobol = Some(Sobol::new(dimensions)?.with_shift(&mut rng));
+                    }
+                    if let Some(sequence) = sobol.as_mut() {
+                        sequence.next_normals(&mut normals[..dimensions]);
+                    }
+                    for z in normals[dimensions..].iter_mut() {
+                        *z = rng.normal();
+                    }
+                    bridge.increments(&normals, &mut increments);
+                }
+            }
+
+            for sign in [1.0, -1.0].iter().take(1 + self.antithetic as usize) {
+                for (i, (dw, drift)) in increments.iter().zip(&drifts).enumerate() {
+                    path[i + 1] = path[i] * E.powf(drift + m.volatility * sign * dw);
+                }
+                visit(sample, &path);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<BlackScholesModel> for MonteCarloModel {
+    fn from(model: BlackScholesModel) -> MonteCarloModel {
+        MonteCarloModel {
+            model,
+            paths: DEFAULT_PATHS,
+            steps: DEFAULT_STEPS,
+            seed: DEFAULT_SEED,
+            antithetic: false,
+            control_variate: false,
+            sampling: Sampling::PseudoRandom,
+        }
+    }
+}
+
+// estimate averages independent samples
+pub(crate) fn estimate(samples: &[f64]) -> Estimate {
+    Estimate {
+        price: mean(samples),
+        standard_error: (covariance(samples, samples) / samples.len() as f64).sqrt(),
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+// covariance is the unbiased sample covariance
+pub(crate) fn covariance(x: &[f64], y: &[f64]) -> f64 {
+    let (mean_x, mean_y) = (mean(x), mean(y));
+    x.iter()
+        .zip(y)
+        .map(|(a, b)| (a - mean_x) * (b - mean_y))
+        .sum::<f64>()
+        / (x.len() - 1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulation(opt: OptionKind) -> MonteCarloModel {
+        MonteCarloModel::new(opt, 58.0, 60.0, 0.035, 0.2, 0.5, Some(0.0125))
+    }
+
+    // assert_within checks that the estimate is within four standard errors of the
+    // expected value
+    fn assert_within(estimate: Estimate, expected: f64) {
+        assert!(
+            (estimate.price - expected).abs() < 4.0 * estimate.standard_error,
+            "{:?} is not within four standard errors of {}",
+            estimate,
+            expected
+        );
+    }
+
+    #[test]
+    fn european_price_matches_black_scholes() {
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let expected = simulation(opt).model.price().unwrap();
+            let estimate = simulation(opt).price().unwrap();
+            assert_within(estimate, expected);
+            assert!(estimate.standard_error < 0.05);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_estimate() {
+        let model = simulation(OptionKind::Call).with_paths(1000);
+        assert_eq!(model.price(), model.price());
+        assert_ne!(model.price(), model.with_seed(1).price());
+    }
+
+    #[test]
+    fn variance_reduction_shrinks_standard_error() {
+        let plain = simulation(OptionKind::Call).price().unwrap();
+        let antithetic = simulation(OptionKind::Call)
+            .with_antithetic(true)
+            .price()
+            .unwrap();
+        // an average price call is strongly correlated with the european call
+        let average = |path: &[f64]| (path.iter().sum::<f64>() / path.len() as f64 - 58.0).max(0.0);
+        let control = simulation(OptionKind::Call)
+            .with_control_variate(true)
+            .with_steps(12)
+            .price_with(average)
+            .unwrap();
+        let uncontrolled = simulation(OptionKind::Call)
+            .with_steps(12)
+            .price_with(average)
+            .unwrap();
+
+        let expected = simulation(OptionKind::Call).model.price().unwrap();
+        assert_within(antithetic, expected);
+        assert!(antithetic.standard_error < plain.standard_error);
+        assert!(control.standard_error < 0.6 * uncontrolled.standard_error);
+    }
+
+    #[test]
+    fn control_variate_is_exact_for_the_european_payoff() {
+        let model = simulation(OptionKind::Put).with_control_variate(true);
+        let estimate = model.price().unwrap();
+        assert!((estimate.price - model.model.price().unwrap()).abs() < 1e-10);
+        assert!(estimate.standard_error < 1e-10);
+    }
+
+    #[test]
+    fn sobol_with_brownian_bridge() {
+        let expected = simulation(OptionKind::Call).model.price().unwrap();
+        let pseudo = simulation(OptionKind::Call)
+            .with_paths(16_384)
+            .with_steps(16)
+            .price()
+            .unwrap();
+        let sobol = simulation(OptionKind::Call)
+            .with_paths(16_384)
+            .with_steps(16)
+            .with_sampling(Sampling::Sobol)
+            .price()
+            .unwrap();
+
+        assert_within(sobol, expected);
+        assert!(sobol.standard_error < 0.2 * pseudo.standard_error);
+    }
+
+    #[test]
+    fn sobol_beyond_tabulated_dimensions() {
+        // the 20 finest bridge dimensions are pseudo-random
+        let expected = simulation(OptionKind::Put).model.price().unwrap();
+        let model = simulation(OptionKind::Put)
+            .with_paths(16_384)
+            .with_steps(52);
+        let pseudo = model.price().unwrap();
+        let sobol = model.with_sampling(Sampling::Sobol).price().unwrap();
+
+        assert_within(sobol, expected);
+        assert!(sobol.standard_error < 0.2 * pseudo.standard_error);
+    }
+
+    #[test]
+    fn path_dependent_payoff() {
+        // a lookback on the maximum is worth at least the european call
+        let model = simulation(OptionKind::Call)
+            .with_steps(50)
+            .with_paths(20_000);
+        let lookback = model
+            .price_with(|path| (path.iter().cloned().fold(0.0, f64::max) - 58.0).max(0.0))
+            .unwrap();
+        assert!(lookback.price > model.model.price().unwrap());
+    }
+
+    #[test]
+    fn err_with_too_few_paths_or_steps() {
+        let model = simulation(OptionKind::Call);
+        assert_eq!(model.with_paths(1).price(), Err(MathError::TooFewPaths(1)));
+        assert_eq!(
+            model.with_paths(3).with_antithetic(true).price(),
+            Err(MathError::TooFewPaths(3))
+        );
+        assert_eq!(
+            model.with_paths(15).with_sampling(Sampling::Sobol).price(),
+            Err(MathError::TooFewPaths(15))
+        );
+        assert_eq!(model.with_steps(0).price(), Err(MathError::TooFewSteps(0)));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{MathError, MathResult};
+
+const MAX_DEPTH: usize = 40;
+
+// Gauss-Legendre abscissae (lower half, the rule is symmetric) and weights for 6, 12
+// and 20 points on [-1, 1]
+pub(crate) const LEGENDRE_6: [(f64, f64); 3] = [
+    (-0.932469514203152, 0.17132449237917036),
+    (-0.6612093864662645, 0.3607615730481386),
+    (-0.2386191860831969, 0.46791393457269104),
+];
+pub(crate) const LEGENDRE_12: [(f64, f64); 6] = [
+    (-0.9815606342467192, 0.04717533638651183),
+    (-0.9041172563704749, 0.10693932599531843),
+    (-0.7699026741943047, 0.16007832854334622),
+    (-0.5873179542866175, 0.20316742672306592),
+    (-0.3678314989981802, 0.2334925365383548),
+    (-0.1252334085114689, 0.24914704581340277),
+];
+pub(crate) const LEGENDRE_20: [(f64, f64); 10] = [
+    (-0.9931285991850949, 0.017614007139152118),
+    (-0.9639719272779138, 0.04060142980038694),
+    (-0.912234428251326, 0.06267204833410907),
+    (-0.8391169718222188, 0.08327674157670475),
+    (-0.7463319064601508, 0.10193011981724044),
+    (-0.636053680726515, 0.11819453196151841),
+    (-0.5108670019508271, 0.13168863844917664),
+    (-0.37370608871541955, 0.14209610931838204),
+    (-0.22778585114164507, 0.14917298647260374),
+    (-0.07652652113349734, 0.15275338713072584),
+];
+
+// gauss_legendre integrates f over [a, b] with one of the symmetric rules above
+pub(crate) fn gauss_legendre<F: Fn(f64) -> f64>(f: &F, a: f64, b: f64, rule: &[(f64, f64)]) -> f64 {
+    let (center, half) = ((a + b) / 2.0, (b - a) / 2.0);
+    rule.iter()
+        .map(|&(node, weight)| weight * (f(center + half * node) + f(center - half * node)))
+        .sum::<f64>()
+        * half
+}
+
+// adaptive integrates f over [a, b], bisecting intervals until the 12 and 20 point
+// rules agree to within the tolerance (absolute, or relative for large integrals)
+pub(crate) fn adaptive<F: Fn(f64) -> f64>(f: &F, a: f64, b: f64, tolerance: f64) -> MathResult {
+    let mut total = 0.0;
+    let mut intervals = vec![(a, b, 0)];
+    while let Some((a, b, depth)) = intervals.pop() {
+        let coarse = gauss_legendre(f, a, b, &LEGENDRE_12);
+        let fine = gauss_legendre(f, a, b, &LEGENDRE_20);
+        if !fine.is_finite() {
+            return Err(MathError::NonFiniteInput("integrand", fine));
+        }
+        if (fine - coarse).abs() <= tolerance * fine.abs().max(1.0) {
+            total += fine;
+        } else if depth == MAX_DEPTH {
+            return Err(MathError::NoConvergence(MAX_DEPTH));
+        } else {
+            let middle = (a + b) / 2.0;
+            intervals.push((middle, b, depth + 1));
+            intervals.push((a, middle, depth + 1));
+        }
+    }
+    Ok(total)
+}
+
+// semi_infinite integrates f over [0, inf) by mapping it onto [0, 1) with
+// u = t / (1 - t), the integrand has to decay faster than 1 / u
+pub(crate) fn semi_infinite<F: Fn(f64) -> f64>(f: &F, tolerance: f64) -> MathResult {
+    adaptive(
+        &|t: f64| {
+            let u = t / (1.0 - t);
+            f(u) / (1.0 - t).powi(2)
+        },
+        0.0,
+        1.0,
+        tolerance,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn rules_integrate_polynomials_exactly() {
+        // an n point rule is exact up to degree 2n - 1
+        let f = |x: f64| 7.0 * x.powi(11) - 3.0 * x.powi(4) + 1.0;
+        let expected = 7.0 / 12.0 * 2f64.powi(12) - 3.0 / 5.0 * 2f64.powi(5) + 2.0;
+        for rule in [&LEGENDRE_6[..], &LEGENDRE_12[..], &LEGENDRE_20[..]]
+            .iter()
+            .skip(1)
+        {
+            let result = gauss_legendre(&f, 0.0, 2.0, rule);
+            assert!((result - expected).abs() < 1e-10 * expected);
+        }
+    }
+
+    #[test]
+    fn adaptive_handles_peaks() {
+        let f = |x: f64| 1.0 / (1e-4 + x * x);
+        let expected = 2.0 * 100.0 * (100.0f64).atan();
+        let result = adaptive(&f, -1.0, 1.0, 1e-12).unwrap();
+        assert!((result - expected).abs() < 1e-9 * expected);
+    }
+
+    #[test]
+    fn semi_infinite_integral() {
+        let result = semi_infinite(&|u: f64| 1.0 / (1.0 + u * u), 1e-12).unwrap();
+        assert!((result - PI / 2.0).abs() < 1e-12);
+        let result = semi_infinite(&|u: f64| (-u * u).exp(), 1e-12).unwrap();
+        assert!((result - PI.sqrt() / 2.0).abs() < 1e-12);
+    }
+}
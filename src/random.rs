@@ -0,0 +1,307 @@
+use crate::distributions::norm_inv_cdf;
+use crate::{MathError, MathResult};
+
+const BITS: usize = 32;
+
+// primitive polynomials and initial direction numbers of Joe and Kuo (2008) for the
+// dimensions after the first: (degree, inner coefficients, direction numbers)
+const DIRECTIONS: [(u32, u32, &[u32]); 31] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+    (6, 19, &[1, 1, 1, 15, 7, 5]),
+    (6, 22, &[1, 3, 1, 15, 13, 25]),
+    (6, 25, &[1, 1, 5, 5, 19, 61]),
+    (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+    (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+    (7, 7, &[1, 1, 3, 13, 7, 35, 63]),
+    (7, 8, &[1, 3, 5, 9, 1, 25, 53]),
+    (7, 14, &[1, 3, 1, 13, 9, 35, 107]),
+    (7, 19, &[1, 3, 1, 5, 27, 61, 31]),
+    (7, 21, &[1, 1, 5, 11, 19, 41, 61]),
+    (7, 28, &[1, 3, 5, 3, 3, 13, 69]),
+    (7, 31, &[1, 1, 7, 13, 1, 19, 1]),
+    (7, 32, &[1, 3, 7, 5, 13, 19, 59]),
+    (7, 37, &[1, 1, 3, 9, 25, 29, 41]),
+    (7, 41, &[1, 3, 5, 13, 23, 1, 55]),
+    (7, 42, &[1, 3, 7, 3, 13, 59, 17]),
+];
+
+// MAX_DIMENSIONS is the highest dimension the Sobol sequence is tabulated for
+pub(crate) const MAX_DIMENSIONS: usize = DIRECTIONS.len() + 1;
+
+// Xoshiro256 is the xoshiro256** generator of Blackman and Vigna, seeded through
+// splitmix64 so that every seed gives a well mixed state
+#[derive(Debug, Clone)]
+pub(crate) struct Xoshiro256 {
+    state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    pub(crate) fn new(seed: u64) -> Xoshiro256 {
+        let mut seed = seed;
+        let mut state = [0; 4];
+        for word in state.iter_mut() {
+            seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *word = z ^ (z >> 31);
+        }
+        Xoshiro256 { state }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    // uniform draws from the open interval (0, 1), so its inverse normal is finite
+    pub(crate) fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    pub(crate) fn normal(&mut self) -> f64 {
+        norm_inv_cdf(self.uniform())
+    }
+}
+
+// Sobol generates the Sobol low-discrepancy sequence in Gray code order, skipping the
+// point at the origin, optionally randomised by a digital shift
+#[derive(Debug, Clone)]
+pub(crate) struct Sobol {
+    directions: Vec<[u32; BITS]>,
+    shift: Vec<u32>,
+    point: Vec<u32>,
+    index: u32,
+}
+
+impl Sobol {
+    pub(crate) fn new(dimensions: usize) -> MathResult<Sobol> {
+        if dimensions > MAX_DIMENSIONS {
+            return Err(MathError::TooManyDimensions(dimensions));
+        }
+        let mut directions = Vec::with_capacity(dimensions);
+        if dimensions > 0 {
+            let mut first = [0; BITS];
+            for (bit, v) in first.iter_mut().enumerate() {
+                *v = 1 << (BITS - 1 - bit);
+            }
+            directions.push(first);
+        }
+        for &(degree, coefficients, initial) in DIRECTIONS.iter().take(dimensions.max(1) - 1) {
+            let degree = degree as usize;
+            let mut v = [0; BITS];
+            for bit in 0..BITS {
+                v[bit] = if bit < degree {
+                    initial[bit] << (BITS - 1 - bit)
+                } else {
+                    let mut value = v[bit - degree] ^ (v[bit - degree] >> degree);
+                    for k in 1..degree {
+                        if (coefficients >> (degree - 1 - k)) & 1 == 1 {
+                            value ^= v[bit - k];
+                        }
+                    }
+                    value
+                };
+            }
+            directions.push(v);
+        }
+        Ok(Sobol {
+            directions,
+            shift: vec![0; dimensions],
+            point: vec![0; dimensions],
+            index: 0,
+        })
+    }
+
+    // with_shift randomises the sequence by XOR-ing every coordinate with random bits
+    pub(crate) fn with_shift(mut self, rng: &mut Xoshiro256) -> Sobol {
+        for shift in self.shift.iter_mut() {
+            *shift = (rng.next_u64() >> 32) as u32;
+        }
+        self
+    }
+
+    // next_normals fills the slice with the inverse normal of the next point
+    pub(crate) fn next_normals(&mut self, normals: &mut [f64]) {
+        // the lowest zero bit of the index selects the direction which flips
+        let bit = (!self.index).trailing_zeros() as usize;
+        self.index += 1;
+        for (i, normal) in normals.iter_mut().enumerate() {
+            self.point[i] ^= self.directions[i][bit];
+            let u = ((self.point[i] ^ self.shift[i]) as f64 + 0.5) / (1u64 << BITS) as f64;
+            *normal = norm_inv_cdf(u);
+        }
+    }
+}
+
+// BrownianBridge turns independent normals into a Brownian path on a time grid, filling
+// in the end point first and then halving the intervals, so that the first (best
+// distributed) dimensions of a quasi-random point drive the largest scale of the path
+#[derive(Debug, Clone)]
+pub(crate) struct BrownianBridge {
+    // (left, middle, right, left weight, right weight, standard deviation)
+    order: Vec<(usize, usize, usize, f64, f64, f64)>,
+    end_deviation: f64,
+}
+
+impl BrownianBridge {
+    // new builds the bridge over the increasing times of the steps, today excluded
+    pub(crate) fn new(times: &[f64]) -> BrownianBridge {
+        let steps = times.len();
+        let time = |i: usize| if i == 0 { 0.0 } else { times[i - 1] };
+        let mut order = Vec::with_capacity(steps.saturating_sub(1));
+        let mut intervals = std::collections::VecDeque::new();
+        intervals.push_back((0, steps));
+        while let Some((left, right)) = intervals.pop_front() {
+            if right - left < 2 {
+                continue;
+            }
+            let middle = (left + right) / 2;
+            let before = time(middle) - time(left);
+            let after = time(right) - time(middle);
+            let width = before + after;
+            order.push((
+                left,
+                middle,
+                right,
+                after / width,
+                before / width,
+                (before * after / width).sqrt(),
+            ));
+            intervals.push_back((left, middle));
+            intervals.push_back((middle, right));
+        }
+        BrownianBridge {
+            order,
+            end_deviation: time(steps).sqrt(),
+        }
+    }
+
+    // increments writes the Brownian increments of every step given one normal per step
+    pub(crate) fn increments(&self, normals: &[f64], increments: &mut [f64]) {
+        let steps = increments.len();
+        let mut path = vec![0.0; steps + 1];
+        path[steps] = self.end_deviation * normals[0];
+        for (&(left, middle, right, wl, wr, deviation), &z) in
+            self.order.iter().zip(normals[1..].iter())
+        {
+            path[middle] = wl * path[left] + wr * path[right] + deviation * z;
+        }
+        for (i, increment) in increments.iter_mut().enumerate() {
+            *increment = path[i + 1] - path[i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xoshiro_is_reproducible_and_uniform() {
+        let mut a = Xoshiro256::new(42);
+        let mut b = Xoshiro256::new(42);
+        let mut c = Xoshiro256::new(43);
+        assert_eq!(a.next_u64(), b.next_u64());
+        assert_ne!(a.next_u64(), c.next_u64());
+
+        let n = 100_000;
+        let mean = (0..n).map(|_| a.uniform()).sum::<f64>() / n as f64;
+        assert!((mean - 0.5).abs() < 5e-3);
+    }
+
+    #[test]
+    fn sobol_first_points() {
+        // the first two dimensions after the origin are (1/2, 1/2), (3/4, 1/4), (1/4, 3/4)
+        let mut sobol = Sobol::new(2).unwrap();
+        let mut normals = [0.0; 2];
+        for expected in [[0.5, 0.5], [0.75, 0.25], [0.25, 0.75]] {
+            sobol.next_normals(&mut normals);
+            for (z, u) in normals.iter().zip(expected.iter()) {
+                let u = u + 0.5 / (1u64 << BITS) as f64;
+                assert!((z - norm_inv_cdf(u)).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_is_balanced_in_every_dimension() {
+        // the first 2^k points put exactly one point in every interval of width 2^-k
+        let mut sobol = Sobol::new(MAX_DIMENSIONS).unwrap();
+        let mut normals = [0.0; MAX_DIMENSIONS];
+        let mut counts = vec![[0; 64]; MAX_DIMENSIONS];
+        for _ in 0..63 {
+            sobol.next_normals(&mut normals);
+            for (count, z) in counts.iter_mut().zip(normals.iter()) {
+                count[(crate::distributions::norm_cdf(*z) * 64.0) as usize] += 1;
+            }
+        }
+        for count in counts {
+            // the origin is skipped, so only the first interval is empty
+            assert_eq!(count[0], 0);
+            assert!(count[1..].iter().all(|&c| c == 1));
+        }
+        assert_eq!(
+            Sobol::new(MAX_DIMENSIONS + 1).unwrap_err(),
+            MathError::TooManyDimensions(MAX_DIMENSIONS + 1)
+        );
+    }
+
+    #[test]
+    fn brownian_bridge_has_independent_increments() {
+        // equally spaced and uneven steps
+        let grids = [
+            [0.2, 0.4, 0.6, 0.8, 1.0, 1.2, 1.4],
+            [0.05, 0.1, 0.5, 0.6, 1.0, 1.3, 1.4],
+        ];
+        for times in grids.iter() {
+            let bridge = BrownianBridge::new(times);
+            let mut rng = Xoshiro256::new(7);
+            let mut normals = [0.0; 7];
+            let mut increments = [0.0; 7];
+            let mut second_moments = [[0.0; 7]; 7];
+            let n = 50_000;
+            for _ in 0..n {
+                normals.iter_mut().for_each(|z| *z = rng.normal());
+                bridge.increments(&normals, &mut increments);
+                for i in 0..7 {
+                    for j in 0..7 {
+                        second_moments[i][j] += increments[i] * increments[j] / n as f64;
+                    }
+                }
+            }
+            for (i, row) in second_moments.iter().enumerate() {
+                for (j, moment) in row.iter().enumerate() {
+                    let expected = match i {
+                        _ if i != j => 0.0,
+                        0 => times[0],
+                        _ => times[i] - times[i - 1],
+                    };
+                    assert!((moment - expected).abs() < 1e-2, "{} {} {}", i, j, moment);
+                }
+            }
+        }
+    }
+}
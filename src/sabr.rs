@@ -0,0 +1,532 @@
+use crate::bachelier::BachelierModel;
+use crate::black76::Black76Model;
+use crate::solver::levenberg_marquardt;
+use crate::{check_finite, BlackScholesModel, MathError, MathResult, OptionKind, VolQuote};
+
+// Expansion selects the lognormal implied volatility formula of the SABR model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expansion {
+    Hagan, // Hagan, Kumar, Lesniewski and Woodward (2002)
+    Obloj, // Obloj (2008), which fixes the leading term of Hagan's far from the money
+}
+
+// SabrParameters describe the forward and volatility processes
+// dF = a F^beta dW, da = nu a dZ, with a(0) = alpha and d<W, Z> = rho dt
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SabrParameters {
+    pub alpha: f64, // initial volatility
+    pub beta: f64,  // elasticity of the forward, between 0 (normal) and 1 (lognormal)
+    pub rho: f64,   // correlation between forward and volatility
+    pub nu: f64,    // volatility of volatility
+}
+
+// SabrModel is the smile of a single expiration under the SABR stochastic volatility
+// model, expressed through the asymptotic implied volatility expansions which are then
+// priced with Black's or Bachelier's formula
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SabrModel {
+    forward: f64,               // forward price of the expiration ($$$ per unit)
+    time_to_expire: f64,        // time to expiration (% of year)
+    parameters: SabrParameters, // forward and volatility processes
+    expansion: Expansion,       // lognormal implied volatility formula
+}
+
+// SabrFit is the outcome of the calibration of one expiration
+#[derive(Debug, Clone, PartialEq)]
+pub struct SabrFit {
+    pub model: SabrModel,    // fitted smile
+    pub residuals: Vec<f64>, // model minus quoted volatility, in the order of the quotes
+}
+
+impl SabrModel {
+    // new creates the smile with Hagan's expansion, use with_expansion to switch to
+    // Obloj's
+    pub fn new(forward: f64, time_to_expire: f64, parameters: SabrParameters) -> SabrModel {
+        SabrModel {
+            forward,
+            time_to_expire,
+            parameters,
+            expansion: Expansion::Hagan,
+        }
+    }
+
+    // try_new creates the model like new does, but rejects a non-positive forward, a
+    // negative time to expiration and parameters outside their domain
+    pub fn try_new(
+        forward: f64,
+        time_to_expire: f64,
+        parameters: SabrParameters,
+    ) -> MathResult<SabrModel> {
+        let p = parameters;
+        check_finite("forward", forward)?;
+        check_finite("time_to_expire", time_to_expire)?;
+        for &(name, value) in &[
+            ("alpha", p.alpha),
+            ("beta", p.beta),
+            ("rho", p.rho),
+            ("nu", p.nu),
+        ] {
+            check_finite(name, value)?;
+        }
+        if forward <= 0.0 {
+            return Err(MathError::NonPositiveStock(forward));
+        }
+        if time_to_expire < 0.0 {
+            return Err(MathError::NegativeTimeToExpire(time_to_expire));
+        }
+        if p.alpha <= 0.0 {
+            return Err(MathError::ParameterOutOfBounds("alpha", p.alpha));
+        }
+        if !(0.0..=1.0).contains(&p.beta) {
+            return Err(MathError::ParameterOutOfBounds("beta", p.beta));
+        }
+        if p.rho.abs() >= 1.0 {
+            return Err(MathError::ParameterOutOfBounds("rho", p.rho));
+        }
+        if p.nu < 0.0 {
+            return Err(MathError::ParameterOutOfBounds("nu", p.nu));
+        }
+        Ok(SabrModel::new(forward, time_to_expire, parameters))
+    }
+
+    pub fn with_expansion(mut self, expansion: Expansion) -> SabrModel {
+        self.expansion = expansion;
+        self
+    }
+
+    pub fn forward(&self) -> f64 {
+        self.forward
+    }
+
+    pub fn time_to_expire(&self) -> f64 {
+        self.time_to_expire
+    }
+
+    pub fn parameters(&self) -> SabrParameters {
+        self.parameters
+    }
+
+    // lognormal_volatility is the Black implied volatility (% p.a.) at the strike
+    pub fn lognormal_volatility(&self, strike: f64) -> MathResult {
+        if strike <= 0.0 {
+            return Err(MathError::NonPositiveStrike(strike));
+        }
+        let SabrParameters {
+            alpha,
+            beta,
+            rho,
+            nu,
+        } = self.parameters;
+        let c = 1.0 - beta;
+        let y = (self.forward / strike).ln();
+        let scale = (self.forward * strike).powf(c / 2.0);
+        let correction = 1.0
+            + (c * c * alpha * alpha / (24.0 * scale * scale)
+                + rho * alpha * beta * nu / (4.0 * scale)
+                + (2.0 - 3.0 * rho * rho) * nu * nu / 24.0)
+                * self.time_to_expire;
+
+        let leading = match self.expansion {
+            Expansion::Hagan => {
+                let z = nu / alpha * scale * y;
+                let series = 1.0 + (c * y).powi(2) / 24.0 + (c * y).powi(4) / 1920.0;
+                alpha / (scale * series) * z_over_x(z, rho)
+            }
+            Expansion::Obloj => {
+                // integral of dx / x^beta from the strike to the forward
+                let integral = strike.powf(c) * y * relative_growth(c * y);
+                let z = nu / alpha * integral;
+                alpha / (strike.powf(c) * relative_growth(c * y)) * z_over_x(z, rho)
+            }
+        };
+        Ok(leading * correction)
+    }
+
+    // normal_volatility is the Bachelier implied volatility ($$$ per unit p.a.) at the
+    // strike, from Hagan's normal expansion
+    pub fn normal_volatility(&self, strike: f64) -> MathResult {
+        if strike <= 0.0 {
+            return Err(MathError::NonPositiveStrike(strike));
+        }
+        let SabrParameters {
+            alpha,
+            beta,
+            rho,
+            nu,
+        } = self.parameters;
+        let c = 1.0 - beta;
+        let y = (self.forward / strike).ln();
+        let mid = (self.forward * strike).sqrt();
+        // (F - K) (1 - beta) / (F^(1 - beta) - K^(1 - beta)) without the cancellation
+        let ratio = strike.powf(beta) * relative_growth(y) / relative_growth(c * y);
+        let zeta = nu / alpha * strike * y * relative_growth(y) / mid.powf(beta);
+        let correction = 1.0
+            + (-beta * (2.0 - beta) * alpha * alpha / (24.0 * mid.powf(2.0 * c))
+                + rho * alpha * beta * nu / (4.0 * mid.powf(c))
+                + (2.0 - 3.0 * rho * rho) * nu * nu / 24.0)
+                * self.time_to_expire;
+        Ok(alpha * ratio * z_over_x(zeta, rho) * correction)
+    }
+
+    // black76 prices the option at the strike with Black's formula and the lognormal
+    // volatility of the smile
+    pub fn black76(
+        &self,
+        opt: OptionKind,
+        strike: f64,
+        interest_rate: f64,
+    ) -> MathResult<Black76Model> {
+        Black76Model::try_new(
+            opt,
+            strike,
+            self.forward,
+            interest_rate,
+            self.lognormal_volatility(strike)?,
+            self.time_to_expire,
+        )
+    }
+
+    // black_scholes prices the option on the stock with the lognormal volatility of the
+    // smile, the forward of the smile is expected to be the forward of the stock
+    pub fn black_scholes(
+        &self,
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        dividend: Option<f64>,
+    ) -> MathResult<BlackScholesModel> {
+        BlackScholesModel::try_new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            self.lognormal_volatility(strike)?,
+            self.time_to_expire,
+            dividend,
+        )
+    }
+
+    // bachelier prices the option at the strike with Bachelier's formula and the normal
+    // volatility of the smile
+    pub fn bachelier(
+        &self,
+        opt: OptionKind,
+        strike: f64,
+        interest_rate: f64,
+    ) -> MathResult<BachelierModel> {
+        BachelierModel::try_new(
+            opt,
+            strike,
+            self.forward,
+            interest_rate,
+            self.normal_volatility(strike)?,
+            self.time_to_expire,
+        )
+    }
+}
+
+// calibrate fits alpha, rho and nu of every expiration in the quotes for the fixed beta,
+// with the forward of each expiration given by the forward function of the time to
+// expiration, the fits are returned in the order of increasing expiration
+//
+// The residuals are the lognormal volatility errors of the given expansion, the fit
+// starts from the at-the-money level with rho = 0 and nu = 0.5
+pub fn calibrate<F>(
+    forward: F,
+    beta: f64,
+    quotes: &[VolQuote],
+    expansion: Expansion,
+) -> MathResult<Vec<SabrFit>>
+where
+    F: Fn(f64) -> f64,
+{
+    let mut expirations: Vec<f64> = Vec::new();
+    for q in quotes {
+        check_finite("strike", q.strike)?;
+        check_finite("volatility", q.volatility)?;
+        if q.strike <= 0.0 {
+            return Err(MathError::NonPositiveStrike(q.strike));
+        }
+        if q.volatility <= 0.0 {
+            return Err(MathError::NonPositiveVolatility(q.volatility));
+        }
+        if !expirations.contains(&q.time_to_expire) {
+            expirations.push(q.time_to_expire);
+        }
+    }
+    expirations.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    expirations
+        .iter()
+        .map(|&time_to_expire| {
+            let smile: Vec<&VolQuote> = quotes
+                .iter()
+                .filter(|q| q.time_to_expire == time_to_expire)
+                .collect();
+            let forward = forward(time_to_expire);
+            let model = |x: &[f64]| {
+                let parameters = SabrParameters {
+                    alpha: x[0].exp(),
+                    beta,
+                    rho: x[1].tanh(),
+                    nu: x[2].exp(),
+                };
+                SabrModel::try_new(forward, time_to_expire, parameters)
+                    .map(|m| m.with_expansion(expansion))
+            };
+            let residuals = |x: &[f64]| {
+                let model = model(x)?;
+                smile
+                    .iter()
+                    .map(|q| Ok(model.lognormal_volatility(q.strike)? - q.volatility))
+                    .collect::<MathResult<Vec<f64>>>()
+            };
+
+            // the quote closest to the money sets the level of alpha
+            let atm = smile.iter().fold(smile[0], |best, q| {
+                if (q.strike / forward).ln().abs() < (best.strike / forward).ln().abs() {
+                    q
+                } else {
+                    best
+                }
+            });
+            let start = [
+                (atm.volatility * forward.powf(1.0 - beta)).ln(),
+                0.0,
+                0.5f64.ln(),
+            ];
+            let x = levenberg_marquardt(residuals, &start)?;
+            Ok(SabrFit {
+                model: model(&x)?,
+                residuals: residuals(&x)?,
+            })
+        })
+        .collect()
+}
+
+// z_over_x is z / x(z) with x(z) = ln((sqrt(1 - 2 rho z + z^2) + z - rho) / (1 - rho)),
+// which tends to 1 at the money
+fn z_over_x(z: f64, rho: f64) -> f64 {
+    if z.abs() < 1e-6 {
+        return 1.0 - 0.5 * rho * z + (2.0 - 3.0 * rho * rho) * z * z / 12.0;
+    }
+    let root = (1.0 - 2.0 * rho * z + z * z).sqrt();
+    // for negative z the numerator cancels, its conjugate form doesn't
+    let x = if z > rho {
+        ((root + z - rho) / (1.0 - rho)).ln()
+    } else {
+        ((1.0 + rho) / (root - z + rho)).ln()
+    };
+    z / x
+}
+
+// relative_growth is (exp(x) - 1) / x, which tends to 1 as x goes to 0
+fn relative_growth(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        x.exp_m1() / x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bachelier::lognormal_to_normal_volatility;
+
+    const PARAMETERS: SabrParameters = SabrParameters {
+        alpha: 0.036,
+        beta: 0.5,
+        rho: -0.25,
+        nu: 0.35,
+    };
+
+    fn smile() -> SabrModel {
+        SabrModel::new(0.03, 2.0, PARAMETERS)
+    }
+
+    #[test]
+    fn at_the_money_volatility() {
+        let SabrParameters {
+            alpha,
+            beta,
+            rho,
+            nu,
+        } = PARAMETERS;
+        let level = 0.03f64.powf(1.0 - beta);
+        let expected = alpha / level
+            * (1.0
+                + ((1.0 - beta).powi(2) * alpha * alpha / (24.0 * level * level)
+                    + rho * alpha * beta * nu / (4.0 * level)
+                    + (2.0 - 3.0 * rho * rho) * nu * nu / 24.0)
+                    * 2.0);
+        for expansion in [Expansion::Hagan, Expansion::Obloj] {
+            let model = smile().with_expansion(expansion);
+            for strike in [0.03, 0.03 * (1.0 + 1e-9)] {
+                let result = model.lognormal_volatility(strike).unwrap();
+                assert!(
+                    (result - expected).abs() < 1e-10,
+                    "{} is not {}",
+                    result,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_smiles() {
+        // without volatility of volatility beta = 1 is Black's and beta = 0 Bachelier's
+        // model
+        let flat = SabrParameters {
+            alpha: 0.2,
+            beta: 1.0,
+            rho: 0.3,
+            nu: 0.0,
+        };
+        let normal = SabrParameters {
+            alpha: 0.01,
+            beta: 0.0,
+            ..flat
+        };
+        for strike in [0.01, 0.03, 0.08] {
+            let model = SabrModel::new(0.03, 1.5, flat);
+            assert!((model.lognormal_volatility(strike).unwrap() - 0.2).abs() < 1e-15);
+            let model = SabrModel::new(0.03, 1.5, normal);
+            assert!((model.normal_volatility(strike).unwrap() - 0.01).abs() < 1e-15);
+        }
+    }
+
+    #[test]
+    fn expansions_agree_for_lognormal_forward() {
+        let parameters = SabrParameters {
+            beta: 1.0,
+            alpha: 0.25,
+            ..PARAMETERS
+        };
+        let hagan = SabrModel::new(0.03, 2.0, parameters);
+        let obloj = hagan.with_expansion(Expansion::Obloj);
+        for strike in [0.01, 0.02, 0.05, 0.1] {
+            let expected = hagan.lognormal_volatility(strike).unwrap();
+            assert!((obloj.lognormal_volatility(strike).unwrap() - expected).abs() < 1e-14);
+        }
+
+        // with beta < 1 they part away from the money
+        let hagan = smile();
+        let obloj = hagan.with_expansion(Expansion::Obloj);
+        let difference = |strike| {
+            (hagan.lognormal_volatility(strike).unwrap()
+                - obloj.lognormal_volatility(strike).unwrap())
+            .abs()
+        };
+        assert!(difference(0.029) < 1e-5);
+        assert!(difference(0.005) > 1e-3);
+    }
+
+    #[test]
+    fn normal_matches_converted_lognormal() {
+        // both are expansions in the time to expiration, the terms they neglect grow in
+        // the wings
+        let model = smile();
+        for strike in [0.025, 0.03, 0.04] {
+            let lognormal = model.lognormal_volatility(strike).unwrap();
+            let expected = lognormal_to_normal_volatility(0.03, strike, 2.0, lognormal).unwrap();
+            let result = model.normal_volatility(strike).unwrap();
+            assert!(
+                (result / expected - 1.0).abs() < 1e-3,
+                "{} is not {}",
+                result,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn prices_with_smile_volatility() {
+        let model = smile();
+        let strike = 0.035;
+        let volatility = model.lognormal_volatility(strike).unwrap();
+        let black = model.black76(OptionKind::Call, strike, 0.02).unwrap();
+        let expected = Black76Model::new(OptionKind::Call, strike, 0.03, 0.02, volatility, 2.0);
+        assert_eq!(black, expected);
+
+        let spot = model
+            .black_scholes(OptionKind::Put, strike, 0.03, 0.02, Some(0.02))
+            .unwrap();
+        let put = Black76Model::new(OptionKind::Put, strike, 0.03, 0.02, volatility, 2.0);
+        assert!((spot.price().unwrap() - put.price().unwrap()).abs() < 1e-15);
+
+        // the normal expansion prices close to the lognormal one
+        let normal = model.bachelier(OptionKind::Call, strike, 0.02).unwrap();
+        let price = black.price().unwrap();
+        assert!((normal.price().unwrap() / price - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn calibration_recovers_parameters() {
+        let forward = |t: f64| 0.03 + 0.002 * t;
+        let truths = [
+            (
+                0.5,
+                SabrParameters {
+                    alpha: 0.04,
+                    rho: -0.4,
+                    nu: 0.6,
+                    ..PARAMETERS
+                },
+            ),
+            (2.0, PARAMETERS),
+        ];
+        let mut quotes = Vec::new();
+        // expirations on purpose out of order
+        for &(time_to_expire, parameters) in truths.iter().rev() {
+            let model = SabrModel::new(forward(time_to_expire), time_to_expire, parameters)
+                .with_expansion(Expansion::Obloj);
+            for &strike in &[0.01, 0.02, 0.025, 0.03, 0.035, 0.045, 0.06] {
+                quotes.push(VolQuote {
+                    strike,
+                    time_to_expire,
+                    volatility: model.lognormal_volatility(strike).unwrap(),
+                });
+            }
+        }
+
+        let fits = calibrate(forward, 0.5, &quotes, Expansion::Obloj).unwrap();
+        assert_eq!(fits.len(), 2);
+        for (fit, &(time_to_expire, truth)) in fits.iter().zip(&truths) {
+            assert_eq!(fit.model.time_to_expire(), time_to_expire);
+            assert_eq!(fit.model.forward(), forward(time_to_expire));
+            let result = fit.model.parameters();
+            assert!((result.alpha - truth.alpha).abs() < 1e-6);
+            assert!((result.rho - truth.rho).abs() < 1e-5);
+            assert!((result.nu - truth.nu).abs() < 1e-5);
+            assert_eq!(fit.residuals.len(), 7);
+            assert!(fit.residuals.iter().all(|r| r.abs() < 1e-8));
+        }
+    }
+
+    #[test]
+    fn err_with_invalid_parameters() {
+        let invalid = SabrParameters {
+            beta: 1.5,
+            ..PARAMETERS
+        };
+        assert_eq!(
+            SabrModel::try_new(0.03, 1.0, invalid),
+            Err(MathError::ParameterOutOfBounds("beta", 1.5))
+        );
+        let invalid = SabrParameters {
+            rho: 1.0,
+            ..PARAMETERS
+        };
+        assert_eq!(
+            SabrModel::try_new(0.03, 1.0, invalid),
+            Err(MathError::ParameterOutOfBounds("rho", 1.0))
+        );
+        assert_eq!(
+            smile().lognormal_volatility(0.0),
+            Err(MathError::NonPositiveStrike(0.0))
+        );
+    }
+}
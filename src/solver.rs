@@ -0,0 +1,288 @@
+use crate::linalg::solve;
+use crate::{MathError, MathResult};
+
+const TOLERANCE: f64 = 1e-14;
+const MAX_ITERATIONS: usize = 200;
+
+// newton_bisection finds a root of f within [lower, upper] starting from the guess
+//
+// f returns the function value together with its derivative. Newton steps are taken
+// while they stay inside the bracket of the root, otherwise the bracket is bisected,
+// so the search converges as long as f changes sign between lower and upper
+pub(crate) fn newton_bisection<F>(f: F, lower: f64, upper: f64, guess: f64) -> MathResult
+where
+    F: Fn(f64) -> MathResult<(f64, f64)>,
+{
+    let (f_lower, _) = f(lower)?;
+    if f_lower == 0.0 {
+        return Ok(lower);
+    }
+    let (f_upper, _) = f(upper)?;
+    if f_upper == 0.0 {
+        return Ok(upper);
+    }
+    if f_lower.signum() == f_upper.signum() {
+        return Err(MathError::RootNotBracketed(lower, upper));
+    }
+
+    // keep the bracket oriented so that f(negative) < 0 < f(positive)
+    let (mut negative, mut positive) = if f_lower < 0.0 {
+        (lower, upper)
+    } else {
+        (upper, lower)
+    };
+    let mut x = if (guess - lower) * (guess - upper) < 0.0 {
+        guess
+    } else {
+        0.5 * (lower + upper)
+    };
+
+    for _ in 0..MAX_ITERATIONS {
+        let (fx, dfx) = f(x)?;
+        if fx == 0.0 {
+            return Ok(x);
+        }
+        if fx < 0.0 {
+            negative = x;
+        } else {
+            positive = x;
+        }
+
+        let newton = x - fx / dfx;
+        let next = if dfx != 0.0 && (newton - negative) * (newton - positive) < 0.0 {
+            newton
+        } else {
+            0.5 * (negative + positive)
+        };
+        if (next - x).abs() <= TOLERANCE * (1.0 + x.abs())
+            || (positive - negative).abs() <= TOLERANCE * (1.0 + x.abs())
+        {
+            return Ok(next);
+        }
+        x = next;
+    }
+    Err(MathError::NoConvergence(MAX_ITERATIONS))
+}
+
+// brent finds a root of f within [lower, upper] with Brent's method, combining inverse
+// quadratic interpolation and secant steps with bisection, f has to change sign
+pub(crate) fn brent<F>(f: F, lower: f64, upper: f64) -> MathResult
+where
+    F: Fn(f64) -> MathResult,
+{
+    let (mut a, mut b) = (lower, upper);
+    let (mut fa, mut fb) = (f(a)?, f(b)?);
+    if fa == 0.0 {
+        return Ok(a);
+    }
+    if fb == 0.0 {
+        return Ok(b);
+    }
+    if fa.signum() == fb.signum() {
+        return Err(MathError::RootNotBracketed(lower, upper));
+    }
+
+    let (mut c, mut fc) = (b, fb);
+    let (mut step, mut previous_step) = (b - a, b - a);
+    for _ in 0..MAX_ITERATIONS {
+        if fb.signum() == fc.signum() {
+            c = a;
+            fc = fa;
+            step = b - a;
+            previous_step = step;
+        }
+        if fc.abs() < fb.abs() {
+            a = b;
+            b = c;
+            c = a;
+            fa = fb;
+            fb = fc;
+            fc = fa;
+        }
+
+        let tolerance = 2.0 * f64::EPSILON * b.abs() + 0.5 * TOLERANCE;
+        let midpoint = 0.5 * (c - b);
+        if midpoint.abs() <= tolerance || fb == 0.0 {
+            return Ok(b);
+        }
+
+        if previous_step.abs() >= tolerance && fa.abs() > fb.abs() {
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * midpoint * s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (
+                    s * (2.0 * midpoint * q * (q - r) - (b - a) * (r - 1.0)),
+                    (q - 1.0) * (r - 1.0) * (s - 1.0),
+                )
+            };
+            if p > 0.0 {
+                q = -q;
+            } else {
+                p = -p;
+            }
+            if 2.0 * p < (3.0 * midpoint * q - (tolerance * q).abs()).min((previous_step * q).abs())
+            {
+                previous_step = step;
+                step = p / q;
+            } else {
+                step = midpoint;
+                previous_step = midpoint;
+            }
+        } else {
+            step = midpoint;
+            previous_step = midpoint;
+        }
+
+        a = b;
+        fa = fb;
+        b += if step.abs() > tolerance {
+            step
+        } else {
+            tolerance.copysign(midpoint)
+        };
+        fb = f(b)?;
+    }
+    Err(MathError::NoConvergence(MAX_ITERATIONS))
+}
+
+// levenberg_marquardt minimises the sum of squared residuals starting from the initial
+// parameters, the Jacobian is taken by forward differences
+//
+// Trial points where the residuals cannot be calculated are treated like steps which
+// increase the error, and the search stops once neither the parameters nor the error
+// move anymore
+pub(crate) fn levenberg_marquardt<F>(residuals: F, initial: &[f64]) -> MathResult<Vec<f64>>
+where
+    F: Fn(&[f64]) -> MathResult<Vec<f64>>,
+{
+    let squares = |r: &[f64]| r.iter().map(|v| v * v).sum::<f64>();
+    let mut x = initial.to_vec();
+    let mut r = residuals(&x)?;
+    let mut error = squares(&r);
+    let mut damping = 1e-3;
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut jacobian = Vec::with_capacity(x.len());
+        for i in 0..x.len() {
+            let h = 1e-7 * x[i].abs().max(1.0);
+            let mut bumped = x.clone();
+            bumped[i] += h;
+            let column: Vec<f64> = residuals(&bumped)?
+                .iter()
+                .zip(&r)
+                .map(|(up, base)| (up - base) / h)
+                .collect();
+            jacobian.push(column);
+        }
+        let n = x.len();
+        let gradient: Vec<f64> = jacobian
+            .iter()
+            .map(|column| column.iter().zip(&r).map(|(j, v)| j * v).sum())
+            .collect();
+        let curvature: Vec<Vec<f64>> = jacobian
+            .iter()
+            .map(|a| {
+                jacobian
+                    .iter()
+                    .map(|b| a.iter().zip(b).map(|(u, v)| u * v).sum())
+                    .collect()
+            })
+            .collect();
+
+        loop {
+            let mut damped = curvature.clone();
+            for (i, row) in damped.iter_mut().enumerate() {
+                row[i] += damping * curvature[i][i].max(f64::EPSILON);
+            }
+            let step = solve(damped, gradient.iter().map(|g| -g).collect());
+            let trial: Option<Vec<f64>> =
+                step.map(|step| x.iter().zip(&step).map(|(x, s)| x + s).collect());
+            let accepted = trial.and_then(|trial| match residuals(&trial) {
+                Ok(trial_r) if squares(&trial_r) < error => Some((trial, trial_r)),
+                _ => None,
+            });
+
+            match accepted {
+                Some((trial, trial_r)) => {
+                    let trial_error = squares(&trial_r);
+                    let settled = (0..n)
+                        .all(|i| (trial[i] - x[i]).abs() <= TOLERANCE.sqrt() * (1.0 + x[i].abs()))
+                        || error - trial_error <= TOLERANCE * error;
+                    x = trial;
+                    r = trial_r;
+                    error = trial_error;
+                    damping = (damping / 10.0).max(1e-12);
+                    if settled {
+                        return Ok(x);
+                    }
+                    break;
+                }
+                None if damping > 1e12 => return Ok(x),
+                None => damping *= 10.0,
+            }
+        }
+    }
+    Err(MathError::NoConvergence(MAX_ITERATIONS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newton_bisection_finds_root() {
+        let result = newton_bisection(|x| Ok((x * x - 2.0, 2.0 * x)), 0.0, 2.0, 1.0).unwrap();
+        assert!((result - 2f64.sqrt()).abs() < 1e-14);
+    }
+
+    #[test]
+    fn newton_bisection_falls_back_to_bisection() {
+        // a flat derivative sends every Newton step out of the bracket
+        let result = newton_bisection(|x| Ok((x.powi(3) - 1.0, 0.0)), -5.0, 5.0, 4.0).unwrap();
+        assert!((result - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn err_newton_bisection_without_sign_change() {
+        let result = newton_bisection(|x| Ok((x * x + 1.0, 2.0 * x)), -1.0, 1.0, 0.5);
+        assert_eq!(result, Err(MathError::RootNotBracketed(-1.0, 1.0)));
+    }
+
+    #[test]
+    fn brent_finds_root() {
+        let result = brent(|x| Ok(x.cos() - x), 0.0, 1.0).unwrap();
+        assert!((result - 0.7390851332151607).abs() < 1e-14);
+    }
+
+    #[test]
+    fn err_brent_without_sign_change() {
+        let result = brent(|x| Ok(x * x + 1.0), -1.0, 1.0);
+        assert_eq!(result, Err(MathError::RootNotBracketed(-1.0, 1.0)));
+    }
+
+    #[test]
+    fn levenberg_marquardt_fits_rosenbrock() {
+        // residuals of the Rosenbrock function, minimal at (1, 1)
+        let residuals = |x: &[f64]| Ok(vec![10.0 * (x[1] - x[0] * x[0]), 1.0 - x[0]]);
+        let result = levenberg_marquardt(residuals, &[-1.2, 1.0]).unwrap();
+        assert!((result[0] - 1.0).abs() < 1e-6 && (result[1] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn levenberg_marquardt_fits_exponential_decay() {
+        let data: Vec<(f64, f64)> = (0..10)
+            .map(|i| (i as f64 / 2.0, 3.0 * (-0.7 * i as f64 / 2.0).exp()))
+            .collect();
+        let residuals = |p: &[f64]| {
+            Ok(data
+                .iter()
+                .map(|&(t, y)| p[0] * (-p[1] * t).exp() - y)
+                .collect())
+        };
+        let result = levenberg_marquardt(residuals, &[1.0, 0.1]).unwrap();
+        assert!((result[0] - 3.0).abs() < 1e-8 && (result[1] - 0.7).abs() < 1e-8);
+    }
+}
@@ -0,0 +1,486 @@
+use crate::{payoff, solver, BlackScholesModel, MathResult, OptionKind, Position};
+
+// number of samples taken between two consecutive strikes when looking for break-even
+// points and extremes, the profit is only curved between strikes for calendar legs
+const SAMPLES_PER_INTERVAL: usize = 64;
+
+// Leg is a single option position of a strategy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Leg {
+    position: Position,           // long or short
+    opt: OptionKind,              // option type (call or put)
+    strike: f64,                  // strike price ($$$ per share)
+    premium: f64,                 // premium paid or received ($$$ per share)
+    quantity: f64,                // number of options
+    remaining: Option<Remaining>, // life after the strategy expires (calendar legs)
+}
+
+// Remaining describes how a leg that outlives the strategy is valued at its expiration
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Remaining {
+    interest_rate: f64,    // continuously compounded risk-free interest rate (% p.a.)
+    volatility: f64,       // volatility (% p.a.)
+    time_to_expire: f64,   // time left after the strategy expires (% of year)
+    dividend: Option<f64>, // continuously compounded dividend yield (% p.a.)
+}
+
+impl Leg {
+    pub fn new(
+        position: Position,
+        opt: OptionKind,
+        strike: f64,
+        premium: f64,
+        quantity: f64,
+    ) -> Leg {
+        Leg {
+            position,
+            opt,
+            strike,
+            premium,
+            quantity,
+            remaining: None,
+        }
+    }
+
+    // outliving marks the leg as expiring time_to_expire after the rest of the strategy,
+    // it's then valued with BlackScholesModel instead of its intrinsic value
+    pub fn outliving(
+        mut self,
+        interest_rate: f64,
+        volatility: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+    ) -> Leg {
+        self.remaining = Some(Remaining {
+            interest_rate,
+            volatility,
+            time_to_expire,
+            dividend,
+        });
+        self
+    }
+
+    // profit calculates profit/loss of the leg when the strategy expires with the
+    // underlying at stock ($$$ per share)
+    fn profit(&self, stock: f64) -> MathResult {
+        let value = match self.remaining {
+            Some(r) => BlackScholesModel::new(
+                self.opt,
+                self.strike,
+                stock,
+                r.interest_rate,
+                r.volatility,
+                r.time_to_expire,
+                r.dividend,
+            )
+            .price()?,
+            None => payoff(
+                Position::Long,
+                self.opt,
+                self.strike,
+                stock,
+                Some(self.premium),
+            )?,
+        };
+        Ok(self.sign() * self.quantity * (value - self.premium))
+    }
+
+    fn sign(&self) -> f64 {
+        match self.position {
+            Position::Long => 1.0,
+            Position::Short => -1.0,
+        }
+    }
+}
+
+// Shares is a position in the underlying held together with the options
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Shares {
+    position: Position, // long or short
+    quantity: f64,      // number of shares
+    price: f64,         // price paid or received ($$$ per share)
+}
+
+// Strategy is a combination of option legs and optionally shares of the underlying
+// which expire together
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Strategy {
+    legs: Vec<Leg>,
+    shares: Option<Shares>,
+}
+
+impl Strategy {
+    pub fn new(legs: Vec<Leg>) -> Strategy {
+        Strategy { legs, shares: None }
+    }
+
+    pub fn with_leg(mut self, leg: Leg) -> Strategy {
+        self.legs.push(leg);
+        self
+    }
+
+    pub fn with_shares(mut self, position: Position, quantity: f64, price: f64) -> Strategy {
+        self.shares = Some(Shares {
+            position,
+            quantity,
+            price,
+        });
+        self
+    }
+
+    // vertical_spread buys one option and sells another of the same type, e.g. a bull
+    // call spread when the long strike is below the short one
+    pub fn vertical_spread(
+        opt: OptionKind,
+        long_strike: f64,
+        long_premium: f64,
+        short_strike: f64,
+        short_premium: f64,
+    ) -> Strategy {
+        Strategy::new(vec![
+            Leg::new(Position::Long, opt, long_strike, long_premium, 1.0),
+            Leg::new(Position::Short, opt, short_strike, short_premium, 1.0),
+        ])
+    }
+
+    // straddle holds a call and a put with the same strike
+    pub fn straddle(
+        position: Position,
+        strike: f64,
+        call_premium: f64,
+        put_premium: f64,
+    ) -> Strategy {
+        Strategy::new(vec![
+            Leg::new(position, OptionKind::Call, strike, call_premium, 1.0),
+            Leg::new(position, OptionKind::Put, strike, put_premium, 1.0),
+        ])
+    }
+
+    // strangle holds an out-of-the-money put and an out-of-the-money call
+    pub fn strangle(
+        position: Position,
+        put_strike: f64,
+        put_premium: f64,
+        call_strike: f64,
+        call_premium: f64,
+    ) -> Strategy {
+        Strategy::new(vec![
+            Leg::new(position, OptionKind::Put, put_strike, put_premium, 1.0),
+            Leg::new(position, OptionKind::Call, call_strike, call_premium, 1.0),
+        ])
+    }
+
+    // butterfly buys the lower and upper wings and sells two options at the middle strike
+    pub fn butterfly(
+        opt: OptionKind,
+        lower_strike: f64,
+        lower_premium: f64,
+        middle_strike: f64,
+        middle_premium: f64,
+        upper_strike: f64,
+        upper_premium: f64,
+    ) -> Strategy {
+        Strategy::new(vec![
+            Leg::new(Position::Long, opt, lower_strike, lower_premium, 1.0),
+            Leg::new(Position::Short, opt, middle_strike, middle_premium, 2.0),
+            Leg::new(Position::Long, opt, upper_strike, upper_premium, 1.0),
+        ])
+    }
+
+    // iron_condor sells a put spread and a call spread around the current price,
+    // each leg is given as (strike, premium) from the lowest to the highest strike
+    pub fn iron_condor(
+        long_put: (f64, f64),
+        short_put: (f64, f64),
+        short_call: (f64, f64),
+        long_call: (f64, f64),
+    ) -> Strategy {
+        Strategy::new(vec![
+            Leg::new(Position::Long, OptionKind::Put, long_put.0, long_put.1, 1.0),
+            Leg::new(
+                Position::Short,
+                OptionKind::Put,
+                short_put.0,
+                short_put.1,
+                1.0,
+            ),
+            Leg::new(
+                Position::Short,
+                OptionKind::Call,
+                short_call.0,
+                short_call.1,
+                1.0,
+            ),
+            Leg::new(
+                Position::Long,
+                OptionKind::Call,
+                long_call.0,
+                long_call.1,
+                1.0,
+            ),
+        ])
+    }
+
+    // calendar_spread sells the near option and buys the far one with the same strike,
+    // the far leg is valued with BlackScholesModel when the near one expires
+    pub fn calendar_spread(
+        opt: OptionKind,
+        strike: f64,
+        near_premium: f64,
+        far_premium: f64,
+        interest_rate: f64,
+        volatility: f64,
+        time_between_expirations: f64,
+    ) -> Strategy {
+        Strategy::new(vec![
+            Leg::new(Position::Short, opt, strike, near_premium, 1.0),
+            Leg::new(Position::Long, opt, strike, far_premium, 1.0).outliving(
+                interest_rate,
+                volatility,
+                time_between_expirations,
+                None,
+            ),
+        ])
+    }
+
+    // covered_call holds one share for every call sold
+    pub fn covered_call(share_price: f64, strike: f64, premium: f64) -> Strategy {
+        Strategy::new(vec![Leg::new(
+            Position::Short,
+            OptionKind::Call,
+            strike,
+            premium,
+            1.0,
+        )])
+        .with_shares(Position::Long, 1.0, share_price)
+    }
+
+    // payoff calculates profit/loss of the whole strategy at expiration with the
+    // underlying at stock ($$$ per share)
+    pub fn payoff(&self, stock: f64) -> MathResult {
+        let mut total = 0.0;
+        for leg in &self.legs {
+            total += leg.profit(stock)?;
+        }
+        if let Some(shares) = self.shares {
+            let sign = match shares.position {
+                Position::Long => 1.0,
+                Position::Short => -1.0,
+            };
+            total += sign * shares.quantity * (stock - shares.price);
+        }
+        Ok(total)
+    }
+
+    // net_premium calculates the cash flow of opening the option legs, it's positive
+    // for a net credit and negative for a net debit
+    pub fn net_premium(&self) -> f64 {
+        self.legs
+            .iter()
+            .map(|leg| -leg.sign() * leg.quantity * leg.premium)
+            .sum()
+    }
+
+    // break_even_points finds every underlying price at which the strategy neither
+    // makes nor loses money at expiration, in ascending order
+    pub fn break_even_points(&self) -> MathResult<Vec<f64>> {
+        let grid = self.grid()?;
+        let mut points: Vec<f64> = Vec::new();
+
+        for pair in grid.windows(2) {
+            let (lower, upper) = (pair[0], pair[1]);
+            let (profit_lower, profit_upper) = (self.payoff(lower)?, self.payoff(upper)?);
+            if profit_lower == 0.0 {
+                points.push(lower);
+            } else if profit_lower.signum() != profit_upper.signum() && profit_upper != 0.0 {
+                points.push(solver::brent(|stock| self.payoff(stock), lower, upper)?);
+            }
+        }
+        let last = grid[grid.len() - 1];
+        if self.payoff(last)? == 0.0 {
+            points.push(last);
+        }
+        points.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+        Ok(points)
+    }
+
+    // max_profit calculates the highest profit at expiration, it's infinite when the
+    // profit keeps growing with the underlying price
+    pub fn max_profit(&self) -> MathResult {
+        if self.terminal_slope() > 0.0 {
+            return Ok(f64::INFINITY);
+        }
+        self.extreme(f64::max)
+    }
+
+    // max_loss calculates the lowest profit/loss at expiration (negative for a loss),
+    // it's negative infinity when the loss keeps growing with the underlying price
+    pub fn max_loss(&self) -> MathResult {
+        if self.terminal_slope() < 0.0 {
+            return Ok(f64::NEG_INFINITY);
+        }
+        self.extreme(f64::min)
+    }
+
+    fn extreme(&self, pick: fn(f64, f64) -> f64) -> MathResult {
+        let grid = self.grid()?;
+        let mut result = self.payoff(grid[0])?;
+        for stock in &grid[1..] {
+            result = pick(result, self.payoff(*stock)?);
+        }
+        Ok(result)
+    }
+
+    // terminal_slope is the change of the profit per $$$ of the underlying beyond the
+    // highest strike
+    fn terminal_slope(&self) -> f64 {
+        let options: f64 = self
+            .legs
+            .iter()
+            .filter(|leg| leg.opt == OptionKind::Call)
+            .map(|leg| leg.sign() * leg.quantity)
+            .sum();
+        let shares = self.shares.map_or(0.0, |shares| match shares.position {
+            Position::Long => shares.quantity,
+            Position::Short => -shares.quantity,
+        });
+        options + shares
+    }
+
+    // grid samples the underlying price from zero past the last break-even point,
+    // with every strike on the grid so that kinks of the payoff are never skipped
+    fn grid(&self) -> MathResult<Vec<f64>> {
+        let mut kinks: Vec<f64> = self.legs.iter().map(|leg| leg.strike).collect();
+        if let Some(shares) = self.shares {
+            kinks.push(shares.price);
+        }
+        let highest = kinks.iter().cloned().fold(1.0, f64::max);
+
+        // beyond the strikes the payoff is (close to) linear, so keep doubling the upper
+        // end until it no longer changes sign
+        let mut upper = 2.0 * highest;
+        for _ in 0..64 {
+            let (profit, further) = (self.payoff(upper)?, self.payoff(2.0 * upper)?);
+            if profit.signum() == further.signum() {
+                break;
+            }
+            upper *= 2.0;
+        }
+        kinks.push(0.0);
+        kinks.push(upper);
+        kinks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        kinks.dedup();
+
+        let mut grid = Vec::with_capacity(kinks.len() * SAMPLES_PER_INTERVAL);
+        for pair in kinks.windows(2) {
+            let step = (pair[1] - pair[0]) / SAMPLES_PER_INTERVAL as f64;
+            grid.extend((0..SAMPLES_PER_INTERVAL).map(|i| pair[0] + step * i as f64));
+        }
+        grid.push(upper);
+        Ok(grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_close;
+
+    #[test]
+    fn bull_call_spread() {
+        let strategy = Strategy::vertical_spread(OptionKind::Call, 50.0, 3.0, 55.0, 1.0);
+
+        assert_close(strategy.net_premium(), -2.0, 1e-9);
+        assert_close(strategy.payoff(45.0).unwrap(), -2.0, 1e-9);
+        assert_close(strategy.payoff(60.0).unwrap(), 3.0, 1e-9);
+        assert_eq!(strategy.break_even_points().unwrap(), vec![52.0]);
+        assert_close(strategy.max_profit().unwrap(), 3.0, 1e-9);
+        assert_close(strategy.max_loss().unwrap(), -2.0, 1e-9);
+    }
+
+    #[test]
+    fn long_straddle() {
+        let strategy = Strategy::straddle(Position::Long, 100.0, 4.0, 3.5);
+        let points = strategy.break_even_points().unwrap();
+
+        assert_eq!(points.len(), 2);
+        assert_close(points[0], 92.5, 1e-9);
+        assert_close(points[1], 107.5, 1e-9);
+        assert_eq!(strategy.max_profit().unwrap(), f64::INFINITY);
+        assert_close(strategy.max_loss().unwrap(), -7.5, 1e-9);
+    }
+
+    #[test]
+    fn short_strangle() {
+        let strategy = Strategy::strangle(Position::Short, 90.0, 1.5, 110.0, 2.0);
+        let points = strategy.break_even_points().unwrap();
+
+        assert_close(strategy.net_premium(), 3.5, 1e-9);
+        assert_close(points[0], 86.5, 1e-9);
+        assert_close(points[1], 113.5, 1e-9);
+        assert_close(strategy.max_profit().unwrap(), 3.5, 1e-9);
+        assert_eq!(strategy.max_loss().unwrap(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn iron_condor() {
+        let strategy = Strategy::iron_condor((80.0, 0.5), (90.0, 1.5), (110.0, 2.0), (120.0, 0.75));
+        let points = strategy.break_even_points().unwrap();
+
+        assert_close(strategy.net_premium(), 2.25, 1e-9);
+        assert_close(points[0], 87.75, 1e-9);
+        assert_close(points[1], 112.25, 1e-9);
+        assert_close(strategy.max_profit().unwrap(), 2.25, 1e-9);
+        assert_close(strategy.max_loss().unwrap(), -7.75, 1e-9);
+    }
+
+    #[test]
+    fn long_put_butterfly() {
+        let strategy = Strategy::butterfly(OptionKind::Put, 90.0, 1.0, 100.0, 4.0, 110.0, 10.0);
+        let points = strategy.break_even_points().unwrap();
+
+        assert_close(strategy.net_premium(), -3.0, 1e-9);
+        assert_close(points[0], 93.0, 1e-9);
+        assert_close(points[1], 107.0, 1e-9);
+        assert_close(strategy.max_profit().unwrap(), 7.0, 1e-9);
+        assert_close(strategy.max_loss().unwrap(), -3.0, 1e-9);
+    }
+
+    #[test]
+    fn covered_call() {
+        let strategy = Strategy::covered_call(50.0, 55.0, 2.0);
+
+        assert_eq!(strategy.break_even_points().unwrap(), vec![48.0]);
+        assert_close(strategy.max_profit().unwrap(), 7.0, 1e-9);
+        assert_close(strategy.max_loss().unwrap(), -48.0, 1e-9);
+    }
+
+    #[test]
+    fn calendar_spread() {
+        let strategy =
+            Strategy::calendar_spread(OptionKind::Call, 100.0, 2.5, 4.0, 0.03, 0.25, 0.25);
+        let points = strategy.break_even_points().unwrap();
+
+        assert_eq!(points.len(), 2);
+        assert!(points[0] < 100.0 && points[1] > 100.0);
+        for point in points {
+            assert!(strategy.payoff(point).unwrap().abs() < 1e-9);
+        }
+        // the far call is worth the most relative to the near one at the strike
+        let at_strike = strategy.payoff(100.0).unwrap();
+        assert!((strategy.max_profit().unwrap() - at_strike).abs() < 1e-2);
+        assert!(strategy.max_loss().unwrap() >= -1.5 - 1e-9);
+    }
+
+    #[test]
+    fn err_with_negative_strike_leg() {
+        let strategy = Strategy::default().with_leg(Leg::new(
+            Position::Long,
+            OptionKind::Call,
+            -50.0,
+            1.0,
+            1.0,
+        ));
+        assert!(strategy.payoff(50.0).is_err());
+    }
+}
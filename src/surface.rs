@@ -0,0 +1,517 @@
+use crate::distributions::norm_cdf;
+use crate::solver::brent;
+use crate::svi::{self, FitTarget, SviSmile};
+use crate::{check_finite, MathError, MathResult, VolQuote};
+use std::f64::consts::E;
+
+// StrikeInterpolation selects how the volatility of an expiration is interpolated
+// between the quoted strikes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StrikeInterpolation {
+    Linear,      // straight lines between the quotes
+    CubicSpline, // natural cubic spline through the quotes
+    Svi,         // raw SVI fitted to the quotes in log-moneyness, whatever the axis
+}
+
+// StrikeAxis selects the coordinate along which the strikes are interpolated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StrikeAxis {
+    Moneyness,    // K / F
+    LogMoneyness, // ln(K / F)
+    Delta,        // forward delta of the call, N(d1)
+}
+
+// Extrapolation selects the volatility beyond the lowest and highest quoted strikes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Extrapolation {
+    Flat,   // volatility of the outermost quote
+    Linear, // continues the slope at the outermost quote, floored at zero
+}
+
+// VolSurface interpolates implied volatility quotes to any strike and time to
+// expiration, linearly in total variance along the time at a fixed point of the strike
+// axis
+//
+// Before the first and after the last expiration the volatility at that point of the
+// axis stays flat
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolSurface {
+    stock: f64,                         // underlying price ($$$ per share)
+    interest_rate: f64,                 // continuously compounded risk-free interest rate (% p.a.)
+    dividend: Option<f64>,              // continuously compounded dividend yield (% p.a.)
+    slices: Vec<Slice>,                 // quotes by increasing time to expiration
+    interpolation: StrikeInterpolation, // interpolation between strikes
+    axis: StrikeAxis,                   // coordinate of the strike interpolation
+    extrapolation: Extrapolation,       // volatility beyond the quoted strikes
+}
+
+// Slice holds the quotes of one expiration
+#[derive(Debug, Clone, PartialEq)]
+struct Slice {
+    time_to_expire: f64,   // time to expiration (% of year)
+    forward: f64,          // forward price of the expiration ($$$ per share)
+    quotes: Vec<VolQuote>, // quotes by increasing strike
+    svi: Option<SviSmile>, // fitted smile for StrikeInterpolation::Svi
+}
+
+impl VolSurface {
+    // new creates a surface interpolating linearly in log-moneyness with flat wings,
+    // use with_interpolation, with_axis and with_extrapolation to change the defaults
+    pub fn new(
+        stock: f64,
+        interest_rate: f64,
+        dividend: Option<f64>,
+        quotes: &[VolQuote],
+    ) -> MathResult<VolSurface> {
+        check_finite("stock", stock)?;
+        check_finite("interest_rate", interest_rate)?;
+        check_finite("dividend", dividend.unwrap_or_default())?;
+        if stock <= 0.0 {
+            return Err(MathError::NonPositiveStock(stock));
+        }
+        let carry = interest_rate - dividend.unwrap_or_default();
+        let slices = svi::expirations(quotes)?
+            .into_iter()
+            .map(|(time_to_expire, mut quotes)| {
+                quotes.sort_by(|a, b| {
+                    a.strike
+                        .partial_cmp(&b.strike)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                Slice {
+                    time_to_expire,
+                    forward: stock * E.powf(carry * time_to_expire),
+                    quotes,
+                    svi: None,
+                }
+            })
+            .collect::<Vec<Slice>>();
+        if slices.is_empty() {
+            return Err(MathError::TooFewQuotes(0));
+        }
+        Ok(VolSurface {
+            stock,
+            interest_rate,
+            dividend,
+            slices,
+            interpolation: StrikeInterpolation::Linear,
+            axis: StrikeAxis::LogMoneyness,
+            extrapolation: Extrapolation::Flat,
+        })
+    }
+
+    // with_interpolation sets the interpolation between strikes, choosing SVI fits a
+    // smile to every expiration (which needs at least 5 quotes each) and fails if a fit
+    // does
+    pub fn with_interpolation(
+        mut self,
+        interpolation: StrikeInterpolation,
+    ) -> MathResult<VolSurface> {
+        if interpolation == StrikeInterpolation::Svi {
+            let quotes: Vec<VolQuote> = self.slices.iter().flat_map(|s| s.quotes.clone()).collect();
+            let forwards: Vec<(f64, f64)> = self
+                .slices
+                .iter()
+                .map(|s| (s.time_to_expire, s.forward))
+                .collect();
+            let stock = self.stock;
+            let forward = |t: f64| forwards.iter().find(|f| f.0 == t).map_or(stock, |f| f.1);
+            let fits = svi::calibrate(forward, &quotes, |_| 1.0, FitTarget::TotalVariance)?;
+            for (slice, fit) in self.slices.iter_mut().zip(fits) {
+                slice.svi = Some(fit.smile);
+            }
+        }
+        self.interpolation = interpolation;
+        Ok(self)
+    }
+
+    pub fn with_axis(mut self, axis: StrikeAxis) -> VolSurface {
+        self.axis = axis;
+        self
+    }
+
+    pub fn with_extrapolation(mut self, extrapolation: Extrapolation) -> VolSurface {
+        self.extrapolation = extrapolation;
+        self
+    }
+
+    pub fn stock(&self) -> f64 {
+        self.stock
+    }
+
+    pub fn interest_rate(&self) -> f64 {
+        self.interest_rate
+    }
+
+    pub fn dividend(&self) -> Option<f64> {
+        self.dividend
+    }
+
+    // volatility is the implied volatility (% p.a.) of the surface at the strike and
+    // time to expiration
+    //
+    // Along the delta axis the point depends on the volatility itself, so the result is
+    // the volatility which reproduces itself through the delta of the strike
+    pub fn volatility(&self, strike: f64, time_to_expire: f64) -> MathResult {
+        check_finite("strike", strike)?;
+        check_finite("time_to_expire", time_to_expire)?;
+        if strike <= 0.0 {
+            return Err(MathError::NonPositiveStrike(strike));
+        }
+        if time_to_expire < 0.0 {
+            return Err(MathError::NegativeTimeToExpire(time_to_expire));
+        }
+        let carry = self.interest_rate - self.dividend.unwrap_or_default();
+        let log_moneyness = (strike / (self.stock * E.powf(carry * time_to_expire))).ln();
+
+        match self.axis {
+            StrikeAxis::Moneyness => {
+                Ok(self.at(log_moneyness.exp(), log_moneyness, time_to_expire))
+            }
+            StrikeAxis::LogMoneyness => Ok(self.at(log_moneyness, log_moneyness, time_to_expire)),
+            StrikeAxis::Delta => {
+                // the delta of a vanishing time to expiration is taken an instant later
+                let time = time_to_expire.max(1e-8);
+                let delta = |volatility: f64| {
+                    norm_cdf(
+                        (-log_moneyness + 0.5 * volatility * volatility * time)
+                            / (volatility * time.sqrt()),
+                    )
+                };
+                brent(
+                    |volatility| Ok(volatility - self.at(delta(volatility), log_moneyness, time)),
+                    1e-4,
+                    10.0,
+                )
+            }
+        }
+    }
+
+    // at interpolates in total variance along the time at the point x of the axis (and
+    // log-moneyness k for SVI slices) and returns the volatility
+    fn at(&self, x: f64, log_moneyness: f64, time_to_expire: f64) -> f64 {
+        let slices = &self.slices;
+        let variance =
+            |slice: &Slice| slice.volatility(x, log_moneyness, self).powi(2) * slice.time_to_expire;
+        let last = slices.len() - 1;
+        let total_variance = match slices
+            .iter()
+            .position(|s| s.time_to_expire >= time_to_expire)
+        {
+            Some(0) => return slices[0].volatility(x, log_moneyness, self),
+            Some(i) => {
+                let (before, after) = (&slices[i - 1], &slices[i]);
+                let weight = (time_to_expire - before.time_to_expire)
+                    / (after.time_to_expire - before.time_to_expire);
+                variance(before) + weight * (variance(after) - variance(before))
+            }
+            None => return slices[last].volatility(x, log_moneyness, self),
+        };
+        (total_variance.max(0.0) / time_to_expire).sqrt()
+    }
+}
+
+impl Slice {
+    // volatility interpolates the quotes at the point x of the axis of the surface, SVI
+    // smiles are evaluated at the log-moneyness
+    fn volatility(&self, x: f64, log_moneyness: f64, surface: &VolSurface) -> f64 {
+        if let Some(smile) = self
+            .svi
+            .filter(|_| surface.interpolation == StrikeInterpolation::Svi)
+        {
+            return (smile.total_variance(log_moneyness).max(0.0) / self.time_to_expire).sqrt();
+        }
+        let points = self.points(surface.axis);
+        let n = points.len();
+        if n == 1 {
+            return points[0].1;
+        }
+        let curvature = match surface.interpolation {
+            StrikeInterpolation::CubicSpline => natural_spline(&points),
+            _ => vec![0.0; n],
+        };
+        let (first, last) = (points[0], points[n - 1]);
+        if x < first.0 || x > last.0 {
+            let (edge, slope) = if x < first.0 {
+                (first, spline_slope(&points, &curvature, 0, 0.0))
+            } else {
+                (last, spline_slope(&points, &curvature, n - 2, 1.0))
+            };
+            return match surface.extrapolation {
+                Extrapolation::Flat => edge.1,
+                Extrapolation::Linear => (edge.1 + slope * (x - edge.0)).max(0.0),
+            };
+        }
+
+        let i = points[..n - 1].iter().rposition(|p| p.0 <= x).unwrap_or(0);
+        let ((x0, y0), (x1, y1)) = (points[i], points[i + 1]);
+        let h = x1 - x0;
+        let t = (x - x0) / h;
+        // cubic spline in the form of Press et al., with zero curvature it is linear
+        (1.0 - t) * y0
+            + t * y1
+            + ((1.0 - t).powi(3) - (1.0 - t)) * curvature[i] * h * h / 6.0
+            + (t.powi(3) - t) * curvature[i + 1] * h * h / 6.0
+    }
+
+    // points returns the quotes as (axis, volatility) by increasing axis
+    fn points(&self, axis: StrikeAxis) -> Vec<(f64, f64)> {
+        let mut points: Vec<(f64, f64)> = self
+            .quotes
+            .iter()
+            .map(|q| {
+                let k = (q.strike / self.forward).ln();
+                let x = match axis {
+                    StrikeAxis::Moneyness => k.exp(),
+                    StrikeAxis::LogMoneyness => k,
+                    StrikeAxis::Delta => {
+                        let deviation = q.volatility * self.time_to_expire.sqrt();
+                        norm_cdf(-k / deviation + 0.5 * deviation)
+                    }
+                };
+                (x, q.volatility)
+            })
+            .collect();
+        // delta falls with the strike
+        if axis == StrikeAxis::Delta {
+            points.reverse();
+        }
+        points
+    }
+}
+
+// natural_spline returns the second derivatives of the natural cubic spline through the
+// points, zero at both ends
+fn natural_spline(points: &[(f64, f64)]) -> Vec<f64> {
+    let n = points.len();
+    let mut curvature = vec![0.0; n];
+    if n < 3 {
+        return curvature;
+    }
+    // Thomas algorithm on the tridiagonal system of the interior points
+    let mut diagonal = vec![0.0; n];
+    let mut rhs = vec![0.0; n];
+    for i in 1..n - 1 {
+        let (h0, h1) = (points[i].0 - points[i - 1].0, points[i + 1].0 - points[i].0);
+        let slopes = (points[i + 1].1 - points[i].1) / h1 - (points[i].1 - points[i - 1].1) / h0;
+        diagonal[i] = 2.0 * (h0 + h1);
+        rhs[i] = 6.0 * slopes;
+        if i > 1 {
+            let factor = h0 / diagonal[i - 1];
+            diagonal[i] -= factor * h0;
+            rhs[i] -= factor * rhs[i - 1];
+        }
+    }
+    for i in (1..n - 1).rev() {
+        let h1 = points[i + 1].0 - points[i].0;
+        curvature[i] = (rhs[i] - h1 * curvature[i + 1]) / diagonal[i];
+    }
+    curvature
+}
+
+// spline_slope is the first derivative of the spline segment i at t = 0 or t = 1
+fn spline_slope(points: &[(f64, f64)], curvature: &[f64], i: usize, t: f64) -> f64 {
+    let ((x0, y0), (x1, y1)) = (points[i], points[i + 1]);
+    let h = x1 - x0;
+    (y1 - y0) / h - (3.0 * (1.0 - t).powi(2) - 1.0) * curvature[i] * h / 6.0
+        + (3.0 * t * t - 1.0) * curvature[i + 1] * h / 6.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svi::SviParameters;
+    use crate::{BlackScholesModel, OptionKind};
+
+    const STRIKES: [f64; 5] = [80.0, 90.0, 100.0, 110.0, 120.0];
+
+    // a skewed smile whose level grows with the time to expiration
+    fn smile(strike: f64, time_to_expire: f64) -> f64 {
+        let k = (strike / 100.0).ln();
+        0.2 + 0.02 * time_to_expire - 0.3 * k + 0.5 * k * k
+    }
+
+    fn quotes() -> Vec<VolQuote> {
+        let mut quotes = Vec::new();
+        for &time_to_expire in &[1.0, 0.25, 2.0] {
+            for &strike in &STRIKES {
+                quotes.push(VolQuote {
+                    strike,
+                    time_to_expire,
+                    volatility: smile(strike, time_to_expire),
+                });
+            }
+        }
+        quotes
+    }
+
+    fn surface() -> VolSurface {
+        VolSurface::new(100.0, 0.0, None, &quotes()).unwrap()
+    }
+
+    #[test]
+    fn reproduces_quotes() {
+        for interpolation in [
+            StrikeInterpolation::Linear,
+            StrikeInterpolation::CubicSpline,
+        ] {
+            for axis in [
+                StrikeAxis::Moneyness,
+                StrikeAxis::LogMoneyness,
+                StrikeAxis::Delta,
+            ] {
+                let surface = surface()
+                    .with_interpolation(interpolation)
+                    .unwrap()
+                    .with_axis(axis);
+                for q in quotes() {
+                    let result = surface.volatility(q.strike, q.time_to_expire).unwrap();
+                    assert!(
+                        (result - q.volatility).abs() < 1e-12,
+                        "{:?} {:?}: {} is not {}",
+                        interpolation,
+                        axis,
+                        result,
+                        q.volatility
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn total_variance_is_linear_in_time() {
+        let surface = surface();
+        let variance = |t: f64| surface.volatility(95.0, t).unwrap().powi(2) * t;
+        let expected = 0.5 * (variance(1.0) + variance(2.0));
+        assert!((variance(1.5) - expected).abs() < 1e-15);
+
+        // flat volatility outside the expirations
+        let first = surface.volatility(95.0, 0.25).unwrap();
+        assert!((surface.volatility(95.0, 0.1).unwrap() - first).abs() < 1e-15);
+        assert!((surface.volatility(95.0, 0.0).unwrap() - first).abs() < 1e-15);
+        let last = surface.volatility(95.0, 2.0).unwrap();
+        assert!((surface.volatility(95.0, 5.0).unwrap() - last).abs() < 1e-15);
+    }
+
+    #[test]
+    fn interpolates_between_strikes() {
+        let linear = surface();
+        let k = |strike: f64| (strike / 100.0).ln();
+        let expected = smile(90.0, 1.0)
+            + (k(95.0) - k(90.0)) / (k(100.0) - k(90.0)) * (smile(100.0, 1.0) - smile(90.0, 1.0));
+        assert!((linear.volatility(95.0, 1.0).unwrap() - expected).abs() < 1e-15);
+
+        // the spline follows the curvature of the smile which the lines cut across
+        let spline = surface()
+            .with_interpolation(StrikeInterpolation::CubicSpline)
+            .unwrap();
+        for &strike in &[85.0, 95.0, 105.0] {
+            let exact = smile(strike, 1.0);
+            let spline_error = (spline.volatility(strike, 1.0).unwrap() - exact).abs();
+            let linear_error = (linear.volatility(strike, 1.0).unwrap() - exact).abs();
+            assert!(spline_error < linear_error / 2.0);
+        }
+    }
+
+    #[test]
+    fn extrapolates_the_wings() {
+        let flat = surface();
+        assert_eq!(flat.volatility(60.0, 1.0).unwrap(), smile(80.0, 1.0));
+        assert_eq!(flat.volatility(150.0, 1.0).unwrap(), smile(120.0, 1.0));
+
+        let linear = surface().with_extrapolation(Extrapolation::Linear);
+        let k = |strike: f64| (strike / 100.0).ln();
+        let slope = (smile(90.0, 1.0) - smile(80.0, 1.0)) / (k(90.0) - k(80.0));
+        let expected = smile(80.0, 1.0) + slope * (k(60.0) - k(80.0));
+        assert!((linear.volatility(60.0, 1.0).unwrap() - expected).abs() < 1e-15);
+    }
+
+    #[test]
+    fn svi_slices() {
+        let parameters = SviParameters {
+            a: 0.03,
+            b: 0.2,
+            rho: -0.5,
+            m: 0.05,
+            sigma: 0.2,
+        };
+        let truth = SviSmile::new(100.0 * E.powf(0.03), 1.0, parameters);
+        let quotes: Vec<VolQuote> = [60.0, 75.0, 90.0, 100.0, 110.0, 130.0, 160.0]
+            .iter()
+            .map(|&strike| VolQuote {
+                strike,
+                time_to_expire: 1.0,
+                volatility: truth.volatility(strike).unwrap(),
+            })
+            .collect();
+        let surface = VolSurface::new(100.0, 0.03, None, &quotes)
+            .unwrap()
+            .with_interpolation(StrikeInterpolation::Svi)
+            .unwrap();
+        for &strike in &[50.0, 82.0, 105.0, 200.0] {
+            let expected = truth.volatility(strike).unwrap();
+            assert!((surface.volatility(strike, 1.0).unwrap() - expected).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn delta_axis_is_consistent() {
+        let surface = surface().with_axis(StrikeAxis::Delta);
+        let volatility = surface.volatility(95.0, 1.0).unwrap();
+        // interpolating at the delta of the result gives the result back
+        let deviation = volatility;
+        let delta = norm_cdf(-(0.95f64).ln() / deviation + 0.5 * deviation);
+        let slice = &surface.slices[1];
+        assert!((slice.volatility(delta, 0.0, &surface) - volatility).abs() < 1e-12);
+        assert!(volatility > smile(100.0, 1.0) && volatility < smile(90.0, 1.0));
+    }
+
+    #[test]
+    fn black_scholes_takes_volatility_from_surface() {
+        let surface = VolSurface::new(100.0, 0.03, Some(0.01), &quotes()).unwrap();
+        let model = BlackScholesModel::from_surface(OptionKind::Put, 95.0, 0.5, &surface).unwrap();
+        let volatility = surface.volatility(95.0, 0.5).unwrap();
+        let expected = BlackScholesModel::new(
+            OptionKind::Put,
+            95.0,
+            100.0,
+            0.03,
+            volatility,
+            0.5,
+            Some(0.01),
+        );
+        assert_eq!(model, expected);
+    }
+
+    #[test]
+    fn err_with_invalid_inputs() {
+        assert_eq!(
+            VolSurface::new(100.0, 0.0, None, &[]),
+            Err(MathError::TooFewQuotes(0))
+        );
+        assert_eq!(
+            surface().volatility(-1.0, 1.0),
+            Err(MathError::NonPositiveStrike(-1.0))
+        );
+        assert_eq!(
+            surface().volatility(100.0, -1.0),
+            Err(MathError::NegativeTimeToExpire(-1.0))
+        );
+        // an SVI smile needs 5 quotes per expiration
+        let mut quotes = quotes();
+        quotes.retain(|q| q.time_to_expire != 0.25 || q.strike != 120.0);
+        let surface = VolSurface::new(100.0, 0.0, None, &quotes).unwrap();
+        assert_eq!(
+            surface.with_interpolation(StrikeInterpolation::Svi),
+            Err(MathError::TooFewQuotes(4))
+        );
+        // a second quote at the same strike would make the spline divide by zero
+        let mut duplicated = quotes.clone();
+        duplicated.push(quotes[0]);
+        assert_eq!(
+            VolSurface::new(100.0, 0.0, None, &duplicated),
+            Err(MathError::DuplicateStrike(quotes[0].strike))
+        );
+    }
+}
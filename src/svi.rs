@@ -0,0 +1,739 @@
+use crate::solver::levenberg_marquardt;
+use crate::{check_finite, BlackScholesModel, MathError, MathResult, OptionKind, VolQuote};
+
+const MIN_QUOTES: usize = 5; // quotes per expiration of a raw fit, one per parameter
+
+// FitTarget selects the quantity whose weighted squared errors a fit minimises
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FitTarget {
+    Volatility,    // implied volatility (% p.a.)
+    TotalVariance, // implied variance times the time to expiration
+}
+
+// SviParameters are Gatheral's (2004) raw stochastic volatility inspired smile of the
+// total implied variance in the log-moneyness k = ln(K / F)
+//
+//   w(k) = a + b (rho (k - m) + sqrt((k - m)^2 + sigma^2))
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SviParameters {
+    pub a: f64,     // level of the variance
+    pub b: f64,     // slope of the wings
+    pub rho: f64,   // rotation, between -1 and 1
+    pub m: f64,     // horizontal translation
+    pub sigma: f64, // curvature at the minimum
+}
+
+// NaturalSviParameters are the natural parametrisation of Gatheral and Jacquier (2014)
+//
+//   w(k) = delta + omega / 2 (1 + zeta rho (k - mu) + sqrt((zeta (k - mu) + rho)^2 + 1 - rho^2))
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NaturalSviParameters {
+    pub delta: f64, // level of the variance
+    pub mu: f64,    // horizontal translation
+    pub rho: f64,   // rotation, between -1 and 1
+    pub omega: f64, // scale of the variance
+    pub zeta: f64,  // scale of the log-moneyness
+}
+
+// SsviParameters are the surface SVI of Gatheral and Jacquier (2014) with the power-law
+// curvature phi(theta) = eta / (theta^gamma (1 + theta)^(1 - gamma)), every expiration
+// is a natural SVI slice set by its at-the-money total variance theta
+//
+//   w(k, theta) = theta / 2 (1 + rho phi k + sqrt((phi k + rho)^2 + 1 - rho^2))
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsviParameters {
+    pub rho: f64,   // correlation between spot and volatility, between -1 and 1
+    pub eta: f64,   // level of the curvature
+    pub gamma: f64, // decay of the curvature with the variance, between 0 and 1
+}
+
+// SviSmile is the smile of one expiration, which prices listed and unlisted strikes
+// with the Black-Scholes model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SviSmile {
+    forward: f64,              // forward price of the expiration ($$$ per share)
+    time_to_expire: f64,       // time to expiration (% of year)
+    parameters: SviParameters, // raw parameters of the smile
+}
+
+// SviFit is the outcome of the fit of one expiration
+#[derive(Debug, Clone, PartialEq)]
+pub struct SviFit {
+    pub smile: SviSmile,     // fitted smile
+    pub residuals: Vec<f64>, // model minus quoted target, in the order of the quotes
+}
+
+// SsviFit is the outcome of the fit of a whole surface
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsviFit {
+    pub parameters: SsviParameters, // fitted surface
+    pub smiles: Vec<SviSmile>,      // slices in the order of increasing expiration
+    pub residuals: Vec<f64>,        // model minus quoted target, in the order of the quotes
+}
+
+impl From<NaturalSviParameters> for SviParameters {
+    fn from(p: NaturalSviParameters) -> SviParameters {
+        SviParameters {
+            a: p.delta + p.omega / 2.0 * (1.0 - p.rho * p.rho),
+            b: p.omega * p.zeta / 2.0,
+            rho: p.rho,
+            m: p.mu - p.rho / p.zeta,
+            sigma: (1.0 - p.rho * p.rho).sqrt() / p.zeta,
+        }
+    }
+}
+
+impl SsviParameters {
+    pub fn phi(&self, theta: f64) -> f64 {
+        self.eta / (theta.powf(self.gamma) * (1.0 + theta).powf(1.0 - self.gamma))
+    }
+
+    // slice returns the raw parameters of the expiration with at-the-money total
+    // variance theta
+    pub fn slice(&self, theta: f64) -> SviParameters {
+        SviParameters::from(NaturalSviParameters {
+            delta: 0.0,
+            mu: 0.0,
+            rho: self.rho,
+            omega: theta,
+            zeta: self.phi(theta),
+        })
+    }
+
+    // is_butterfly_free checks the sufficient conditions of Gatheral and Jacquier
+    // (2014), theorem 4.2, for the slice with at-the-money total variance theta
+    pub fn is_butterfly_free(&self, theta: f64) -> bool {
+        let phi = self.phi(theta);
+        let skew = 1.0 + self.rho.abs();
+        theta * phi * skew < 4.0 && theta * phi * phi * skew <= 4.0
+    }
+}
+
+impl SviSmile {
+    pub fn new(forward: f64, time_to_expire: f64, parameters: SviParameters) -> SviSmile {
+        SviSmile {
+            forward,
+            time_to_expire,
+            parameters,
+        }
+    }
+
+    // try_new creates the smile like new does, but rejects a non-positive forward, a
+    // non-positive time to expiration and parameters which allow negative variance
+    pub fn try_new(
+        forward: f64,
+        time_to_expire: f64,
+        parameters: SviParameters,
+    ) -> MathResult<SviSmile> {
+        let p = parameters;
+        check_finite("forward", forward)?;
+        check_finite("time_to_expire", time_to_expire)?;
+        for &(name, value) in &[
+            ("a", p.a),
+            ("b", p.b),
+            ("rho", p.rho),
+            ("m", p.m),
+            ("sigma", p.sigma),
+        ] {
+            check_finite(name, value)?;
+        }
+        if forward <= 0.0 {
+            return Err(MathError::NonPositiveStock(forward));
+        }
+        if time_to_expire <= 0.0 {
+            return Err(MathError::NegativeTimeToExpire(time_to_expire));
+        }
+        if p.b < 0.0 {
+            return Err(MathError::ParameterOutOfBounds("b", p.b));
+        }
+        if p.rho.abs() >= 1.0 {
+            return Err(MathError::ParameterOutOfBounds("rho", p.rho));
+        }
+        if p.sigma <= 0.0 {
+            return Err(MathError::ParameterOutOfBounds("sigma", p.sigma));
+        }
+        // the minimum of the total variance
+        if p.a + p.b * p.sigma * (1.0 - p.rho * p.rho).sqrt() < 0.0 {
+            return Err(MathError::ParameterOutOfBounds("a", p.a));
+        }
+        Ok(SviSmile::new(forward, time_to_expire, parameters))
+    }
+
+    pub fn forward(&self) -> f64 {
+        self.forward
+    }
+
+    pub fn time_to_expire(&self) -> f64 {
+        self.time_to_expire
+    }
+
+    pub fn parameters(&self) -> SviParameters {
+        self.parameters
+    }
+
+    // total_variance is w(k) at the log-moneyness k = ln(K / F)
+    pub fn total_variance(&self, log_moneyness: f64) -> f64 {
+        let SviParameters {
+            a,
+            b,
+            rho,
+            m,
+            sigma,
+        } = self.parameters;
+        let x = log_moneyness - m;
+        a + b * (rho * x + (x * x + sigma * sigma).sqrt())
+    }
+
+    // volatility is the implied volatility (% p.a.) at the strike
+    pub fn volatility(&self, strike: f64) -> MathResult {
+        if strike <= 0.0 {
+            return Err(MathError::NonPositiveStrike(strike));
+        }
+        let variance = self.total_variance((strike / self.forward).ln());
+        Ok((variance.max(0.0) / self.time_to_expire).sqrt())
+    }
+
+    // durrleman is Durrleman's g function at the log-moneyness, the risk-neutral density
+    // of the smile is negative (butterfly arbitrage) where g < 0
+    //
+    //   g(k) = (1 - k w' / (2 w))^2 - w'^2 / 4 (1 / w + 1 / 4) + w'' / 2
+    pub fn durrleman(&self, log_moneyness: f64) -> f64 {
+        let SviParameters {
+            b, rho, m, sigma, ..
+        } = self.parameters;
+        let k = log_moneyness;
+        let x = k - m;
+        let root = (x * x + sigma * sigma).sqrt();
+        let w = self.total_variance(k);
+        let slope = b * (rho + x / root);
+        let curvature = b * sigma * sigma / root.powi(3);
+        (1.0 - k * slope / (2.0 * w)).powi(2) - slope * slope / 4.0 * (1.0 / w + 0.25)
+            + curvature / 2.0
+    }
+
+    // butterfly_violations returns the log-moneyness points of the grid where
+    // Durrleman's g function is negative
+    pub fn butterfly_violations(&self, log_moneyness: &[f64]) -> Vec<f64> {
+        log_moneyness
+            .iter()
+            .cloned()
+            .filter(|&k| self.durrleman(k) < 0.0)
+            .collect()
+    }
+
+    // black_scholes prices the option on the stock with the volatility of the smile at
+    // the strike, the forward of the smile is expected to be the forward of the stock
+    pub fn black_scholes(
+        &self,
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        dividend: Option<f64>,
+    ) -> MathResult<BlackScholesModel> {
+        BlackScholesModel::try_new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            self.volatility(strike)?,
+            self.time_to_expire,
+            dividend,
+        )
+    }
+
+    // error is the model minus the quoted target
+    fn error(&self, quote: &VolQuote, target: FitTarget) -> MathResult {
+        let model = self.volatility(quote.strike)?;
+        Ok(match target {
+            FitTarget::Volatility => model - quote.volatility,
+            FitTarget::TotalVariance => {
+                (model * model - quote.volatility * quote.volatility) * self.time_to_expire
+            }
+        })
+    }
+}
+
+// calendar_violations returns the expirations (the later one of each pair) and
+// log-moneyness points of the grid where the total variance of the smiles decreases
+// with the time to expiration, which is calendar arbitrage
+pub fn calendar_violations(smiles: &[SviSmile], log_moneyness: &[f64]) -> Vec<(f64, f64)> {
+    let mut sorted = smiles.to_vec();
+    sorted.sort_by(|a, b| {
+        a.time_to_expire
+            .partial_cmp(&b.time_to_expire)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut violations = Vec::new();
+    for pair in sorted.windows(2) {
+        for &k in log_moneyness {
+            if pair[1].total_variance(k) < pair[0].total_variance(k) {
+                violations.push((pair[1].time_to_expire, k));
+            }
+        }
+    }
+    violations
+}
+
+// calibrate fits raw SVI parameters to every expiration in the quotes, with the forward
+// of each expiration given by the forward function of the time to expiration and the
+// weight of each quote by the weight function, the fits are returned in the order of
+// increasing expiration
+//
+// The fit starts from the wing slopes and the lowest variance of the quotes, and fails
+// with TooFewQuotes for an expiration with fewer than 5 quotes
+pub fn calibrate<F, W>(
+    forward: F,
+    quotes: &[VolQuote],
+    weight: W,
+    target: FitTarget,
+) -> MathResult<Vec<SviFit>>
+where
+    F: Fn(f64) -> f64,
+    W: Fn(&VolQuote) -> f64,
+{
+    expirations(quotes)?
+        .into_iter()
+        .map(|(time_to_expire, smile)| {
+            if smile.len() < MIN_QUOTES {
+                return Err(MathError::TooFewQuotes(smile.len()));
+            }
+            let forward = forward(time_to_expire);
+            let unpack = |x: &[f64]| SviParameters {
+                a: x[0],
+                b: x[1].exp(),
+                rho: x[2].tanh(),
+                m: x[3],
+                sigma: x[4].exp(),
+            };
+            let model = |x: &[f64]| SviSmile::try_new(forward, time_to_expire, unpack(x));
+            let residuals = |x: &[f64]| {
+                let model = model(x)?;
+                smile
+                    .iter()
+                    .map(|q| Ok(weight(q).sqrt() * model.error(q, target)?))
+                    .collect()
+            };
+
+            let start = initial_guess(forward, time_to_expire, &smile);
+            let x = levenberg_marquardt(residuals, &start)?;
+            let smile_fit = model(&x)?;
+            Ok(SviFit {
+                smile: smile_fit,
+                residuals: smile
+                    .iter()
+                    .map(|q| smile_fit.error(q, target))
+                    .collect::<MathResult<Vec<f64>>>()?,
+            })
+        })
+        .collect()
+}
+
+// calibrate_ssvi fits one set of SSVI parameters to all expirations in the quotes, with
+// the at-the-money total variance of each expiration interpolated linearly from its
+// quotes in the log-moneyness (flat beyond the quoted strikes)
+pub fn calibrate_ssvi<F, W>(
+    forward: F,
+    quotes: &[VolQuote],
+    weight: W,
+    target: FitTarget,
+) -> MathResult<SsviFit>
+where
+    F: Fn(f64) -> f64,
+    W: Fn(&VolQuote) -> f64,
+{
+    let slices: Vec<(f64, f64, f64)> = expirations(quotes)?
+        .into_iter()
+        .map(|(time_to_expire, smile)| {
+            let forward = forward(time_to_expire);
+            let mut points: Vec<(f64, f64)> = smile
+                .iter()
+                .map(|q| {
+                    let k = (q.strike / forward).ln();
+                    (k, q.volatility * q.volatility * time_to_expire)
+                })
+                .collect();
+            points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            (time_to_expire, forward, at_the_money(&points))
+        })
+        .collect();
+
+    let unpack = |x: &[f64]| SsviParameters {
+        rho: x[0].tanh(),
+        eta: x[1].exp(),
+        gamma: 1.0 / (1.0 + (-x[2]).exp()),
+    };
+    let smiles = |x: &[f64]| {
+        let parameters = unpack(x);
+        slices
+            .iter()
+            .map(|&(time_to_expire, forward, theta)| {
+                SviSmile::try_new(forward, time_to_expire, parameters.slice(theta))
+            })
+            .collect::<MathResult<Vec<SviSmile>>>()
+    };
+    let errors = |x: &[f64], weighted: bool| {
+        let smiles = smiles(x)?;
+        quotes
+            .iter()
+            .map(|q| {
+                let smile = smiles
+                    .iter()
+                    .find(|s| s.time_to_expire == q.time_to_expire)
+                    .expect("every quote has a slice");
+                let scale = if weighted { weight(q).sqrt() } else { 1.0 };
+                Ok(scale * smile.error(q, target)?)
+            })
+            .collect::<MathResult<Vec<f64>>>()
+    };
+
+    // rho = -0.5, eta = 1 and gamma = 0.5
+    let x = levenberg_marquardt(|x: &[f64]| errors(x, true), &[(-0.5f64).atanh(), 0.0, 0.0])?;
+    Ok(SsviFit {
+        parameters: unpack(&x),
+        smiles: smiles(&x)?,
+        residuals: errors(&x, false)?,
+    })
+}
+
+// expirations validates the quotes and groups them by increasing time to expiration,
+// failing with DuplicateStrike when an expiration is quoted twice at the same strike
+pub(crate) fn expirations(quotes: &[VolQuote]) -> MathResult<Vec<(f64, Vec<VolQuote>)>> {
+    let mut groups: Vec<(f64, Vec<VolQuote>)> = Vec::new();
+    for q in quotes {
+        check_finite("strike", q.strike)?;
+        check_finite("volatility", q.volatility)?;
+        check_finite("time_to_expire", q.time_to_expire)?;
+        if q.strike <= 0.0 {
+            return Err(MathError::NonPositiveStrike(q.strike));
+        }
+        if q.volatility <= 0.0 {
+            return Err(MathError::NonPositiveVolatility(q.volatility));
+        }
+        if q.time_to_expire <= 0.0 {
+            return Err(MathError::NegativeTimeToExpire(q.time_to_expire));
+        }
+        match groups.iter_mut().find(|g| g.0 == q.time_to_expire) {
+            Some(group) => {
+                if group.1.iter().any(|p| p.strike == q.strike) {
+                    return Err(MathError::DuplicateStrike(q.strike));
+                }
+                group.1.push(*q)
+            }
+            None => groups.push((q.time_to_expire, vec![*q])),
+        }
+    }
+    groups.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(groups)
+}
+
+// initial_guess reads the raw parameters off the total variance of the quotes: the
+// wing slopes give b and rho, the lowest point gives a and m
+fn initial_guess(forward: f64, time_to_expire: f64, quotes: &[VolQuote]) -> [f64; 5] {
+    let mut points: Vec<(f64, f64)> = quotes
+        .iter()
+        .map(|q| {
+            let k = (q.strike / forward).ln();
+            (k, q.volatility * q.volatility * time_to_expire)
+        })
+        .collect();
+    points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    let n = points.len();
+    let slope = |i: usize, j: usize| {
+        if n < 2 {
+            0.0
+        } else {
+            (points[j].1 - points[i].1) / (points[j].0 - points[i].0)
+        }
+    };
+    let left = slope(0, 1.min(n - 1));
+    let right = slope(n.saturating_sub(2), n - 1);
+
+    let b = ((right - left) / 2.0).max(1e-3);
+    let rho = ((right + left) / (2.0 * b)).clamp(-0.9, 0.9);
+    let sigma = 0.1;
+    let lowest = points
+        .iter()
+        .fold(points[0], |low, &p| if p.1 < low.1 { p } else { low });
+    let a = lowest.1 - b * sigma * (1.0 - rho * rho).sqrt();
+    [a, b.ln(), rho.atanh(), lowest.0, sigma.ln()]
+}
+
+// at_the_money interpolates the total variance of the sorted points at k = 0
+fn at_the_money(points: &[(f64, f64)]) -> f64 {
+    match points.iter().position(|p| p.0 >= 0.0) {
+        Some(0) => points[0].1,
+        Some(i) => {
+            let ((k0, w0), (k1, w1)) = (points[i - 1], points[i]);
+            w0 + (w1 - w0) * -k0 / (k1 - k0)
+        }
+        None => points[points.len() - 1].1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{E, PI};
+
+    const RAW: SviParameters = SviParameters {
+        a: 0.04,
+        b: 0.4,
+        rho: -0.4,
+        m: 0.05,
+        sigma: 0.2,
+    };
+
+    const SURFACE: SsviParameters = SsviParameters {
+        rho: -0.6,
+        eta: 1.2,
+        gamma: 0.4,
+    };
+
+    const GRID: [f64; 9] = [-1.0, -0.6, -0.3, -0.1, 0.0, 0.1, 0.3, 0.6, 1.0];
+
+    fn quotes(smiles: &[SviSmile], strikes: &[f64]) -> Vec<VolQuote> {
+        smiles
+            .iter()
+            .flat_map(|smile| {
+                strikes.iter().map(move |&strike| VolQuote {
+                    strike,
+                    time_to_expire: smile.time_to_expire,
+                    volatility: smile.volatility(strike).unwrap(),
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn natural_parameters_match_raw() {
+        let natural = NaturalSviParameters {
+            delta: 0.01,
+            mu: 0.1,
+            rho: -0.3,
+            omega: 0.2,
+            zeta: 2.5,
+        };
+        let smile = SviSmile::new(100.0, 1.0, natural.into());
+        for &k in &GRID {
+            let x = natural.zeta * (k - natural.mu);
+            let expected = natural.delta
+                + natural.omega / 2.0
+                    * (1.0
+                        + natural.rho * x
+                        + ((x + natural.rho).powi(2) + 1.0 - natural.rho.powi(2)).sqrt());
+            assert!((smile.total_variance(k) - expected).abs() < 1e-15);
+        }
+    }
+
+    #[test]
+    fn ssvi_slice_matches_surface() {
+        let theta = 0.09;
+        let phi = SURFACE.phi(theta);
+        let smile = SviSmile::new(100.0, 1.0, SURFACE.slice(theta));
+        assert!((smile.total_variance(0.0) - theta).abs() < 1e-15);
+        for &k in &GRID {
+            let expected = theta / 2.0
+                * (1.0
+                    + SURFACE.rho * phi * k
+                    + ((phi * k + SURFACE.rho).powi(2) + 1.0 - SURFACE.rho.powi(2)).sqrt());
+            assert!((smile.total_variance(k) - expected).abs() < 1e-15);
+        }
+    }
+
+    #[test]
+    fn durrleman_matches_density_of_call_prices() {
+        // the second strike derivative of undiscounted calls is the density
+        // g(k) / (K sqrt(2 pi w)) exp(-d2^2 / 2)
+        let smile = SviSmile::new(1.0, 1.0, RAW);
+        let call = |strike: f64| {
+            smile
+                .black_scholes(OptionKind::Call, strike, 1.0, 0.0, None)
+                .unwrap()
+                .price()
+                .unwrap()
+        };
+        for &k in &[-0.5, -0.2, 0.0, 0.3] {
+            let strike = E.powf(k);
+            let h = 1e-4;
+            let density = (call(strike + h) - 2.0 * call(strike) + call(strike - h)) / (h * h);
+            let w = smile.total_variance(k);
+            let d2 = -k / w.sqrt() - w.sqrt() / 2.0;
+            let expected =
+                smile.durrleman(k) / (strike * (2.0 * PI * w).sqrt()) * E.powf(-d2 * d2 / 2.0);
+            assert!(
+                (density - expected).abs() < 1e-5,
+                "{} is not {}",
+                density,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn butterfly_arbitrage() {
+        let smile = SviSmile::new(1.0, 1.0, RAW);
+        assert!(smile.butterfly_violations(&GRID).is_empty());
+
+        // Axel Vogt's example of a smile with a negative density
+        let vogt = SviParameters {
+            a: -0.041,
+            b: 0.1331,
+            rho: 0.306,
+            m: 0.3586,
+            sigma: 0.4153,
+        };
+        let smile = SviSmile::new(1.0, 1.0, vogt);
+        let grid: Vec<f64> = (0..=300).map(|i| -1.5 + 0.01 * i as f64).collect();
+        let violations = smile.butterfly_violations(&grid);
+        assert!(!violations.is_empty());
+        assert!(violations.iter().all(|&k| k > 0.0 && k < 1.5));
+
+        assert!(SURFACE.is_butterfly_free(0.04));
+        let steep = SsviParameters {
+            eta: 4.0,
+            ..SURFACE
+        };
+        assert!(!steep.is_butterfly_free(1.0));
+    }
+
+    #[test]
+    fn calendar_arbitrage() {
+        let smiles: Vec<SviSmile> = [(0.5, 0.03), (1.0, 0.06), (2.0, 0.1)]
+            .iter()
+            .map(|&(t, theta)| SviSmile::new(100.0, t, SURFACE.slice(theta)))
+            .collect();
+        assert!(calendar_violations(&smiles, &GRID).is_empty());
+
+        // a later expiration with less variance in the left wing
+        let crossing = SviSmile::new(
+            100.0,
+            3.0,
+            SviParameters {
+                a: 0.1,
+                b: 0.05,
+                rho: 0.0,
+                m: 0.0,
+                sigma: 0.1,
+            },
+        );
+        let mut smiles = smiles;
+        smiles.insert(0, crossing);
+        let violations = calendar_violations(&smiles, &GRID);
+        assert!(violations.contains(&(3.0, -1.0)));
+        assert!(!violations.contains(&(3.0, 0.0)));
+    }
+
+    #[test]
+    fn calibration_recovers_raw_parameters() {
+        let forward = |t: f64| 100.0 * E.powf(0.02 * t);
+        let truths = [
+            SviSmile::new(forward(0.5), 0.5, RAW),
+            SviSmile::new(
+                forward(1.5),
+                1.5,
+                SviParameters {
+                    a: 0.06,
+                    b: 0.3,
+                    rho: -0.6,
+                    m: 0.1,
+                    sigma: 0.3,
+                },
+            ),
+        ];
+        let strikes = [50.0, 65.0, 80.0, 90.0, 100.0, 110.0, 125.0, 150.0, 200.0];
+        let quotes = quotes(&truths, &strikes);
+        for target in [FitTarget::Volatility, FitTarget::TotalVariance] {
+            let fits = calibrate(forward, &quotes, |_| 1.0, target).unwrap();
+            assert_eq!(fits.len(), 2);
+            for (fit, truth) in fits.iter().zip(&truths) {
+                let (result, expected) = (fit.smile.parameters(), truth.parameters());
+                assert_eq!(fit.smile.time_to_expire(), truth.time_to_expire());
+                assert!((result.a - expected.a).abs() < 1e-6);
+                assert!((result.b - expected.b).abs() < 1e-6);
+                assert!((result.rho - expected.rho).abs() < 1e-6);
+                assert!((result.m - expected.m).abs() < 1e-6);
+                assert!((result.sigma - expected.sigma).abs() < 1e-6);
+                assert!(fit.residuals.iter().all(|r| r.abs() < 1e-9));
+            }
+        }
+    }
+
+    #[test]
+    fn weights_favour_quotes() {
+        // a smile with an outlier, which the fit ignores when its weight vanishes
+        let truth = SviSmile::new(100.0, 1.0, RAW);
+        let strikes = [50.0, 65.0, 80.0, 90.0, 100.0, 110.0, 125.0, 150.0, 200.0];
+        let mut quotes = quotes(&[truth], &strikes);
+        quotes[4].volatility += 0.02;
+        let weight = |q: &VolQuote| if q.strike == 100.0 { 0.0 } else { 1.0 };
+        let fit = &calibrate(|_| 100.0, &quotes, weight, FitTarget::Volatility).unwrap()[0];
+        assert!((fit.residuals[4] + 0.02).abs() < 1e-6);
+        assert!((fit.smile.parameters().a - RAW.a).abs() < 1e-6);
+    }
+
+    #[test]
+    fn calibration_recovers_ssvi_parameters() {
+        let forward = |_| 100.0;
+        let smiles: Vec<SviSmile> = [(0.25, 0.01), (1.0, 0.04), (3.0, 0.1)]
+            .iter()
+            .map(|&(t, theta)| SviSmile::new(100.0, t, SURFACE.slice(theta)))
+            .collect();
+        let strikes = [60.0, 80.0, 90.0, 100.0, 110.0, 120.0, 150.0];
+        let quotes = quotes(&smiles, &strikes);
+        let fit = calibrate_ssvi(forward, &quotes, |_| 1.0, FitTarget::TotalVariance).unwrap();
+        assert!((fit.parameters.rho - SURFACE.rho).abs() < 1e-6);
+        assert!((fit.parameters.eta - SURFACE.eta).abs() < 1e-6);
+        assert!((fit.parameters.gamma - SURFACE.gamma).abs() < 1e-6);
+        assert_eq!(fit.smiles.len(), 3);
+        assert_eq!(fit.residuals.len(), quotes.len());
+        assert!(fit.residuals.iter().all(|r| r.abs() < 1e-10));
+    }
+
+    #[test]
+    fn err_with_negative_variance() {
+        let invalid = SviParameters { a: -0.1, ..RAW };
+        assert_eq!(
+            SviSmile::try_new(100.0, 1.0, invalid),
+            Err(MathError::ParameterOutOfBounds("a", -0.1))
+        );
+        let quotes = [VolQuote {
+            strike: 100.0,
+            time_to_expire: 1.0,
+            volatility: -0.2,
+        }];
+        assert_eq!(
+            calibrate(|_| 100.0, &quotes, |_| 1.0, FitTarget::Volatility),
+            Err(MathError::NonPositiveVolatility(-0.2))
+        );
+    }
+
+    #[test]
+    fn err_with_too_few_quotes() {
+        // the second expiration can't pin down five parameters
+        let smiles = [
+            SviSmile::new(100.0, 0.5, RAW),
+            SviSmile::new(100.0, 1.0, RAW),
+        ];
+        let mut quotes = quotes(&smiles, &[60.0, 80.0, 100.0, 120.0, 150.0]);
+        quotes.pop();
+        assert_eq!(
+            calibrate(|_| 100.0, &quotes, |_| 1.0, FitTarget::TotalVariance),
+            Err(MathError::TooFewQuotes(4))
+        );
+    }
+
+    #[test]
+    fn err_with_duplicate_strike() {
+        let smiles = [SviSmile::new(100.0, 0.5, RAW)];
+        let mut quotes = quotes(&smiles, &[60.0, 80.0, 100.0, 120.0, 150.0]);
+        quotes.push(VolQuote {
+            volatility: quotes[2].volatility + 0.01,
+            ..quotes[2]
+        });
+        assert_eq!(
+            calibrate(|_| 100.0, &quotes, |_| 1.0, FitTarget::TotalVariance),
+            Err(MathError::DuplicateStrike(100.0))
+        );
+    }
+}
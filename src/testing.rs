@@ -0,0 +1,61 @@
+use crate::{BlackScholesModel, Greeks};
+
+// assert_close compares values with an absolute tolerance
+pub(crate) fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (actual - expected).abs() < tolerance,
+        "{} is not within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+}
+
+// assert_relative compares values with a tolerance relative to the expected magnitude,
+// which turns absolute for magnitudes below 1
+pub(crate) fn assert_relative(actual: f64, expected: f64, tolerance: f64) {
+    assert_close(actual, expected, tolerance * expected.abs().max(1.0));
+}
+
+// central_diff bumps one input of the model up and down by h and returns
+// the finite-difference derivative of f
+pub(crate) fn central_diff<F: Fn(&BlackScholesModel) -> f64>(
+    model: &BlackScholesModel,
+    h: f64,
+    bump: fn(&mut BlackScholesModel, f64),
+    f: F,
+) -> f64 {
+    let mut up = *model;
+    bump(&mut up, h);
+    let mut down = *model;
+    bump(&mut down, -h);
+    (f(&up) - f(&down)) / (2.0 * h)
+}
+
+// finite_difference_greeks takes the greeks of any price of the market inputs of the
+// Black-Scholes model by central differences, the stock is bumped relative to its level
+pub(crate) fn finite_difference_greeks<P: Fn(&BlackScholesModel) -> f64>(
+    model: &BlackScholesModel,
+    price: P,
+) -> Greeks {
+    let h = 1e-4;
+    let h_stock = h * model.stock;
+    let mut up = *model;
+    up.stock += h_stock;
+    let mut down = *model;
+    down.stock -= h_stock;
+
+    Greeks {
+        delta: central_diff(model, h_stock, |m, h| m.stock += h, &price),
+        gamma: (price(&up) - 2.0 * price(model) + price(&down)) / h_stock.powi(2),
+        vega: central_diff(model, h, |m, h| m.volatility += h, &price),
+        theta: -central_diff(model, h, |m, h| m.time_to_expire += h, &price),
+        rho: central_diff(model, h, |m, h| m.interest_rate += h, &price),
+        dividend_rho: central_diff(
+            model,
+            h,
+            |m, h| m.dividend = Some(m.dividend.unwrap_or_default() + h),
+            &price,
+        ),
+    }
+}
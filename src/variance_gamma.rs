@@ -0,0 +1,232 @@
+use crate::complex::Complex;
+use crate::fourier::{CharacteristicFunction, Cos};
+use crate::{check_finite, BlackScholesModel, MathError, MathResult, OptionKind};
+use std::f64::consts::E;
+
+// VarianceGammaParameters describe the Brownian motion with drift theta and volatility
+// sigma which runs on a gamma clock of unit mean rate and variance rate nu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VarianceGammaParameters {
+    pub sigma: f64, // volatility of the subordinated Brownian motion
+    pub nu: f64,    // variance rate of the gamma time change (kurtosis)
+    pub theta: f64, // drift of the subordinated Brownian motion (skewness)
+}
+
+// VarianceGammaModel prices european options under the variance gamma process of
+// Madan, Carr and Chang (1998), a pure jump model without a diffusion part
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VarianceGammaModel {
+    opt: OptionKind,                     // option type (call or put)
+    strike: f64,                         // strike price ($$$ per share)
+    stock: f64,                          // underlying price ($$$ per share)
+    interest_rate: f64,                  // continuously compounded risk-free rate (% p.a.)
+    time_to_expire: f64,                 // time to expiration (% of year)
+    dividend: Option<f64>,               // continuously compounded dividend yield (% p.a.)
+    parameters: VarianceGammaParameters, // variance gamma process
+}
+
+impl VarianceGammaModel {
+    pub fn new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+        parameters: VarianceGammaParameters,
+    ) -> VarianceGammaModel {
+        VarianceGammaModel {
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            time_to_expire,
+            dividend,
+            parameters,
+        }
+    }
+
+    // try_new creates the model like new does, but rejects parameters for which the
+    // process is undefined or the stock has no finite expectation, which needs
+    // 1 - theta nu - sigma^2 nu / 2 > 0
+    pub fn try_new(
+        opt: OptionKind,
+        strike: f64,
+        stock: f64,
+        interest_rate: f64,
+        time_to_expire: f64,
+        dividend: Option<f64>,
+        parameters: VarianceGammaParameters,
+    ) -> MathResult<VarianceGammaModel> {
+        let VarianceGammaParameters { sigma, nu, theta } = parameters;
+        for &(name, value) in &[("sigma", sigma), ("nu", nu), ("theta", theta)] {
+            check_finite(name, value)?;
+        }
+        if sigma <= 0.0 {
+            return Err(MathError::ParameterOutOfBounds("sigma", sigma));
+        }
+        if nu <= 0.0 {
+            return Err(MathError::ParameterOutOfBounds("nu", nu));
+        }
+        if 1.0 - theta * nu - 0.5 * sigma * sigma * nu <= 0.0 {
+            return Err(MathError::ParameterOutOfBounds("theta", theta));
+        }
+        BlackScholesModel::try_new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            sigma,
+            time_to_expire,
+            dividend,
+        )?;
+        Ok(VarianceGammaModel::new(
+            opt,
+            strike,
+            stock,
+            interest_rate,
+            time_to_expire,
+            dividend,
+            parameters,
+        ))
+    }
+
+    pub fn parameters(&self) -> VarianceGammaParameters {
+        self.parameters
+    }
+
+    // price calculates the fair value of the option ($$$ per share) with the COS method
+    pub fn price(&self) -> MathResult {
+        Ok(Cos::default().prices(self, self.opt, &[self.strike])?[0])
+    }
+}
+
+impl CharacteristicFunction for VarianceGammaModel {
+    // characteristic_function needs 1 - theta nu - sigma^2 nu / 2 > 0, otherwise the
+    // stock has no finite expectation
+    fn characteristic_function(&self, u: Complex) -> Complex {
+        let VarianceGammaParameters { sigma, nu, theta } = self.parameters;
+        let t = self.time_to_expire;
+        let iu = Complex::I * u;
+        // martingale correction
+        let omega = (1.0 - theta * nu - 0.5 * sigma * sigma * nu).ln() / nu;
+        let base = 1.0 - iu * theta * nu + 0.5 * sigma * sigma * nu * u * u;
+        (iu * omega * t - t / nu * base.ln()).exp()
+    }
+
+    fn forward(&self) -> f64 {
+        let carry = self.interest_rate - self.dividend.unwrap_or_default();
+        self.stock * E.powf(carry * self.time_to_expire)
+    }
+
+    fn discount(&self) -> f64 {
+        E.powf(-self.interest_rate * self.time_to_expire)
+    }
+
+    fn cumulants(&self) -> (f64, f64, f64) {
+        let VarianceGammaParameters { sigma, nu, theta } = self.parameters;
+        let t = self.time_to_expire;
+        let omega = (1.0 - theta * nu - 0.5 * sigma * sigma * nu).ln() / nu;
+        let (s2, t2) = (sigma * sigma, theta * theta);
+        (
+            (omega + theta) * t,
+            (s2 + nu * t2) * t,
+            3.0 * (s2 * s2 * nu + 2.0 * t2 * t2 * nu.powi(3) + 4.0 * s2 * t2 * nu * nu) * t,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fourier::CarrMadan;
+    use crate::BlackScholesModel;
+
+    // Fang and Oosterlee (2008), table 7: S = 100, K = 90, r = 10%, sigma = 0.12,
+    // nu = 0.2, theta = -0.14
+    const FANG_OOSTERLEE: VarianceGammaParameters = VarianceGammaParameters {
+        sigma: 0.12,
+        nu: 0.2,
+        theta: -0.14,
+    };
+
+    fn call(time_to_expire: f64) -> VarianceGammaModel {
+        VarianceGammaModel::new(
+            OptionKind::Call,
+            90.0,
+            100.0,
+            0.1,
+            time_to_expire,
+            None,
+            FANG_OOSTERLEE,
+        )
+    }
+
+    #[test]
+    fn call_prices() {
+        for &(time_to_expire, expected) in &[(0.1, 10.993703187), (1.0, 19.099354724)] {
+            let result = call(time_to_expire).price().unwrap();
+            assert!(
+                (result - expected).abs() < 1e-6,
+                "{} is not {}",
+                result,
+                expected
+            );
+            let fft = CarrMadan::default()
+                .prices(&call(time_to_expire), OptionKind::Call, &[90.0])
+                .unwrap();
+            assert!((fft[0] - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn small_variance_rate_is_black_scholes() {
+        let parameters = VarianceGammaParameters {
+            sigma: 0.2,
+            nu: 1e-6,
+            theta: 0.0,
+        };
+        for opt in [OptionKind::Call, OptionKind::Put] {
+            let model =
+                VarianceGammaModel::new(opt, 95.0, 100.0, 0.03, 0.5, Some(0.01), parameters);
+            let expected = BlackScholesModel::new(opt, 95.0, 100.0, 0.03, 0.2, 0.5, Some(0.01))
+                .price()
+                .unwrap();
+            assert!((model.price().unwrap() - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn err_with_invalid_parameters() {
+        let model = |parameters| {
+            VarianceGammaModel::try_new(OptionKind::Call, 90.0, 100.0, 0.1, 1.0, None, parameters)
+        };
+        assert!(model(FANG_OOSTERLEE).is_ok());
+        // the stock would have no finite expectation
+        let explosive = VarianceGammaParameters {
+            sigma: 0.5,
+            nu: 5.0,
+            theta: 0.3,
+        };
+        assert_eq!(
+            model(explosive),
+            Err(MathError::ParameterOutOfBounds("theta", 0.3))
+        );
+        let invalid = VarianceGammaParameters {
+            nu: 0.0,
+            ..FANG_OOSTERLEE
+        };
+        assert_eq!(
+            model(invalid),
+            Err(MathError::ParameterOutOfBounds("nu", 0.0))
+        );
+        let invalid = VarianceGammaParameters {
+            sigma: -0.1,
+            ..FANG_OOSTERLEE
+        };
+        assert_eq!(
+            model(invalid),
+            Err(MathError::ParameterOutOfBounds("sigma", -0.1))
+        );
+    }
+}